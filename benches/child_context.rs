@@ -0,0 +1,51 @@
+//! Cost of creating and dropping child contexts. Run with `cargo bench --bench child_context`.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use tokio_tree_context::Context;
+
+const ITERATIONS: u32 = 1_000_000;
+
+fn measure(name: &str, mut op: impl FnMut()) {
+    for _ in 0..ITERATIONS / 10 {
+        op();
+    }
+    let started = Instant::now();
+    for _ in 0..ITERATIONS {
+        op();
+    }
+    let per_op: Duration = started.elapsed() / ITERATIONS;
+    println!("{:<40} {:>8?}/op", name, per_op);
+}
+
+fn main() {
+    let mut root = Context::new();
+    measure("create and drop unused child", || {
+        black_box(root.new_child_context());
+    });
+
+    let mut parent = root.new_child_context();
+    let _siblings: Vec<Context> = (0..10_000).map(|_| parent.new_child_context()).collect();
+    measure("create and drop child with 10k siblings", || {
+        black_box(parent.new_child_context());
+    });
+
+    let mut shards = root.new_child_context();
+    let started = Instant::now();
+    for _ in 0..1_000 {
+        black_box((0..512).map(|_| shards.new_child_context()).collect::<Vec<_>>());
+    }
+    println!("{:<40} {:>8?}/op", "create 512 children one by one", started.elapsed() / 1_000);
+    let started = Instant::now();
+    for _ in 0..1_000 {
+        black_box(shards.new_child_contexts(512));
+    }
+    println!("{:<40} {:>8?}/op", "create 512 children in bulk", started.elapsed() / 1_000);
+
+    let runtime = tokio::runtime::Builder::new_multi_thread().build().unwrap();
+    runtime.block_on(async {
+        measure("create child and spawn one task", || {
+            let mut child = root.new_child_context();
+            black_box(child.spawn(async {}));
+        });
+    });
+}
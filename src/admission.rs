@@ -0,0 +1,234 @@
+use std::fmt;
+use std::future::Future;
+use std::panic::Location;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{Context, ContextId, ContextInner, TaskOptions};
+
+/// Decision of an `AdmissionHook` about a task that is about to be spawned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Admission {
+    /// Launch the task right away
+    Admit,
+    /// Do not launch the task
+    Reject(String),
+    /// Launch the task, but only start running it after the given delay
+    Delay(Duration),
+}
+
+/// What an `AdmissionHook` knows about the task that is about to be spawned
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TaskMeta {
+    /// Name of the task, if it was spawned with a name
+    pub name: Option<String>,
+    /// Number of tasks currently alive under the context the task is spawned on
+    pub live_tasks: usize,
+    /// Set with `Context::try_spawn_with_priority`, its meaning is up to the hook
+    pub priority: Option<i32>,
+}
+
+/// Load shedding point consulted before a task is spawned.
+///
+/// Any `Fn(&TaskMeta) -> Admission + Send + Sync` closure is an admission hook.
+///
+/// Only synchronous hooks are supported: the decision is made before `try_spawn` returns, so a rejection can be
+/// reported as its error. The hook runs on the spawning thread and should be cheap. Decisions that need IO, such as
+/// asking a remote rate limiter, are better made before spawning, or inside the task itself.
+pub trait AdmissionHook: Send + Sync {
+    /// Decide whether the task may be spawned
+    fn admit(&self, meta: &TaskMeta) -> Admission;
+}
+
+impl<F> AdmissionHook for F
+where
+    F: Fn(&TaskMeta) -> Admission + Send + Sync,
+{
+    fn admit(&self, meta: &TaskMeta) -> Admission {
+        self(meta)
+    }
+}
+
+/// Reason a task could not be spawned
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SpawnError {
+    /// The context is already cancelled
+    Cancelled,
+    /// The admission hook rejected the task with the given reason
+    Rejected(String),
+    /// No semaphore permit became available in time, see `Context::spawn_with_semaphore_timeout`
+    AcquireTimeout,
+    /// A task with this name already exists, see `NamedGroup::spawn`
+    DuplicateName(String),
+    /// The deadline of the context already passed, see `ContextBuilder::inherit_deadline`
+    DeadlineExceeded,
+    /// The context and everything running under it are gone, see `ContextRef::spawn`
+    ScopeClosed,
+    /// The context has no ancestor with this id, see `CancelScope::Ancestor`
+    UnknownAncestor(ContextId),
+    /// The task costs more than the whole capacity of the context, see `Context::spawn_weighted`
+    ExceedsCapacity { cost: u32, capacity: u32 },
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpawnError::Cancelled => write!(f, "context is cancelled"),
+            SpawnError::Rejected(reason) => write!(f, "task rejected: {}", reason),
+            SpawnError::AcquireTimeout => write!(f, "timed out waiting for a permit"),
+            SpawnError::DuplicateName(name) => write!(f, "duplicate task name: {}", name),
+            SpawnError::DeadlineExceeded => write!(f, "deadline exceeded"),
+            SpawnError::ScopeClosed => write!(f, "context is gone"),
+            SpawnError::UnknownAncestor(id) => write!(f, "no ancestor context with id {}", id),
+            SpawnError::ExceedsCapacity { cost, capacity } => write!(f, "task cost {} exceeds capacity {}", cost, capacity),
+        }
+    }
+}
+
+impl std::error::Error for SpawnError {}
+
+impl ContextInner {
+    /// The hook of this context, or of the nearest ancestor that has one
+    fn admission_hook(&self) -> Option<Arc<dyn AdmissionHook>> {
+        if let Some(hook) = self.admission_hook.lock().unwrap().as_ref() {
+            return Some(hook.clone());
+        }
+        self.parent.as_ref().and_then(|parent| parent.admission_hook())
+    }
+
+    pub(crate) fn admit(&self, name: Option<String>, priority: Option<i32>) -> Admission {
+        match self.admission_hook() {
+            Some(hook) => hook.admit(&TaskMeta {
+                name,
+                live_tasks: self.active_tasks.load(std::sync::atomic::Ordering::SeqCst),
+                priority,
+            }),
+            None => Admission::Admit,
+        }
+    }
+}
+
+impl Context {
+    /// Install a hook that is consulted before every task spawned on this context is launched.
+    ///
+    /// Child contexts use the hook of their nearest ancestor unless they install their own. The hook is never called
+    /// once the context is cancelled.
+    /// ```rust, no_run
+    /// use tokio_tree_context::{Admission, Context, TaskMeta};
+    ///
+    /// let mut ctx = Context::new();
+    /// ctx.set_admission_hook(|meta: &TaskMeta| {
+    ///     if meta.live_tasks >= 100 {
+    ///         Admission::Reject("too busy".into())
+    ///     } else {
+    ///         Admission::Admit
+    ///     }
+    /// });
+    /// ```
+    pub fn set_admission_hook<H: AdmissionHook + 'static>(&mut self, hook: H) {
+        *self.inner.admission_hook.lock().unwrap() = Some(Arc::new(hook));
+    }
+
+    /// Spawn a task, reporting an error instead of silently not running the task when the context is cancelled or
+    /// the admission hook rejects it.
+    ///
+    /// A `Delay` decision of the admission hook is applied inside the spawned task, before the future is first polled.
+    #[track_caller]
+    pub fn try_spawn<T>(&mut self, future: T) -> Result<tokio::task::JoinHandle<Option<T::Output>>, SpawnError>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        self.spawn_task(None, future, None)
+    }
+
+    /// Same as `try_spawn`, but the admission hook sees the given task name
+    #[track_caller]
+    pub fn try_spawn_named<T>(&mut self, name: impl Into<String>, future: T) -> Result<tokio::task::JoinHandle<Option<T::Output>>, SpawnError>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        self.spawn_task(Some(name.into()), future, None)
+    }
+
+    /// Same as `try_spawn`, but the admission hook sees the given priority
+    #[track_caller]
+    pub fn try_spawn_with_priority<T>(&mut self, priority: i32, future: T) -> Result<tokio::task::JoinHandle<Option<T::Output>>, SpawnError>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        let options = TaskOptions { priority: Some(priority), ..Default::default() };
+        Ok(self.inner.spawn(self.inner.task_future_at(None, future, Location::caller(), options)?))
+    }
+
+    /// Spawn a named task. If the admission hook rejects it, the returned handle resolves to None, as with `spawn`.
+    #[track_caller]
+    pub fn spawn_named<T>(&mut self, name: impl Into<String>, future: T) -> tokio::task::JoinHandle<Option<T::Output>>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        self.inner.spawn_or_none(self.inner.task_future(Some(name.into()), future, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn hook_rejects_delays_and_is_inherited() {
+        let mut ctx = Context::new();
+        ctx.set_admission_hook(|meta: &TaskMeta| match meta.name.as_deref() {
+            Some("background") if meta.live_tasks > 0 => Admission::Reject("busy".into()),
+            Some("slow") => Admission::Delay(Duration::from_secs(1)),
+            _ => Admission::Admit,
+        });
+        let mut child = ctx.new_child_context();
+
+        let running = child.spawn(std::future::pending::<()>());
+        assert!(child.try_spawn_named("background", async {}).is_err());
+        assert!(ctx.try_spawn_named("background", async {}).is_ok());
+        assert_eq!(child.spawn_named("background", async { 1 }).await.unwrap(), None);
+
+        let started = tokio::time::Instant::now();
+        let slow = child.try_spawn_named("slow", async { 2 }).unwrap();
+        assert_eq!(slow.await.unwrap(), Some(2));
+        assert!(started.elapsed() >= Duration::from_secs(1));
+
+        child.set_admission_hook(|_: &TaskMeta| Admission::Admit);
+        assert!(child.try_spawn_named("background", async {}).is_ok());
+
+        drop(ctx);
+        assert_eq!(running.await.unwrap(), None);
+        assert_eq!(child.try_spawn(async {}).unwrap_err(), SpawnError::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn rejections_are_counted_and_published() {
+        let mut ctx = Context::new();
+        ctx.set_admission_hook(|meta: &TaskMeta| match meta.priority {
+            Some(priority) if priority < 0 => Admission::Reject("low priority".into()),
+            _ => Admission::Admit,
+        });
+        let mut events = ctx.subscribe_events();
+        assert!(ctx.try_spawn_with_priority(1, async {}).is_ok());
+        assert_eq!(ctx.try_spawn_with_priority(-1, async {}).unwrap_err(), SpawnError::Rejected("low priority".into()));
+        ctx.set_admission_hook(|_: &TaskMeta| Admission::Reject("busy".into()));
+        assert_eq!(ctx.spawn(async { 1 }).await.unwrap(), None);
+
+        let counts = ctx.stats_epoch().counts;
+        assert_eq!((counts.spawned, counts.rejected), (1, 2));
+        let mut rejected = Vec::new();
+        while rejected.len() < 2 {
+            if let Some(crate::ContextEvent::TaskRejected { error, .. }) = events.recv().await {
+                rejected.push(error);
+            }
+        }
+        assert_eq!(rejected, vec![SpawnError::Rejected("low priority".into()), SpawnError::Rejected("busy".into())]);
+    }
+}
@@ -0,0 +1,203 @@
+use std::fmt;
+use tokio::task::JoinHandle;
+
+use crate::Context;
+
+/// A set of CPUs, as a bitmask of CPUs 0 to 63
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct CpuSet(pub u64);
+
+impl CpuSet {
+    /// The set with only `cpu`
+    pub fn single(cpu: u32) -> CpuSet {
+        CpuSet::default().with(cpu)
+    }
+
+    /// This set with `cpu` added
+    pub fn with(self, cpu: u32) -> CpuSet {
+        assert!(cpu < 64, "CpuSet holds CPUs 0 to 63, got {}", cpu);
+        CpuSet(self.0 | (1 << cpu))
+    }
+
+    pub fn contains(&self, cpu: u32) -> bool {
+        cpu < 64 && self.0 & (1 << cpu) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    fn cpus(self) -> impl Iterator<Item = u32> {
+        (0..64).filter(move |cpu| self.contains(*cpu))
+    }
+}
+
+impl fmt::Display for CpuSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cpus: Vec<_> = self.cpus().map(|cpu| cpu.to_string()).collect();
+        write!(f, "{{{}}}", cpus.join(","))
+    }
+}
+
+/// Returned by `Context::spawn_with_affinity` when threads cannot be pinned to the CPUs, either because the platform
+/// does not support affinity or because none of the CPUs is available to the process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AffinityNotSupported {
+    pub cpus: CpuSet,
+}
+
+impl fmt::Display for AffinityNotSupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot pin threads to CPUs {}", self.cpus)
+    }
+}
+
+impl std::error::Error for AffinityNotSupported {}
+
+impl Context {
+    /// Run `closure` on a thread of the blocking pool, pinned to the CPUs of `cpu_set` while it runs. The thread gets
+    /// its previous affinity back afterwards.
+    ///
+    /// The task counts as a task of this context. A closure that has started keeps running when the context is
+    /// cancelled, but the handle resolves to None right away.
+    /// ```rust, no_run
+    /// use tokio_tree_context::{Context, CpuSet};
+    ///
+    /// # fn checksum(_: &[u8]) -> u64 { 0 }
+    /// # async fn example(mut ctx: Context, block: Vec<u8>) {
+    /// let numa_node = CpuSet::single(0).with(1).with(2).with(3);
+    /// match ctx.spawn_with_affinity(numa_node, move || checksum(&block)) {
+    ///     Ok(handle) => println!("{:?}", handle.await),
+    ///     Err(e) => eprintln!("{e}"),
+    /// }
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_affinity<F, R>(&mut self, cpu_set: CpuSet, closure: F) -> Result<JoinHandle<Option<R>>, AffinityNotSupported>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        if cpu_set.is_empty() || !platform::can_pin(cpu_set) {
+            return Err(AffinityNotSupported { cpus: cpu_set });
+        }
+        let runtime = self.inner.runtime();
+        Ok(self.spawn(async move {
+            let pinned = runtime.spawn_blocking(move || {
+                let previous = platform::pin(cpu_set);
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(closure));
+                platform::restore(previous);
+                result
+            });
+            match pinned.await {
+                Ok(Ok(result)) => result,
+                Ok(Err(payload)) => std::panic::resume_unwind(payload),
+                Err(error) => std::panic::resume_unwind(error.into_panic()),
+            }
+        }))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::CpuSet;
+
+    fn current() -> Option<libc::cpu_set_t> {
+        // SAFETY: cpu_set_t is plain data, and the size passed is the size of the set
+        unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            (libc::pthread_getaffinity_np(libc::pthread_self(), std::mem::size_of::<libc::cpu_set_t>(), &mut set) == 0)
+                .then_some(set)
+        }
+    }
+
+    fn set(set: &libc::cpu_set_t) {
+        // SAFETY: the set is initialised and the size passed is its size
+        unsafe {
+            libc::pthread_setaffinity_np(libc::pthread_self(), std::mem::size_of::<libc::cpu_set_t>(), set);
+        }
+    }
+
+    /// True if at least one of the CPUs is available to the calling thread
+    pub(super) fn can_pin(cpus: CpuSet) -> bool {
+        // SAFETY: CPU_ISSET only reads the initialised set
+        current().is_some_and(|allowed| cpus.cpus().any(|cpu| unsafe { libc::CPU_ISSET(cpu as usize, &allowed) }))
+    }
+
+    pub(super) fn pin(cpus: CpuSet) -> Option<libc::cpu_set_t> {
+        let previous = current();
+        // SAFETY: the set is zeroed before the CPUs are added
+        let pinned = unsafe {
+            let mut pinned: libc::cpu_set_t = std::mem::zeroed();
+            cpus.cpus().for_each(|cpu| libc::CPU_SET(cpu as usize, &mut pinned));
+            pinned
+        };
+        set(&pinned);
+        previous
+    }
+
+    pub(super) fn restore(previous: Option<libc::cpu_set_t>) {
+        if let Some(previous) = previous {
+            set(&previous);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::CpuSet;
+    use std::ffi::c_void;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentThread() -> *mut c_void;
+        fn SetThreadAffinityMask(thread: *mut c_void, mask: usize) -> usize;
+    }
+
+    pub(super) fn can_pin(cpus: CpuSet) -> bool {
+        usize::try_from(cpus.0).is_ok()
+    }
+
+    pub(super) fn pin(cpus: CpuSet) -> usize {
+        // SAFETY: the pseudo handle of the current thread is always valid
+        unsafe { SetThreadAffinityMask(GetCurrentThread(), cpus.0 as usize) }
+    }
+
+    pub(super) fn restore(previous: usize) {
+        if previous != 0 {
+            // SAFETY: see `pin`
+            unsafe {
+                SetThreadAffinityMask(GetCurrentThread(), previous);
+            }
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod platform {
+    use super::CpuSet;
+
+    pub(super) fn can_pin(_: CpuSet) -> bool {
+        false
+    }
+
+    pub(super) fn pin(_: CpuSet) {}
+
+    pub(super) fn restore(_: ()) {}
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn closure_runs_on_the_pinned_cpu() {
+        let mut ctx = Context::new();
+        let cpu = (0..64).find(|cpu| platform::can_pin(CpuSet::single(*cpu))).unwrap();
+        // SAFETY: sched_getcpu has no preconditions
+        let pinned = ctx.spawn_with_affinity(CpuSet::single(cpu), || unsafe { libc::sched_getcpu() });
+        assert_eq!(pinned.unwrap().await.unwrap(), Some(cpu as i32));
+        assert_eq!(ctx.spawn_with_affinity(CpuSet::default(), || ()).err(), Some(AffinityNotSupported { cpus: CpuSet::default() }));
+        assert_eq!(CpuSet::single(1).with(3).to_string(), "{1,3}");
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::{Duration, Instant};
+
+use crate::Context;
+
+impl Context {
+    /// Block the current thread until the context is cancelled. Returns right away if it already is.
+    ///
+    /// For plain threads such as a GUI event loop: no runtime is needed, and the thread sleeps instead of polling.
+    /// Never call this from async code, it blocks the runtime thread.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut root = Context::new();
+    /// let worker = root.new_child_context();
+    /// std::thread::spawn(move || {
+    ///     worker.cancelled_blocking();
+    ///     println!("shutting down the legacy poller");
+    /// });
+    /// ```
+    pub fn cancelled_blocking(&self) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.blocking_waiters += 1;
+        while state.cause.is_none() {
+            state = self.inner.cancel_condvar.wait(state).unwrap();
+        }
+        state.blocking_waiters -= 1;
+    }
+
+    /// `cancelled_blocking` that gives up after `timeout`. Returns whether the context is cancelled.
+    pub fn cancelled_blocking_timeout(&self, timeout: Duration) -> bool {
+        let until = Instant::now() + timeout;
+        let mut state = self.inner.state.lock().unwrap();
+        state.blocking_waiters += 1;
+        while state.cause.is_none() {
+            let remaining = until.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            state = self.inner.cancel_condvar.wait_timeout(state, remaining).unwrap().0;
+        }
+        state.blocking_waiters -= 1;
+        state.cause.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn threads_block_until_cancelled() {
+        let mut root = Context::new();
+        let child = Arc::new(root.new_child_context());
+        assert!(!child.cancelled_blocking_timeout(Duration::from_millis(10)));
+        let waiters: Vec<_> = (0..4)
+            .map(|_| {
+                let child = child.clone();
+                std::thread::spawn(move || child.cancelled_blocking())
+            })
+            .collect();
+        drop(root);
+        waiters.into_iter().for_each(|waiter| waiter.join().unwrap());
+        assert!(child.cancelled_blocking_timeout(Duration::ZERO));
+    }
+}
@@ -0,0 +1,136 @@
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::{broadcast, Notify};
+use tokio::time::Instant;
+
+use crate::{CancellationCause, Context, ContextInner};
+
+/// Monitor wakes up at most this often to check the budget
+const GRANULARITY: Duration = Duration::from_millis(10);
+
+/// Cumulative run time budget of the tasks of a context
+pub(crate) struct TimeBudget {
+    limit: Duration,
+    state: Mutex<BudgetState>,
+    changed: Notify,
+}
+
+struct BudgetState {
+    /// Run time used up to `since`
+    spent: Duration,
+    /// Tasks running since `since`
+    running: u32,
+    since: Instant,
+}
+
+impl BudgetState {
+    fn accrue(&mut self) {
+        let now = Instant::now();
+        self.spent += (now - self.since) * self.running;
+        self.since = now;
+    }
+}
+
+impl TimeBudget {
+    pub(crate) fn new(limit: Duration) -> TimeBudget {
+        TimeBudget {
+            limit,
+            state: Mutex::new(BudgetState {
+                spent: Duration::ZERO,
+                running: 0,
+                since: Instant::now(),
+            }),
+            changed: Notify::new(),
+        }
+    }
+
+    pub(crate) fn task_started(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.accrue();
+        state.running += 1;
+        self.changed.notify_one();
+    }
+
+    pub(crate) fn task_finished(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.accrue();
+        state.running -= 1;
+        self.changed.notify_one();
+    }
+
+    /// Remaining budget and the number of tasks currently using it
+    fn snapshot(&self) -> (Duration, u32) {
+        let mut state = self.state.lock().unwrap();
+        state.accrue();
+        (self.limit.saturating_sub(state.spent), state.running)
+    }
+}
+
+/// Cancel the context once its budget is used up. Exits when the context is cancelled or gone.
+pub(crate) async fn monitor(inner: Weak<ContextInner>, budget: Arc<TimeBudget>, mut cancel_receiver: broadcast::Receiver<()>) {
+    loop {
+        let changed = budget.changed.notified();
+        let (remaining, running) = budget.snapshot();
+        match inner.upgrade() {
+            Some(inner) if inner.is_cancelled() => return,
+            Some(inner) if remaining.is_zero() => {
+                inner.cancel(CancellationCause::BudgetExhausted);
+                return;
+            }
+            Some(_) => {}
+            None => return,
+        }
+        let exhausted_in = if running == 0 {
+            None
+        } else {
+            Some((remaining / running).max(GRANULARITY))
+        };
+        tokio::select! {
+            _ = changed => {},
+            _ = tokio::time::sleep(exhausted_in.unwrap_or_default()), if exhausted_in.is_some() => {},
+            _ = cancel_receiver.recv() => return,
+        }
+    }
+}
+
+impl Context {
+    /// Run time left in the time budget of this context, or None if the context has no budget
+    pub fn budget_remaining(&self) -> Option<Duration> {
+        self.inner.budget.as_ref().map(|budget| budget.snapshot().0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn budget_sums_concurrent_tasks() {
+        let mut ctx = Context::builder().time_budget(Duration::from_secs(10)).build();
+        let first = ctx.spawn(std::future::pending::<()>());
+        let second = ctx.spawn(std::future::pending::<()>());
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(ctx.budget_remaining(), Some(Duration::from_secs(6)));
+        assert!(!ctx.is_cancelled());
+
+        // two tasks use the remaining six seconds in three
+        tokio::time::sleep(Duration::from_millis(3020)).await;
+        assert!(ctx.is_cancelled());
+        assert_eq!(ctx.cancellation_cause(), Some(CancellationCause::BudgetExhausted));
+        assert_eq!(first.await.unwrap(), None);
+        assert_eq!(second.await.unwrap(), None);
+        assert_eq!(ctx.budget_remaining(), Some(Duration::ZERO));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tasks_waiting_for_capacity_use_no_budget() {
+        let mut ctx = Context::builder().time_budget(Duration::from_secs(10)).capacity(1).build();
+        let first = ctx.spawn(tokio::time::sleep(Duration::from_secs(2)));
+        let _second = ctx.spawn(std::future::pending::<()>());
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(ctx.budget_remaining(), Some(Duration::from_secs(8)));
+        assert_eq!(first.await.unwrap(), Some(()));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(ctx.budget_remaining(), Some(Duration::from_secs(7)));
+    }
+}
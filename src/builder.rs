@@ -0,0 +1,161 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::stall::StallHandler;
+use crate::{Context, MemoryLimitAction, PanicPolicy, StalledTask};
+
+/// Configures a new context before it is created. Obtained with `Context::builder()`.
+///
+/// ```rust, no_run
+/// use std::time::Duration;
+/// use tokio_tree_context::Context;
+///
+/// let mut root = Context::new();
+/// let mut batch = Context::builder()
+///     .time_budget(Duration::from_secs(600))
+///     .build_child(&mut root);
+/// ```
+#[derive(Default)]
+pub struct ContextBuilder {
+    pub(crate) name: Option<Arc<str>>,
+    pub(crate) time_budget: Option<Duration>,
+    pub(crate) deadline: Option<Instant>,
+    /// Set by `timeout`, so `Context::is_timed_out` can tell the deadline came from a timeout
+    pub(crate) deadline_is_timeout: bool,
+    pub(crate) idle_timeout: Option<Duration>,
+    pub(crate) idle_includes_descendants: bool,
+    pub(crate) stall_threshold: Option<Duration>,
+    pub(crate) on_stall: Option<StallHandler>,
+    pub(crate) panic_policy: PanicPolicy,
+    pub(crate) inherit_deadline: bool,
+    pub(crate) capacity: Option<u32>,
+    pub(crate) memory_limit: Option<(u64, MemoryLimitAction)>,
+    pub(crate) cancel_batch_size: Option<usize>,
+    /// Set by `Context::with_owned_runtime`
+    pub(crate) runtime: Option<tokio::runtime::Handle>,
+}
+
+impl ContextBuilder {
+    /// Name the context, shown in its `tree()` snapshot
+    pub fn name(mut self, name: impl Into<Arc<str>>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Limit the total run time of all tasks of the context.
+    ///
+    /// The run time of concurrently running tasks adds up: two tasks running for one second use two seconds of the
+    /// budget. Once the budget is used up the context cancels itself with `CancellationCause::BudgetExhausted`.
+    /// Contexts with a time budget must be built inside a tokio runtime.
+    pub fn time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// Cancel the context with `CancellationCause::Deadline` once `deadline` is reached.
+    ///
+    /// Contexts with a deadline must be built inside a tokio runtime.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self.deadline_is_timeout = false;
+        self
+    }
+
+    /// Cancel the context with `CancellationCause::Deadline` once `timeout` has passed. Unlike a plain deadline, the
+    /// context then reports `is_timed_out()` rather than `is_deadline_exceeded()`.
+    ///
+    /// Contexts with a timeout must be built inside a tokio runtime.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(Instant::now() + timeout);
+        self.deadline_is_timeout = true;
+        self
+    }
+
+    /// Cancel the context with `CancellationCause::Idle` once it has had no live tasks for `timeout`.
+    ///
+    /// The timer starts when the context is created and restarts whenever its last live task finishes. Contexts with
+    /// an idle timeout must be built inside a tokio runtime.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Whether tasks of descendant contexts keep the context from becoming idle. Off by default.
+    pub fn idle_includes_descendants(mut self, include: bool) -> Self {
+        self.idle_includes_descendants = include;
+        self
+    }
+
+    /// Treat tasks of this context that have not been polled for longer than `threshold` as stalled.
+    ///
+    /// Stalled tasks are listed by `Context::stalled_tasks` and in the `tree()` snapshot, and each stall is counted in
+    /// `stats()` and published as `ContextEvent::TaskStalled`. A task that is legitimately waiting (a long sleep, a
+    /// quiet socket) is not polled either, so pick a threshold above the longest expected wait. Tracking costs one
+    /// relaxed atomic store per poll, the time since the last poll is measured to within half the threshold.
+    pub fn stall_threshold(mut self, threshold: Duration) -> Self {
+        self.stall_threshold = Some(threshold);
+        self
+    }
+
+    /// Call `handler` once each time a task of this context starts stalling. Requires `stall_threshold`.
+    pub fn on_stall<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&StalledTask) + Send + Sync + 'static,
+    {
+        self.on_stall = Some(Arc::new(handler));
+        self
+    }
+
+    /// What to do when a task of the context panics. Defaults to `PanicPolicy::Ignore`.
+    pub fn panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.panic_policy = policy;
+        self
+    }
+
+    /// Bound every task of the context by the remaining time until its effective deadline, the earliest deadline of
+    /// the context and its ancestors. Off by default.
+    ///
+    /// Tasks then time out at the deadline instead of being cancelled with the context, explicit task timeouts are
+    /// clamped to the deadline, and spawning after the deadline fails with `SpawnError::DeadlineExceeded`.
+    pub fn inherit_deadline(mut self, inherit: bool) -> Self {
+        self.inherit_deadline = inherit;
+        self
+    }
+
+    /// Let the tasks of the context hold at most `units` units at once. `Context::spawn_weighted` picks the cost of a
+    /// task, other spawns cost one unit. Tasks of child contexts do not count.
+    pub fn capacity(mut self, units: u32) -> Self {
+        self.capacity = Some(units);
+        self
+    }
+
+    /// Cap the bytes reserved with `Context::reserve_memory` in the context and its descendants at `bytes`.
+    /// `action` picks what a reservation past the cap does.
+    pub fn memory_limit(mut self, bytes: u64, action: MemoryLimitAction) -> Self {
+        self.memory_limit = Some((bytes, action));
+        self
+    }
+
+    /// Wake the waiters of a cancellation of this context `size` contexts at a time, yielding to the runtime between
+    /// batches. Descendants inherit the setting.
+    ///
+    /// The whole subtree is marked cancelled right away either way, so `is_cancelled` is true everywhere as soon as
+    /// `cancel` returns. Only waking the tasks that wait for the cancellation is spread out, so that cancelling a very
+    /// wide tree does not hold up the worker that cancels it. By default all waiters are woken before `cancel` returns.
+    /// Without a runtime, all batches are woken right away.
+    pub fn cancel_batch_size(mut self, size: usize) -> Self {
+        self.cancel_batch_size = Some(size.max(1));
+        self
+    }
+
+    /// Create a root context
+    pub fn build(self) -> Context {
+        Context::create(None, self)
+    }
+
+    /// Create a child context of `parent`
+    pub fn build_child(self, parent: &mut Context) -> Context {
+        Context::create(Some(&parent.inner), self)
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+
+use crate::Context;
+
+/// Reason `Context::call` did not return a response
+#[derive(Debug, PartialEq, Eq)]
+pub enum CallError<Req> {
+    /// The context was cancelled or its deadline passed before the request could be sent. The request is handed back.
+    NotSent(Req),
+    /// The receiving side of the channel is gone. The request is handed back.
+    Closed(Req),
+    /// The request was sent, but the context was cancelled before the response arrived
+    Cancelled,
+    /// The request was sent, but the deadline of the context passed before the response arrived
+    DeadlineExceeded,
+    /// The responder dropped the reply sender without responding
+    NoResponse,
+}
+
+impl<Req> CallError<Req> {
+    /// The request, if it was never sent
+    pub fn into_request(self) -> Option<Req> {
+        match self {
+            CallError::NotSent(request) | CallError::Closed(request) => Some(request),
+            _ => None,
+        }
+    }
+}
+
+impl<Req> fmt::Display for CallError<Req> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::NotSent(_) => write!(f, "context is cancelled, request not sent"),
+            CallError::Closed(_) => write!(f, "receiver is gone, request not sent"),
+            CallError::Cancelled => write!(f, "context is cancelled"),
+            CallError::DeadlineExceeded => write!(f, "deadline exceeded"),
+            CallError::NoResponse => write!(f, "responder dropped without responding"),
+        }
+    }
+}
+
+impl<Req: fmt::Debug> std::error::Error for CallError<Req> {}
+
+impl Context {
+    /// Send `req` over `tx` together with a reply sender, and wait for the response.
+    ///
+    /// Both the send and the wait give up when the context is cancelled or its effective deadline passes. The request
+    /// is only moved into the channel once there is room for it, so a call that gives up while sending hands the
+    /// request back in the error.
+    ///
+    /// Not cancel safe: dropping the future drops the request if it was not sent yet, and the response if it was.
+    /// ```rust, no_run
+    /// use tokio::sync::{mpsc, oneshot};
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(ctx: Context, cache: mpsc::Sender<(String, oneshot::Sender<Option<u64>>)>) {
+    /// match ctx.call(&cache, "user:42".to_string()).await {
+    ///     Ok(hit) => println!("cached: {:?}", hit),
+    ///     Err(e) => println!("lookup failed: {}", e),
+    /// }
+    /// # }
+    /// ```
+    pub async fn call<Req, Resp>(&self, tx: &mpsc::Sender<(Req, oneshot::Sender<Resp>)>, req: Req) -> Result<Resp, CallError<Req>> {
+        let deadline = self.inner.effective_deadline();
+        let expired = async move {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+        let mut expired = std::pin::pin!(expired);
+        let mut cancelled = std::pin::pin!(self.inner.cancelled());
+        let permit = tokio::select! {
+            biased;
+            _ = &mut cancelled => return Err(CallError::NotSent(req)),
+            _ = &mut expired => return Err(CallError::NotSent(req)),
+            permit = tx.reserve() => match permit {
+                Ok(permit) => permit,
+                Err(_) => return Err(CallError::Closed(req)),
+            },
+        };
+        let (reply, response) = oneshot::channel();
+        permit.send((req, reply));
+        tokio::select! {
+            biased;
+            response = response => response.map_err(|_| CallError::NoResponse),
+            _ = &mut expired => Err(CallError::DeadlineExceeded),
+            // the deadline of an ancestor cancels this context as well
+            _ = cancelled => match deadline {
+                Some(deadline) if deadline <= Instant::now() => Err(CallError::DeadlineExceeded),
+                _ => Err(CallError::Cancelled),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn call_reports_each_way_of_giving_up() {
+        let mut root = Context::new();
+        let (tx, mut rx) = mpsc::channel::<(u32, oneshot::Sender<u32>)>(1);
+        let responder = tokio::spawn(async move {
+            let (req, reply) = rx.recv().await.unwrap();
+            reply.send(req * 2).unwrap();
+            let (_, reply) = rx.recv().await.unwrap();
+            drop(reply);
+            let (_, _slow) = rx.recv().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        assert_eq!(root.call(&tx, 21).await, Ok(42));
+        assert_eq!(root.call(&tx, 1).await, Err(CallError::NoResponse));
+
+        let timeout = Context::builder().timeout(Duration::from_secs(1)).build_child(&mut root);
+        assert_eq!(timeout.call(&tx, 3).await, Err(CallError::DeadlineExceeded));
+
+        // the channel is full and nobody receives, so the request comes back
+        tx.send((4, oneshot::channel().0)).await.unwrap();
+        let waiting = root.new_child_context();
+        let cancel = async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            root.cancel();
+        };
+        let (called, _) = tokio::join!(waiting.call(&tx, 5), cancel);
+        assert_eq!(called, Err(CallError::NotSent(5)));
+
+        responder.abort();
+        let _ = responder.await;
+        assert_eq!(Context::new().call(&tx, 6).await.unwrap_err().into_request(), Some(6));
+    }
+}
@@ -0,0 +1,132 @@
+use std::future::Future;
+use std::panic::Location;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{CancellationCause, Context, ContextId, ContextInner, SpawnError, TaskOptions};
+
+/// Which cancellation a task listens to, see `Context::spawn_with_cancel_scope`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum CancelScope {
+    /// Stop when the context is cancelled for any reason, including the cancellation of an ancestor
+    #[default]
+    Full,
+    /// Stop only when the context itself is cancelled, not when the cancellation comes from an ancestor
+    OwnContextOnly,
+    /// Stop only when the given ancestor (or the context itself) is cancelled. Cancelling the contexts in between
+    /// does not stop the task.
+    Ancestor(ContextId),
+}
+
+impl ContextInner {
+    /// Resolves once a task listening to `scope` should stop. Fails if that is already the case.
+    pub(crate) fn cancel_listener(self: &Arc<Self>, scope: CancelScope) -> Result<impl Future<Output = ()> + Send + 'static, SpawnError> {
+        let (target, ignore_parent) = match scope {
+            CancelScope::Full => (self.clone(), false),
+            CancelScope::OwnContextOnly => (self.clone(), true),
+            CancelScope::Ancestor(id) => {
+                let mut context = Some(self);
+                while context.is_some_and(|context| context.id != id) {
+                    context = context.and_then(|context| context.parent.as_ref());
+                }
+                (context.ok_or(SpawnError::UnknownAncestor(id))?.clone(), false)
+            }
+        };
+        let stops = move |target: &ContextInner| match target.cause() {
+            Some(CancellationCause::Parent) => !ignore_parent,
+            cause => cause.is_some(),
+        };
+        // subscribe before checking, so a cancel that happens in between is still received
+        let mut own = self.subscribe();
+        let mut watched = target.subscribe();
+        if self.is_forced() || stops(&target) {
+            return Err(SpawnError::Cancelled);
+        }
+        let inner = self.clone();
+        Ok(async move {
+            // the own context is watched too, since `force_cancel` stops tasks whatever their scope
+            loop {
+                tokio::select! {
+                    _ = own.recv() => {},
+                    _ = watched.recv() => {},
+                }
+                if inner.is_forced() || stops(&target) {
+                    return;
+                }
+            }
+        })
+    }
+}
+
+impl Context {
+    /// Spawn a task that only stops when the cancellation chosen by `scope` happens, or when `timeout` is reached.
+    ///
+    /// Useful for cleanup tasks that must outlive the cancellation of their parent scope, but still stop on shutdown.
+    /// The scope is shown next to the task in `tree()`. Fails with `SpawnError::UnknownAncestor` if the ancestor of
+    /// `CancelScope::Ancestor` is not an ancestor of this context.
+    /// ```rust, no_run
+    /// use tokio_tree_context::{CancelScope, Context};
+    ///
+    /// let mut root = Context::new();
+    /// let mut request = root.new_child_context();
+    /// // keeps cleaning up when the request is cancelled, stops when the root shuts down
+    /// let janitor = request.spawn_with_cancel_scope(CancelScope::Ancestor(root.id()), async move {
+    ///     // clean up
+    /// }, None);
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_cancel_scope<T>(
+        &mut self,
+        scope: CancelScope,
+        future: T,
+        timeout: Option<Duration>,
+    ) -> Result<tokio::task::JoinHandle<Option<T::Output>>, SpawnError>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        self.inner
+            .task_future_at(None, future, Location::caller(), TaskOptions { timeout, scope, ..Default::default() })
+            .map(|task| self.inner.spawn(task))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn cancel_scope_selects_the_cancellation_to_follow() {
+        let mut root = Context::new();
+        let mut parent = root.new_child_context();
+        let mut ctx = parent.new_child_context();
+        let pending = std::future::pending::<()>;
+        let full = ctx.spawn_with_cancel_scope(CancelScope::Full, pending(), None).unwrap();
+        let own = ctx.spawn_with_cancel_scope(CancelScope::OwnContextOnly, pending(), None).unwrap();
+        let janitor = ctx.spawn_with_cancel_scope(CancelScope::Ancestor(root.id()), pending(), None).unwrap();
+        let bounded = ctx
+            .spawn_with_cancel_scope(CancelScope::Ancestor(root.id()), pending(), Some(Duration::from_secs(1)))
+            .unwrap();
+        let unknown = ctx.new_child_context().id();
+        assert_eq!(
+            ctx.spawn_with_cancel_scope(CancelScope::Ancestor(unknown), pending(), None).unwrap_err(),
+            SpawnError::UnknownAncestor(unknown)
+        );
+
+        drop(parent);
+        assert_eq!(full.await.unwrap(), None);
+        assert_eq!(bounded.await.unwrap(), None);
+        tokio::task::yield_now().await;
+        assert!(!own.is_finished() && !janitor.is_finished());
+        let scopes: Vec<_> = ctx.tree().root.tasks.iter().map(|task| task.cancel_scope).collect();
+        assert_eq!(scopes, vec![CancelScope::OwnContextOnly, CancelScope::Ancestor(root.id())]);
+        // spawning with the scope of a context cancelled by its parent still works
+        assert!(ctx.spawn_with_cancel_scope(CancelScope::OwnContextOnly, async {}, None).is_ok());
+
+        drop(root);
+        assert_eq!(janitor.await.unwrap(), None);
+        assert!(!own.is_finished());
+        own.abort();
+    }
+}
@@ -0,0 +1,175 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use tokio::sync::mpsc;
+
+use crate::{CancellationCause, Context, ContextInner};
+
+/// Future resolving to the `CancellationCause` once a context is cancelled. Created by `Context::cancellation_signal`.
+///
+/// It can be cloned to hand out to several tasks, and does not keep the context from being cancelled or dropped.
+pub struct CancellationSignal {
+    inner: Arc<ContextInner>,
+    wait: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+}
+
+impl Clone for CancellationSignal {
+    fn clone(&self) -> Self {
+        CancellationSignal {
+            inner: self.inner.clone(),
+            wait: None,
+        }
+    }
+}
+
+impl CancellationSignal {
+    #[cfg(feature = "sink")]
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.inner.is_cancelled()
+    }
+}
+
+impl Future for CancellationSignal {
+    type Output = CancellationCause;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<CancellationCause> {
+        if self.wait.is_none() {
+            // subscribe before checking the flag, so a cancel that happens in between is still received
+            let mut rx = self.inner.subscribe();
+            self.wait = Some(Box::pin(async move {
+                let _ = rx.recv().await;
+            }));
+        }
+        if self.inner.is_cancelled() || self.wait.as_mut().unwrap().as_mut().poll(cx).is_ready() {
+            return Poll::Ready(self.inner.cause().unwrap_or(CancellationCause::Explicit));
+        }
+        Poll::Pending
+    }
+}
+
+/// Message sent by `Context::inject_cancel_into_channel` when the context is cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelSignal {
+    /// The context was cancelled for any reason other than its deadline
+    Cancel,
+    /// The deadline of the context was reached
+    Deadline,
+}
+
+impl Context {
+    /// A future that resolves to the cause once this context is cancelled, and never resolves otherwise.
+    /// ```rust, no_run
+    /// use tokio_tree_context::{CancellationCause, Context};
+    ///
+    /// let mut ctx = Context::new();
+    /// let signal = ctx.cancellation_signal();
+    /// ctx.spawn(async move {
+    ///     match signal.await {
+    ///         CancellationCause::BudgetExhausted => println!("ran out of time"),
+    ///         cause => println!("shutting down: {:?}", cause),
+    ///     }
+    /// });
+    /// ```
+    pub fn cancellation_signal(&self) -> CancellationSignal {
+        CancellationSignal {
+            inner: self.inner.clone(),
+            wait: None,
+        }
+    }
+
+    /// Wait until this context is cancelled and return the cause. Resolves right away with the stored cause if it is
+    /// already cancelled.
+    ///
+    /// The same as awaiting `cancellation_signal()`, for tasks that do not need to hand the signal around.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut ctx = Context::new();
+    /// let cause = ctx.await_cancellation_with_cause();
+    /// ctx.spawn(async move {
+    ///     println!("stopping: {:?}", cause.await);
+    /// });
+    /// ```
+    pub fn await_cancellation_with_cause(&self) -> impl Future<Output = CancellationCause> + Send + Unpin + 'static {
+        self.cancellation_signal()
+    }
+
+    /// Send a `CancelSignal` on `tx` once this context is cancelled, then drop `tx`.
+    ///
+    /// This lets existing mpsc based workers learn about cancellation without holding the context. Must be called
+    /// inside a tokio runtime.
+    /// ```rust, no_run
+    /// use tokio::sync::mpsc;
+    /// use tokio_tree_context::{CancelSignal, Context};
+    ///
+    /// let ctx = Context::new();
+    /// let (tx, mut rx) = mpsc::channel(1);
+    /// ctx.inject_cancel_into_channel(tx);
+    /// tokio::spawn(async move {
+    ///     if let Some(CancelSignal::Deadline) = rx.recv().await {
+    ///         println!("out of time");
+    ///     }
+    /// });
+    /// ```
+    pub fn inject_cancel_into_channel(&self, tx: mpsc::Sender<CancelSignal>) {
+        let signal = self.cancellation_signal();
+        self.inner.spawn(async move {
+            let signal = match signal.await {
+                CancellationCause::Deadline => CancelSignal::Deadline,
+                _ => CancelSignal::Cancel,
+            };
+            let _ = tx.send(signal).await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn signal_resolves_with_cause_in_every_clone() {
+        let mut ctx = Context::new();
+        let child = ctx.new_child_context();
+        let signal = ctx.cancellation_signal();
+        let first = tokio::spawn(signal.clone());
+        let second = tokio::spawn(signal);
+        let child_signal = tokio::spawn(child.cancellation_signal());
+        tokio::task::yield_now().await;
+
+        drop(ctx);
+        assert_eq!(first.await.unwrap(), CancellationCause::Explicit);
+        assert_eq!(second.await.unwrap(), CancellationCause::Explicit);
+        assert_eq!(child_signal.await.unwrap(), CancellationCause::Parent);
+        // created after the cancellation
+        assert_eq!(child.cancellation_signal().await, CancellationCause::Parent);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancellation_cause_is_awaited() {
+        let mut root = Context::new();
+        let ctx = root.with_timeout(std::time::Duration::from_secs(1));
+        assert_eq!(ctx.await_cancellation_with_cause().await, CancellationCause::Deadline);
+        assert_eq!(ctx.await_cancellation_with_cause().await, CancellationCause::Deadline);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancel_signals_are_injected() {
+        let ctx = Context::new();
+        let (tx, mut rx) = mpsc::channel(1);
+        ctx.inject_cancel_into_channel(tx);
+        drop(ctx);
+        assert_eq!(rx.recv().await, Some(CancelSignal::Cancel));
+        assert_eq!(rx.recv().await, None);
+
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(1);
+        let mut ctx = Context::builder().deadline(deadline).build();
+        let child = ctx.new_child_context();
+        assert_eq!(child.deadline(), Some(deadline));
+        let (tx, mut rx) = mpsc::channel(1);
+        ctx.inject_cancel_into_channel(tx);
+        assert_eq!(rx.recv().await, Some(CancelSignal::Deadline));
+        assert_eq!(child.cancellation_cause(), Some(CancellationCause::Parent));
+    }
+}
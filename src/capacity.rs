@@ -0,0 +1,120 @@
+use std::future::Future;
+use std::panic::Location;
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{Context, SpawnError, TaskOptions};
+
+/// Units of work a context can run at once, set with `ContextBuilder::capacity`
+pub(crate) struct Capacity {
+    total: u32,
+    units: Arc<Semaphore>,
+}
+
+/// How much of the capacity of a context is in use, see `ContextStats::capacity`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CapacityStats {
+    /// The configured capacity
+    pub total: u32,
+    /// Units held by running tasks
+    pub used: u32,
+    /// Units available to waiting and new tasks
+    pub free: u32,
+}
+
+impl Capacity {
+    pub(crate) fn new(total: u32) -> Self {
+        Capacity {
+            total,
+            units: Arc::new(Semaphore::new(total as usize)),
+        }
+    }
+
+    /// Fail early for costs that could never be acquired
+    pub(crate) fn check(&self, cost: u32) -> Result<(), SpawnError> {
+        if cost > self.total {
+            return Err(SpawnError::ExceedsCapacity { cost, capacity: self.total });
+        }
+        Ok(())
+    }
+
+    /// Wait for `cost` units. Waiters are served in FIFO order, and dropping the future gives back nothing it has not
+    /// returned yet.
+    pub(crate) fn acquire(&self, cost: u32) -> impl Future<Output = OwnedSemaphorePermit> + Send + 'static {
+        let units = self.units.clone();
+        async move { units.acquire_many_owned(cost).await.expect("capacity semaphore is never closed") }
+    }
+
+    pub(crate) fn stats(&self) -> CapacityStats {
+        let free = self.units.available_permits() as u32;
+        CapacityStats {
+            total: self.total,
+            used: self.total - free,
+            free,
+        }
+    }
+}
+
+impl Context {
+    /// Spawn a task that takes `cost` units of the context's capacity while it runs.
+    ///
+    /// The task waits for the units before it is first polled and gives them back when it completes or is cancelled,
+    /// also while still waiting. Waiting tasks get their units in spawn order. Other spawns cost one unit, and all tasks
+    /// are free on contexts without a capacity. Fails with `SpawnError::ExceedsCapacity` if `cost` is more than the
+    /// whole capacity.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example() {
+    /// let mut jobs = Context::builder().capacity(64).build();
+    /// jobs.spawn_weighted(1, async move { /* make a thumbnail */ }).unwrap();
+    /// jobs.spawn_weighted(8, async move { /* transcode a video */ }).unwrap();
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn spawn_weighted<T>(&mut self, cost: u32, future: T) -> Result<tokio::task::JoinHandle<Option<T::Output>>, SpawnError>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        self.inner
+            .task_future_at(None, future, Location::caller(), TaskOptions { cost: Some(cost), ..Default::default() })
+            .map(|task| self.inner.spawn(task))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn weighted_tasks_share_the_capacity() {
+        let mut ctx = Context::builder().capacity(4).build();
+        assert_eq!(ctx.spawn_weighted(5, async {}).unwrap_err(), SpawnError::ExceedsCapacity { cost: 5, capacity: 4 });
+        let big = ctx.spawn_weighted(3, tokio::time::sleep(Duration::from_secs(10))).unwrap();
+        tokio::task::yield_now().await;
+        let stats = ctx.stats().capacity.unwrap();
+        assert_eq!((stats.used, stats.free), (3, 1));
+
+        // the waiting cost-2 task is served first, even though one unit would do for the next one
+        let medium = ctx.spawn_weighted(2, async { tokio::time::Instant::now() }).unwrap();
+        tokio::task::yield_now().await;
+        let small = ctx.spawn(async { tokio::time::Instant::now() });
+        tokio::task::yield_now().await;
+        assert!(!small.is_finished());
+
+        let mut child = Context::builder().capacity(1).build();
+        child.spawn_weighted(1, std::future::pending::<()>()).unwrap();
+        let waiting = child.spawn(async {});
+        tokio::task::yield_now().await;
+        drop(child);
+        assert_eq!(waiting.await.unwrap(), None);
+
+        big.await.unwrap();
+        let (medium, small) = (medium.await.unwrap().unwrap(), small.await.unwrap().unwrap());
+        assert!(medium <= small);
+        assert_eq!(ctx.stats().capacity.unwrap().free, 4);
+    }
+}
@@ -0,0 +1,278 @@
+//! Channels that close when their context is cancelled, so pipelines unwind on shutdown instead of deadlocking on
+//! back-pressure.
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use tokio::sync::{mpsc, oneshot, watch};
+
+use crate::{CancellationSignal, Context, ContextInner};
+
+/// Error of receivers once the channel is closed, by cancellation or because all senders are gone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Closed;
+
+impl fmt::Display for Closed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "channel closed")
+    }
+}
+
+impl std::error::Error for Closed {}
+
+/// Sending half of `Context::channel`
+pub struct ScopedSender<T> {
+    tx: mpsc::Sender<T>,
+    context: Arc<ContextInner>,
+}
+
+impl<T> Clone for ScopedSender<T> {
+    fn clone(&self) -> Self {
+        ScopedSender {
+            tx: self.tx.clone(),
+            context: self.context.clone(),
+        }
+    }
+}
+
+impl<T> ScopedSender<T> {
+    /// Send `value`, waiting for capacity. Fails right away once the context is cancelled, even if the buffer is
+    /// full.
+    ///
+    /// Not cancel safe: dropping the future before the value is queued drops the value with it. In a `select!`, wait
+    /// for a slot with `reserve` instead.
+    pub async fn send(&self, value: T) -> Result<(), mpsc::error::SendError<T>> {
+        let cancelled = self.context.cancelled();
+        tokio::select! {
+            biased;
+            _ = cancelled => Err(mpsc::error::SendError(value)),
+            permit = self.tx.reserve() => match permit {
+                Ok(permit) => {
+                    permit.send(value);
+                    Ok(())
+                }
+                Err(_) => Err(mpsc::error::SendError(value)),
+            },
+        }
+    }
+
+    /// Wait for a slot in the buffer, without committing to a value yet. Fails once the context is cancelled or the
+    /// receiver is gone.
+    ///
+    /// Cancel safe: dropping the future, or the permit, gives the slot back.
+    pub async fn reserve(&self) -> Result<mpsc::Permit<'_, T>, Closed> {
+        let cancelled = self.context.cancelled();
+        tokio::select! {
+            biased;
+            _ = cancelled => Err(Closed),
+            permit = self.tx.reserve() => permit.map_err(|_| Closed),
+        }
+    }
+
+    /// Send `value` if there is capacity
+    pub fn try_send(&self, value: T) -> Result<(), mpsc::error::TrySendError<T>> {
+        if self.context.is_cancelled() {
+            return Err(mpsc::error::TrySendError::Closed(value));
+        }
+        self.tx.try_send(value)
+    }
+}
+
+/// Receiving half of `Context::channel`
+pub struct ScopedReceiver<T> {
+    rx: mpsc::Receiver<T>,
+    context: Arc<ContextInner>,
+}
+
+impl<T> ScopedReceiver<T> {
+    /// Receive the next value. Once the context is cancelled, the values still buffered are returned, then None.
+    ///
+    /// Cancel safe: a value is only taken out of the channel when the future completes.
+    pub async fn recv(&mut self) -> Option<T> {
+        let cancelled = self.context.cancelled();
+        tokio::select! {
+            biased;
+            value = self.rx.recv() => return value,
+            _ = cancelled => {},
+        }
+        self.rx.close();
+        self.rx.recv().await
+    }
+}
+
+/// Sending half of `Context::watch`
+pub struct ScopedWatchSender<T> {
+    tx: watch::Sender<T>,
+    context: Arc<ContextInner>,
+}
+
+impl<T> ScopedWatchSender<T> {
+    /// Replace the value, failing once the context is cancelled or all receivers are gone
+    pub fn send(&self, value: T) -> Result<(), watch::error::SendError<T>> {
+        if self.context.is_cancelled() {
+            return Err(watch::error::SendError(value));
+        }
+        self.tx.send(value)
+    }
+}
+
+/// Receiving half of `Context::watch`
+#[derive(Clone)]
+pub struct ScopedWatchReceiver<T> {
+    rx: watch::Receiver<T>,
+    context: Arc<ContextInner>,
+}
+
+impl<T> ScopedWatchReceiver<T> {
+    /// Wait for a new value. Fails once the context is cancelled or the sender is gone.
+    ///
+    /// Cancel safe: a change seen by a dropped future is still reported by the next call.
+    pub async fn changed(&mut self) -> Result<(), Closed> {
+        let cancelled = self.context.cancelled();
+        tokio::select! {
+            biased;
+            _ = cancelled => Err(Closed),
+            changed = self.rx.changed() => changed.map_err(|_| Closed),
+        }
+    }
+
+    /// The latest value, which stays readable after cancellation
+    pub fn borrow(&self) -> watch::Ref<'_, T> {
+        self.rx.borrow()
+    }
+}
+
+/// Sending half of `Context::oneshot`
+pub struct ScopedOneshotSender<T> {
+    tx: oneshot::Sender<T>,
+    context: Arc<ContextInner>,
+}
+
+impl<T> ScopedOneshotSender<T> {
+    /// Send `value`, giving it back if the context is cancelled or the receiver is gone
+    pub fn send(self, value: T) -> Result<(), T> {
+        if self.context.is_cancelled() {
+            return Err(value);
+        }
+        self.tx.send(value)
+    }
+}
+
+/// Receiving half of `Context::oneshot`. Awaiting it, or `&mut` it, waits for the value like `recv`.
+pub struct ScopedOneshotReceiver<T> {
+    rx: oneshot::Receiver<T>,
+    cancelled: CancellationSignal,
+}
+
+impl<T> ScopedOneshotReceiver<T> {
+    /// Wait for the value. Fails once the context is cancelled or the sender is gone.
+    ///
+    /// Not cancel safe, since the receiver is consumed: a value sent after the future is dropped is lost. In a
+    /// `select!`, await `&mut receiver` instead, which leaves the receiver in place.
+    pub async fn recv(self) -> Result<T, Closed> {
+        self.await
+    }
+}
+
+impl<T> Future for ScopedOneshotReceiver<T> {
+    type Output = Result<T, Closed>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        // a value that is already there wins over the cancellation
+        if let Poll::Ready(value) = Pin::new(&mut self.rx).poll(cx) {
+            return Poll::Ready(value.map_err(|_| Closed));
+        }
+        Pin::new(&mut self.cancelled).poll(cx).map(|_| Err(Closed))
+    }
+}
+
+impl Context {
+    /// Bounded mpsc channel that closes when this context is cancelled.
+    ///
+    /// After cancellation senders fail right away instead of waiting for capacity, and the receiver returns the
+    /// values still buffered, then None.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example() {
+    /// let mut ctx = Context::new();
+    /// let (tx, mut rx) = ctx.channel(16);
+    /// ctx.spawn(async move {
+    ///     while tx.send(1).await.is_ok() {}
+    /// });
+    /// while let Some(item) = rx.recv().await {
+    ///     println!("{}", item);
+    /// }
+    /// # }
+    /// ```
+    pub fn channel<T>(&self, capacity: usize) -> (ScopedSender<T>, ScopedReceiver<T>) {
+        let (tx, rx) = mpsc::channel(capacity);
+        let sender = ScopedSender {
+            tx,
+            context: self.inner.clone(),
+        };
+        let receiver = ScopedReceiver {
+            rx,
+            context: self.inner.clone(),
+        };
+        (sender, receiver)
+    }
+
+    /// Watch channel that closes when this context is cancelled
+    pub fn watch<T>(&self, initial: T) -> (ScopedWatchSender<T>, ScopedWatchReceiver<T>) {
+        let (tx, rx) = watch::channel(initial);
+        let sender = ScopedWatchSender {
+            tx,
+            context: self.inner.clone(),
+        };
+        let receiver = ScopedWatchReceiver {
+            rx,
+            context: self.inner.clone(),
+        };
+        (sender, receiver)
+    }
+
+    /// Oneshot channel that closes when this context is cancelled
+    pub fn oneshot<T>(&self) -> (ScopedOneshotSender<T>, ScopedOneshotReceiver<T>) {
+        let (tx, rx) = oneshot::channel();
+        let sender = ScopedOneshotSender {
+            tx,
+            context: self.inner.clone(),
+        };
+        let receiver = ScopedOneshotReceiver {
+            rx,
+            cancelled: self.cancellation_signal(),
+        };
+        (sender, receiver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn full_channel_unwinds_on_cancel() {
+        let ctx = Context::new();
+        let (tx, mut rx) = ctx.channel(1);
+        tx.send(1).await.unwrap();
+        let blocked = tokio::spawn(async move { tx.send(2).await });
+        tokio::task::yield_now().await;
+        assert!(!blocked.is_finished());
+
+        let (watch_tx, mut watch_rx) = ctx.watch(0);
+        let (oneshot_tx, oneshot_rx) = ctx.oneshot::<u32>();
+        ctx.cancel();
+        assert_eq!(blocked.await.unwrap().unwrap_err().0, 2);
+        // buffered values are still delivered
+        assert_eq!(rx.recv().await, Some(1));
+        assert_eq!(rx.recv().await, None);
+
+        assert_eq!(watch_rx.changed().await, Err(Closed));
+        assert!(watch_tx.send(1).is_err());
+        assert_eq!(*watch_rx.borrow(), 0);
+        assert_eq!(oneshot_rx.recv().await, Err(Closed));
+        assert_eq!(oneshot_tx.send(1), Err(1));
+    }
+}
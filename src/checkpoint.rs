@@ -0,0 +1,120 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{Cancelled, Context, ContextInner, ContextRef, TaskOptions};
+
+/// When a task sees the cancellation of its context, see `Context::spawn_with_granularity`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CancellationGranularity {
+    /// The task is stopped at whatever point it is waiting when the context is cancelled
+    #[default]
+    Immediate,
+    /// The task is not stopped, it sees the cancellation at its next `checkpoint()` and finishes on its own. With
+    /// `but_force_after`, a task that has not finished that long after the cancellation is stopped anyway.
+    AtCheckpoints { but_force_after: Option<Duration> },
+}
+
+impl CancellationGranularity {
+    /// Resolves when a task with this granularity should be stopped, given the cancellation it listens to
+    pub(crate) async fn deliver(self, cancelled: impl Future<Output = ()>, inner: Arc<ContextInner>) {
+        cancelled.await;
+        let grace = match self {
+            CancellationGranularity::Immediate => return,
+            CancellationGranularity::AtCheckpoints { but_force_after } => but_force_after,
+        };
+        let context = inner.clone();
+        let grace = async move {
+            match grace {
+                Some(grace) => {
+                    tokio::time::sleep(grace).await;
+                    context.keep_alives_released().await;
+                }
+                None => std::future::pending().await,
+            }
+        };
+        // `force_cancel` stops the task right away, whatever its granularity
+        tokio::select! {
+            _ = grace => {},
+            _ = inner.forced() => {},
+        }
+    }
+}
+
+impl Context {
+    /// Fails with `Cancelled` if this context is cancelled. Call it between units of work of a task spawned with
+    /// `CancellationGranularity::AtCheckpoints`.
+    pub async fn checkpoint(&self) -> Result<(), Cancelled> {
+        if self.is_cancelled() {
+            return Err(Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Spawn a task that sees the cancellation of this context as `granularity` says.
+    ///
+    /// With `CancellationGranularity::AtCheckpoints`, a unit of work that has started is never interrupted midway
+    /// (unless `but_force_after` expires), since the task only stops when it observes the cancellation itself.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::{CancellationGranularity, Context};
+    ///
+    /// let mut ctx = Context::new();
+    /// let scope = ctx.as_ref();
+    /// let granularity = CancellationGranularity::AtCheckpoints { but_force_after: Some(Duration::from_secs(30)) };
+    /// ctx.spawn_with_granularity(granularity, async move {
+    ///     while scope.checkpoint().await.is_ok() {
+    ///         // process one message, without being interrupted
+    ///     }
+    /// });
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_granularity<T>(&mut self, granularity: CancellationGranularity, future: T) -> tokio::task::JoinHandle<Option<T::Output>>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        let options = TaskOptions { granularity, ..Default::default() };
+        self.inner.spawn_or_none(self.inner.task_future_at(None, future, std::panic::Location::caller(), options))
+    }
+}
+
+impl ContextRef {
+    /// Same as `Context::checkpoint`. Fails if the context is gone.
+    pub async fn checkpoint(&self) -> Result<(), Cancelled> {
+        if self.is_cancelled() {
+            return Err(Cancelled);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn cancellation_waits_for_the_next_checkpoint() {
+        let mut root = Context::new();
+        let mut ctx = root.new_child_context();
+        let scope = ctx.as_ref();
+        let granularity = CancellationGranularity::AtCheckpoints { but_force_after: None };
+        let units = ctx.spawn_with_granularity(granularity, async move {
+            let mut units = 0;
+            while scope.checkpoint().await.is_ok() {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                units += 1;
+            }
+            units
+        });
+        let forced = CancellationGranularity::AtCheckpoints { but_force_after: Some(Duration::from_secs(5)) };
+        let stuck = ctx.spawn_with_granularity(forced, std::future::pending::<()>());
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        drop(ctx);
+        // the third unit is finished rather than interrupted
+        assert_eq!(units.await.unwrap(), Some(3));
+        let cancelled_at = tokio::time::Instant::now();
+        assert_eq!(stuck.await.unwrap(), None);
+        assert_eq!(cancelled_at.elapsed(), Duration::from_millis(4500));
+    }
+}
@@ -0,0 +1,206 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::{Context, ContextId, ContextInner};
+
+/// A cleanup registered with `Context::defer`
+pub(crate) struct Cleanup {
+    name: String,
+    order: i32,
+    future: Pin<Box<dyn Future<Output = ()> + Send>>,
+}
+
+/// Cleanups of a context, in registration order
+pub(crate) type Cleanups = Mutex<Vec<Cleanup>>;
+
+/// What happened to a cleanup during `Context::cancel_and_clean_up`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum CleanupOutcome {
+    /// The cleanup ran to completion
+    Ran,
+    /// The cleanup was still running when the budget ran out, and was dropped
+    TimedOut,
+    /// The budget ran out before the cleanup could start
+    Skipped,
+}
+
+/// One cleanup in a `CleanupReport`, in the order cleanups were run
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CleanupRun {
+    pub name: String,
+    pub context: ContextId,
+    pub outcome: CleanupOutcome,
+}
+
+/// Returned by `Context::cancel_and_clean_up`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CleanupReport {
+    pub cleanups: Vec<CleanupRun>,
+}
+
+impl CleanupReport {
+    /// Whether every cleanup ran to completion
+    pub fn is_complete(&self) -> bool {
+        self.cleanups.iter().all(|cleanup| cleanup.outcome == CleanupOutcome::Ran)
+    }
+}
+
+impl Context {
+    /// Register `cleanup` to run when the context is shut down with `cancel_and_clean_up`, on it or an ancestor.
+    ///
+    /// Cleanups of a context run after those of its descendants, and in reverse registration order within the
+    /// context. A context that is cancelled any other way drops its cleanups without running them.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut pool = Context::new();
+    /// pool.defer("close pool", async move { /* close the pool */ });
+    /// let connection = pool.new_child_context();
+    /// // runs before "close pool"
+    /// connection.defer("return connection", async move { /* hand the connection back */ });
+    /// ```
+    pub fn defer<F>(&self, name: impl Into<String>, cleanup: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.defer_with_order(name, 0, cleanup);
+    }
+
+    /// `defer` with an explicit ordering key. All cleanups of the tree run in ascending `order`, and in the order
+    /// described at `defer` among equal keys, which use 0.
+    pub fn defer_with_order<F>(&self, name: impl Into<String>, order: i32, cleanup: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.inner.cleanups.get_or_init(Default::default).lock().unwrap().push(Cleanup {
+            name: name.into(),
+            order,
+            future: Box::pin(cleanup),
+        });
+    }
+
+    /// Cancel this context, then run the cleanups of it and its descendants one at a time, all within `budget`.
+    ///
+    /// Before a cleanup runs, the tasks of its context are given the time left to finish. A cleanup that does not
+    /// finish in time is dropped, and the ones after it are skipped. The report lists every cleanup in the order it
+    /// ran or would have run.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(root: Context) {
+    /// let report = root.cancel_and_clean_up(Duration::from_secs(10)).await;
+    /// if !report.is_complete() {
+    ///     eprintln!("unclean shutdown: {:?}", report);
+    /// }
+    /// # }
+    /// ```
+    pub fn cancel_and_clean_up(self, budget: Duration) -> impl Future<Output = CleanupReport> + Send + Unpin + 'static {
+        let until = Instant::now() + budget;
+        let contexts = self.inner.post_order();
+        self.disarm_expectation();
+        drop(self);
+        let mut cleanups: Vec<_> = contexts
+            .into_iter()
+            .flat_map(|inner| {
+                let taken = inner.cleanups.get().map(|cleanups| std::mem::take(&mut *cleanups.lock().unwrap())).unwrap_or_default();
+                taken.into_iter().rev().map(move |cleanup| (inner.clone(), cleanup))
+            })
+            .collect();
+        // stable, so equal keys keep the tree order
+        cleanups.sort_by_key(|(_, cleanup)| cleanup.order);
+        Box::pin(async move {
+            let mut report = CleanupReport::default();
+            let mut out_of_time = false;
+            for (inner, cleanup) in cleanups {
+                let outcome = if out_of_time || tokio::time::timeout_at(until, inner.clone().tasks_done()).await.is_err() {
+                    CleanupOutcome::Skipped
+                } else if tokio::time::timeout_at(until, cleanup.future).await.is_err() {
+                    CleanupOutcome::TimedOut
+                } else {
+                    CleanupOutcome::Ran
+                };
+                out_of_time = outcome != CleanupOutcome::Ran;
+                report.cleanups.push(CleanupRun {
+                    name: cleanup.name,
+                    context: inner.id,
+                    outcome,
+                });
+            }
+            report
+        })
+    }
+}
+
+impl ContextInner {
+    /// The context and its live descendants, children before their parent and siblings in creation order
+    fn post_order(self: &Arc<Self>) -> Vec<Arc<ContextInner>> {
+        let mut order = Vec::new();
+        let mut stack = vec![(self.clone(), false)];
+        while let Some((inner, visited)) = stack.pop() {
+            if visited {
+                order.push(inner);
+                continue;
+            }
+            let children = inner.live_children();
+            stack.push((inner, true));
+            stack.extend(children.into_iter().rev().map(|child| (child, false)));
+        }
+        order
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn cleanups_run_children_first_within_the_budget() {
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let cleanup = |name: &'static str, delay: u64| {
+            let ran = ran.clone();
+            async move {
+                tokio::time::sleep(Duration::from_secs(delay)).await;
+                ran.lock().unwrap().push(name);
+            }
+        };
+        let mut pool = Context::new();
+        pool.defer("close pool", cleanup("close pool", 1));
+        pool.defer("flush pool", cleanup("flush pool", 1));
+        let mut first = pool.new_child_context();
+        first.defer("first connection", cleanup("first connection", 1));
+        first.spawn(tokio::time::sleep(Duration::from_secs(2)));
+        let second = pool.new_child_context();
+        second.defer("second connection", cleanup("second connection", 1));
+        pool.defer_with_order("metrics", -1, cleanup("metrics", 1));
+        pool.defer_with_order("stuck", 1, cleanup("stuck", 60));
+        pool.defer_with_order("never", 2, cleanup("never", 1));
+
+        let start = Instant::now();
+        let report = pool.cancel_and_clean_up(Duration::from_secs(10)).await;
+        drop((first, second));
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+        let outcomes: Vec<_> = report.cleanups.iter().map(|run| (run.name.as_str(), run.outcome)).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("metrics", CleanupOutcome::Ran),
+                // waits for the task of its context first
+                ("first connection", CleanupOutcome::Ran),
+                ("second connection", CleanupOutcome::Ran),
+                ("flush pool", CleanupOutcome::Ran),
+                ("close pool", CleanupOutcome::Ran),
+                ("stuck", CleanupOutcome::TimedOut),
+                ("never", CleanupOutcome::Skipped),
+            ]
+        );
+        assert!(!report.is_complete());
+        assert_eq!(*ran.lock().unwrap(), vec!["metrics", "first connection", "second connection", "flush pool", "close pool"]);
+    }
+}
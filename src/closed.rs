@@ -0,0 +1,131 @@
+use std::future::Future;
+use std::sync::atomic::{fence, Ordering};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::Notify;
+
+use crate::{CancellationCause, ContextId, ContextInner, ContextRef, ContextStats};
+
+/// How a context closed, see `ContextRef::closed_with_id`
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct CloseInfo {
+    pub cause: CancellationCause,
+    /// Statistics of the context at the moment its last task finished
+    pub stats: ContextStats,
+}
+
+/// Shared by a context and the `ContextRef`s to it, so the close can be observed after the context is gone
+pub(crate) struct CloseState {
+    id: ContextId,
+    info: OnceLock<CloseInfo>,
+    closed: Notify,
+}
+
+impl CloseState {
+    pub(crate) fn new(id: ContextId) -> Arc<CloseState> {
+        Arc::new(CloseState { id, info: OnceLock::new(), closed: Notify::new() })
+    }
+
+    /// State of a context that never existed, already closed
+    pub(crate) fn gone() -> Arc<CloseState> {
+        let state = CloseState::new(ContextId::next());
+        let _ = state.info.set(CloseInfo { cause: CancellationCause::Explicit, stats: ContextStats::default() });
+        state
+    }
+}
+
+impl ContextInner {
+    /// Record the close of the context once it is cancelled and has no live tasks. Called whenever either changes.
+    /// Nothing is recorded until a `ContextRef` asked for the state, which then records a close that already
+    /// happened.
+    pub(crate) fn check_closed(&self) {
+        // pairs with the fence in `close_state`, so one of the two sees both the state and the last task ending
+        fence(Ordering::SeqCst);
+        let Some(close) = self.close.get() else {
+            return;
+        };
+        if close.info.get().is_some() || self.active_tasks.load(Ordering::SeqCst) != 0 {
+            return;
+        }
+        let Some(cause) = self.cause() else {
+            return;
+        };
+        if close.info.set(CloseInfo { cause, stats: self.stats() }).is_ok() {
+            close.closed.notify_waiters();
+        }
+    }
+
+    /// The close state shared with `ContextRef`s, created on first use
+    pub(crate) fn close_state(&self) -> Arc<CloseState> {
+        let state = self.close.get_or_init(|| CloseState::new(self.id)).clone();
+        self.check_closed();
+        state
+    }
+}
+
+impl ContextRef {
+    /// Resolves with the id of the context and how it closed, once it is cancelled and its last task has finished.
+    /// Resolves right away if that already happened, even if the context is gone.
+    ///
+    /// The future does not keep the context alive, so many of them can be pushed into a `FuturesUnordered` or a
+    /// `JoinSet` to supervise a set of scopes from one loop. A handle to no context, as returned by
+    /// `SlotRef::context_ref` once the slot is dropped, resolves right away with a fresh id.
+    /// ```rust, no_run
+    /// use tokio::task::JoinSet;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(mut root: Context) {
+    /// let mut workers = Vec::new();
+    /// let mut scopes = JoinSet::new();
+    /// for _ in 0..100 {
+    ///     let mut worker = root.new_child_context();
+    ///     worker.spawn(async move { /* serve */ });
+    ///     scopes.spawn(worker.as_ref().closed_with_id());
+    ///     workers.push(worker);
+    /// }
+    /// while let Some(Ok((id, info))) = scopes.join_next().await {
+    ///     println!("{id} closed: {:?}, {} tasks live", info.cause, info.stats.live_tasks);
+    /// }
+    /// # }
+    /// ```
+    pub fn closed_with_id(&self) -> impl Future<Output = (ContextId, CloseInfo)> + Send + Unpin + 'static {
+        let state = self.close.clone();
+        Box::pin(async move {
+            loop {
+                let closed = state.closed.notified();
+                if let Some(info) = state.info.get() {
+                    return (state.id, info.clone());
+                }
+                closed.await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CancellationCause, Context};
+
+    #[tokio::test]
+    async fn closed_with_id_waits_for_the_drain() {
+        let mut root = Context::new();
+        let mut child = root.new_child_context();
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let scope = crate::CancelScope::OwnContextOnly;
+        let handle = child.spawn_with_cancel_scope(scope, async move { rx.await.is_ok() }, None).unwrap();
+        let child_ref = child.as_ref();
+        let closed = tokio::spawn(child_ref.closed_with_id());
+        root.cancel();
+        tokio::task::yield_now().await;
+        assert!(!closed.is_finished());
+
+        tx.send(()).unwrap();
+        assert_eq!(handle.await.unwrap(), Some(true));
+        let (id, info) = closed.await.unwrap();
+        assert_eq!(id, child.id());
+        assert_eq!(info.cause, CancellationCause::Parent);
+        drop(child);
+        let (again, _) = child_ref.closed_with_id().await;
+        assert_eq!(again, id);
+    }
+}
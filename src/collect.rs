@@ -0,0 +1,220 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use tokio::sync::broadcast;
+use tokio::task::JoinSet;
+
+use crate::{CancellationSignal, Context, ContextInner};
+
+/// Collects the results of tasks spawned with `Context::spawn_collecting` and `CollectingHandle::spawn`, in the order
+/// they complete.
+///
+/// Tasks that are cancelled or time out produce no result. Once the context is cancelled no more results are
+/// returned. Dropping the handle aborts the tasks that are still running.
+pub struct CollectingHandle<T> {
+    inner: Arc<ContextInner>,
+    cancel_receiver: broadcast::Receiver<()>,
+    tasks: JoinSet<Option<T>>,
+}
+
+impl<T: Send + 'static> CollectingHandle<T> {
+    /// Spawn another task on the same context, collecting its result with the others
+    #[track_caller]
+    pub fn spawn<F>(&mut self, future: F)
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        if let Ok(task) = self.inner.task_future(None, future, None) {
+            self.tasks.spawn_on(task, &self.inner.runtime());
+        }
+    }
+
+    /// Number of tasks whose result has not been returned yet
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Whether all results have been returned
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Wait for the next task to complete and return its result.
+    ///
+    /// Returns None once every task has been collected, or once the context is cancelled. If a task panicked, the
+    /// panic is resumed here. Cancel safe: a result is only taken when the future completes.
+    pub async fn next_result(&mut self) -> Option<T> {
+        loop {
+            if self.inner.is_cancelled() {
+                return None;
+            }
+            let joined = tokio::select! {
+                _ = self.cancel_receiver.recv() => return None,
+                joined = self.tasks.join_next() => joined?,
+            };
+            match joined {
+                Ok(Some(result)) => return Some(result),
+                Ok(None) => continue,
+                Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+                Err(_) => continue,
+            }
+        }
+    }
+
+    /// Wait for all tasks and return their results in completion order.
+    ///
+    /// If the context is cancelled while waiting, the results collected so far are returned. Not cancel safe: dropping
+    /// the future drops those results, call `next_result` in a loop instead.
+    pub async fn collect_all(&mut self) -> Vec<T> {
+        let mut results = Vec::with_capacity(self.tasks.len());
+        while let Some(result) = self.next_result().await {
+            results.push(result);
+        }
+        results
+    }
+}
+
+/// Stream of the results of tasks spawned with `Context::spawn_futures_unordered`, in completion order.
+///
+/// Yields `Some(result)` for every task that completes. If the context is cancelled, yields a single `None` and then
+/// ends. Dropping the stream aborts the tasks that are still running.
+pub struct UnorderedResults<T> {
+    cancelled: Option<CancellationSignal>,
+    tasks: JoinSet<Option<T>>,
+}
+
+impl<T: Send + 'static> futures_core::Stream for UnorderedResults<T> {
+    type Item = Option<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Option<T>>> {
+        let this = self.get_mut();
+        let Some(cancelled) = this.cancelled.as_mut() else {
+            return Poll::Ready(None);
+        };
+        if Pin::new(cancelled).poll(cx).is_ready() {
+            this.cancelled = None;
+            this.tasks.abort_all();
+            return Poll::Ready(Some(None));
+        }
+        loop {
+            match std::task::ready!(this.tasks.poll_join_next(cx)) {
+                Some(Ok(Some(result))) => return Poll::Ready(Some(Some(result))),
+                Some(Err(e)) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+                Some(_) => continue,
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl Context {
+    /// Spawn every future of `futures` as a task, and stream their results as they complete.
+    /// ```rust, no_run
+    /// use futures_util::StreamExt;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn run() {
+    /// let mut ctx = Context::new();
+    /// let mut pages = ctx.spawn_futures_unordered((1..=3).map(|page| async move { page * 10 }));
+    /// while let Some(Some(page)) = pages.next().await {
+    ///     println!("got {}", page);
+    /// }
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn spawn_futures_unordered<I, F>(&mut self, futures: I) -> UnorderedResults<F::Output>
+    where
+        I: IntoIterator<Item = F>,
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let mut tasks = JoinSet::new();
+        for future in futures {
+            if let Ok(task) = self.inner.task_future(None, future, None) {
+                tasks.spawn_on(task, &self.inner.runtime());
+            }
+        }
+        UnorderedResults {
+            cancelled: Some(self.cancellation_signal()),
+            tasks,
+        }
+    }
+
+    /// Spawn a task and return a handle that collects its result. More tasks of the same result type can be added
+    /// to the handle with `CollectingHandle::spawn`.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn run() {
+    /// let mut ctx = Context::new();
+    /// let mut sizes = ctx.spawn_collecting(async { 1 });
+    /// sizes.spawn(async { 2 });
+    /// let total: usize = sizes.collect_all().await.into_iter().sum();
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn spawn_collecting<F>(&mut self, future: F) -> CollectingHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let mut handle = CollectingHandle {
+            inner: self.inner.clone(),
+            cancel_receiver: self.inner.subscribe(),
+            tasks: JoinSet::new(),
+        };
+        handle.spawn(future);
+        handle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn results_arrive_in_completion_order() {
+        let mut ctx = Context::new();
+        let mut results = ctx.spawn_collecting(async {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            "slow"
+        });
+        results.spawn(async { "fast" });
+        assert_eq!(results.len(), 2);
+        assert_eq!(results.collect_all().await, vec!["fast", "slow"]);
+        assert!(results.is_empty());
+
+        let mut lengths = ctx.spawn_collecting(async { 1usize });
+        lengths.spawn(std::future::pending());
+        assert_eq!(lengths.next_result().await, Some(1));
+        ctx.cancel();
+        assert_eq!(lengths.next_result().await, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unordered_results_end_with_none_on_cancel() {
+        use futures_util::StreamExt;
+
+        let mut ctx = Context::new();
+        let delays = [3, 1, 2];
+        let results = ctx.spawn_futures_unordered(delays.map(|delay| async move {
+            tokio::time::sleep(Duration::from_secs(delay)).await;
+            delay
+        }));
+        assert_eq!(results.collect::<Vec<_>>().await, vec![Some(1), Some(2), Some(3)]);
+
+        let mut child = ctx.new_child_context();
+        let mut results = child.spawn_futures_unordered([false, true].map(|stuck| async move {
+            if stuck {
+                std::future::pending::<()>().await;
+            }
+            1
+        }));
+        assert_eq!(results.next().await, Some(Some(1)));
+        drop(child);
+        assert_eq!(results.next().await, Some(None));
+        assert_eq!(results.next().await, None);
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+use crate::{CancellationCause, Context, ContextId, ContextInner, TaskCounts};
+
+/// What is left of a child context once it is gone, kept by `Context::retain_closed`
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ClosedChild {
+    pub id: ContextId,
+    pub name: Option<Arc<str>>,
+    pub cause: Option<CancellationCause>,
+    /// Tasks of the child and of its own descendants that were gone before it
+    pub counts: TaskCounts,
+    pub closed_at: Instant,
+}
+
+/// Bookkeeping for the children of a context that are gone
+#[derive(Default)]
+pub(crate) struct ClosedChildren {
+    log: Mutex<ClosedLog>,
+}
+
+#[derive(Default)]
+struct ClosedLog {
+    /// Task counts of every descendant that is gone
+    historical: TaskCounts,
+    /// Ring buffer of the last `retain` closed children
+    recent: VecDeque<ClosedChild>,
+    retain: usize,
+    /// Children dropped since the last compaction, whose entries may still be in the list of children
+    dead_entries: usize,
+    /// Set with `Context::compact_after`
+    threshold: Option<usize>,
+}
+
+impl ContextInner {
+    /// Task counts of every descendant that is gone
+    pub(crate) fn closed_historical(&self) -> TaskCounts {
+        self.closed.get().map(|closed| closed.log.lock().unwrap().historical).unwrap_or_default()
+    }
+
+    fn closed_log(&self) -> std::sync::MutexGuard<'_, ClosedLog> {
+        self.closed.get_or_init(Default::default).log.lock().unwrap()
+    }
+
+    /// Fold the counters of a child that is being dropped into this context
+    fn child_dropped(&self, child: &ContextInner) {
+        let counts = child.task_counters.counts().plus(child.closed_historical());
+        // the common case of a child that never ran a task under a parent nobody asked about closed children
+        if counts == TaskCounts::default() && self.closed.get().is_none() {
+            return;
+        }
+        let mut log = self.closed_log();
+        log.historical = log.historical.plus(counts);
+        if log.retain > 0 {
+            if log.recent.len() >= log.retain {
+                log.recent.pop_front();
+            }
+            let cause = child.state.lock().unwrap().cause.clone();
+            log.recent.push_back(ClosedChild { id: child.id, name: child.name.clone(), cause, counts, closed_at: Instant::now() });
+        }
+        log.dead_entries += 1;
+        let compact = log.threshold.is_some_and(|threshold| log.dead_entries >= threshold);
+        drop(log);
+        if compact {
+            // the child may be dropped while this context holds its own lock, the next drop compacts instead
+            if let Ok(mut state) = self.state.try_lock() {
+                state.children.retain(|child| child.strong_count() > 0);
+                drop(state);
+                self.closed_log().dead_entries = 0;
+            }
+        }
+    }
+
+    fn compact(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let before = state.children.len();
+        state.children.retain(|child| child.strong_count() > 0);
+        state.children.shrink_to_fit();
+        let removed = before - state.children.len();
+        drop(state);
+        if let Some(closed) = self.closed.get() {
+            closed.log.lock().unwrap().dead_entries = 0;
+        }
+        removed
+    }
+}
+
+impl Drop for ContextInner {
+    fn drop(&mut self) {
+        if let Some(parent) = &self.parent {
+            parent.child_dropped(self);
+        }
+    }
+}
+
+impl Context {
+    /// Remove the entries of children that are gone and release the memory they held, returning how many were
+    /// removed.
+    ///
+    /// The entry of a dropped child keeps the allocation of the child until it is removed. By default, entries are
+    /// removed whenever the list of children would have to grow, so without calling this the list holds at most
+    /// about twice the largest number of children that were alive at once, and no summaries of closed children are
+    /// kept. Long-lived contexts that had a burst of children can call it to shrink back, or have it done
+    /// automatically with `compact_after`.
+    pub fn compact(&self) -> usize {
+        self.inner.compact()
+    }
+
+    /// Compact this context automatically once `closed` children were dropped since the last compaction. With 1,
+    /// the entry of each child is removed as soon as it is gone.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let server = Context::new();
+    /// server.compact_after(1024);
+    /// server.retain_closed(100);
+    /// ```
+    pub fn compact_after(&self, closed: usize) {
+        self.inner.closed_log().threshold = Some(closed.max(1));
+    }
+
+    /// Keep the summaries of the last `n` children that were dropped, for debugging. 0, the default, keeps none.
+    pub fn retain_closed(&self, n: usize) {
+        let mut log = self.inner.closed_log();
+        log.retain = n;
+        let excess = log.recent.len().saturating_sub(n);
+        log.recent.drain(..excess);
+    }
+
+    /// The children kept by `retain_closed`, oldest first
+    pub fn closed_children(&self) -> Vec<ClosedChild> {
+        match self.inner.closed.get() {
+            Some(closed) => closed.log.lock().unwrap().recent.iter().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_million_closed_children_stay_bounded() {
+        let mut root = Context::new();
+        root.compact_after(1000);
+        root.retain_closed(16);
+        let _live = root.new_child_context();
+        for _ in 0..1_000_000 {
+            let mut child = Context::builder().name("request").build_child(&mut root);
+            drop(child.new_child_context());
+            drop(child);
+            let state = root.inner.state.lock().unwrap();
+            assert!(state.children.len() <= 1001);
+            assert!(state.children.capacity() <= 2048);
+        }
+        assert_eq!(root.closed_children().len(), 16);
+        assert_eq!(root.closed_children()[0].name.as_deref(), Some("request"));
+        assert_eq!(root.closed_children()[0].cause, Some(CancellationCause::Explicit));
+        root.compact();
+        assert_eq!(root.inner.state.lock().unwrap().children.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn closed_children_stay_bounded_by_default() {
+        let mut root = Context::new();
+        let _live = root.new_child_context();
+        for _ in 0..100_000 {
+            let mut child = root.new_child_context();
+            child.spawn(async {}).await.unwrap();
+            let state = root.inner.state.lock().unwrap();
+            assert!(state.children.len() <= state.children.capacity());
+            assert!(state.children.capacity() <= 4);
+        }
+        assert!(root.closed_children().is_empty());
+        let log = root.inner.closed_log();
+        assert_eq!(log.recent.capacity(), 0);
+        assert_eq!(log.historical.completed, 100_000);
+    }
+
+    #[tokio::test]
+    async fn dropped_children_fold_into_the_parent() {
+        let mut root = Context::new();
+        root.retain_closed(1);
+        for _ in 0..3 {
+            let mut child = root.new_child_context();
+            let mut grandchild = child.new_child_context();
+            grandchild.spawn(async {}).await.unwrap();
+            child.spawn(async {}).await.unwrap();
+        }
+        let snapshot = root.stats_epoch();
+        assert_eq!(snapshot.closed_children.completed, 6);
+        assert_eq!(root.closed_children()[0].counts.spawned, 2);
+    }
+}
@@ -0,0 +1,226 @@
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::Context;
+
+/// What `Context::consume` does with the messages still buffered in the channel once the context is cancelled
+#[derive(Debug, Clone)]
+pub enum DrainPolicy<T> {
+    /// Drop all buffered messages
+    DiscardRemaining,
+    /// Handle at most this many buffered messages, drop the rest
+    DrainUpTo(usize),
+    /// Handle buffered messages for at most this long, drop the rest. A handler still running at the end is
+    /// interrupted, and its message counted as dropped.
+    DrainFor(Duration),
+    /// Forward all buffered messages to another channel, without waiting for room in it. Messages that do not fit are
+    /// dropped.
+    HandoffTo(mpsc::Sender<T>),
+}
+
+/// Outcome of `Context::consume`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConsumeSummary {
+    /// Messages handled before the context was cancelled
+    pub processed: usize,
+    /// Buffered messages handled after the context was cancelled
+    pub drained: usize,
+    /// Buffered messages forwarded by `DrainPolicy::HandoffTo`
+    pub handed_off: usize,
+    /// Buffered messages that were neither handled nor forwarded
+    pub dropped: usize,
+}
+
+impl Context {
+    /// Handle every message of `rx` until this context is cancelled or all senders are gone, then deal with the
+    /// messages left in the channel according to `policy`.
+    ///
+    /// On cancellation the receiver is closed first, so senders see the channel closed instead of blocking, and the
+    /// messages already buffered are then drained, forwarded or dropped. A message whose handler is running when the
+    /// context is cancelled is handled to the end.
+    ///
+    /// The returned future does not borrow the context. Run it inline or with `tokio::spawn`, but not with
+    /// `Context::spawn`: that would stop it on cancellation before the drain.
+    /// ```rust, no_run
+    /// use tokio::sync::mpsc;
+    /// use tokio_tree_context::{Context, DrainPolicy};
+    ///
+    /// # async fn run() {
+    /// let ctx = Context::new();
+    /// let (tx, rx) = mpsc::channel::<String>(100);
+    /// let consumer = tokio::spawn(ctx.consume(rx, |line| async move {
+    ///     println!("{}", line);
+    /// }, DrainPolicy::DrainUpTo(10)));
+    /// // later
+    /// drop(ctx);
+    /// let summary = consumer.await.unwrap();
+    /// println!("dropped {} messages on shutdown", summary.dropped);
+    /// # }
+    /// ```
+    pub fn consume<T, H, Fut>(&self, mut rx: mpsc::Receiver<T>, mut handler: H, policy: DrainPolicy<T>) -> impl Future<Output = ConsumeSummary> + Send + 'static
+    where
+        T: Send + 'static,
+        H: FnMut(T) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let mut cancelled = self.cancellation_signal();
+        async move {
+            let mut summary = ConsumeSummary::default();
+            loop {
+                tokio::select! {
+                    biased;
+                    _ = &mut cancelled => break,
+                    msg = rx.recv() => match msg {
+                        Some(msg) => {
+                            handler(msg).await;
+                            summary.processed += 1;
+                        }
+                        None => return summary,
+                    },
+                }
+            }
+            rx.close();
+            match policy {
+                DrainPolicy::DiscardRemaining => {}
+                DrainPolicy::DrainUpTo(limit) => {
+                    while summary.drained < limit {
+                        match rx.recv().await {
+                            Some(msg) => {
+                                handler(msg).await;
+                                summary.drained += 1;
+                            }
+                            None => break,
+                        }
+                    }
+                }
+                DrainPolicy::DrainFor(duration) => {
+                    let deadline = Instant::now() + duration;
+                    while Instant::now() < deadline {
+                        let Some(msg) = rx.recv().await else {
+                            break;
+                        };
+                        if tokio::time::timeout_at(deadline, handler(msg)).await.is_err() {
+                            summary.dropped += 1;
+                            break;
+                        }
+                        summary.drained += 1;
+                    }
+                }
+                DrainPolicy::HandoffTo(tx) => {
+                    // never waits for room, the consumer of `tx` may be shutting down as well
+                    while let Some(msg) = rx.recv().await {
+                        match tx.try_send(msg) {
+                            Ok(()) => summary.handed_off += 1,
+                            Err(mpsc::error::TrySendError::Full(_)) => summary.dropped += 1,
+                            Err(mpsc::error::TrySendError::Closed(_)) => {
+                                summary.dropped += 1;
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+            while rx.recv().await.is_some() {
+                summary.dropped += 1;
+            }
+            summary
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn cancelled_with_backlog(policy: DrainPolicy<u32>) -> (ConsumeSummary, Vec<u32>) {
+        let ctx = Context::new();
+        let (tx, rx) = mpsc::channel(10);
+        let (handled_tx, mut handled_rx) = mpsc::unbounded_channel();
+        let (started_tx, mut started_rx) = mpsc::unbounded_channel();
+        let consumer = tokio::spawn(ctx.consume(rx, move |msg| {
+            let handled_tx = handled_tx.clone();
+            let started_tx = started_tx.clone();
+            async move {
+                let _ = started_tx.send(());
+                // the first message is slow, so the rest piles up
+                if msg == 0 {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+                let _ = handled_tx.send(msg);
+            }
+        }, policy));
+        tx.send(0).await.unwrap();
+        started_rx.recv().await.unwrap();
+        for msg in 1..=5 {
+            tx.send(msg).await.unwrap();
+        }
+        drop(ctx);
+        let summary = consumer.await.unwrap();
+        assert!(tx.send(6).await.is_err());
+        let mut handled = Vec::new();
+        while let Ok(msg) = handled_rx.try_recv() {
+            handled.push(msg);
+        }
+        (summary, handled)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drain_policies_account_for_every_message() {
+        let (summary, handled) = cancelled_with_backlog(DrainPolicy::DiscardRemaining).await;
+        assert_eq!(summary, ConsumeSummary { processed: 1, drained: 0, handed_off: 0, dropped: 5 });
+        assert_eq!(handled, vec![0]);
+
+        let (summary, handled) = cancelled_with_backlog(DrainPolicy::DrainUpTo(2)).await;
+        assert_eq!(summary, ConsumeSummary { processed: 1, drained: 2, handed_off: 0, dropped: 3 });
+        assert_eq!(handled, vec![0, 1, 2]);
+
+        let (handoff_tx, mut handoff_rx) = mpsc::channel(10);
+        let (summary, _) = cancelled_with_backlog(DrainPolicy::HandoffTo(handoff_tx)).await;
+        assert_eq!(summary, ConsumeSummary { processed: 1, drained: 0, handed_off: 5, dropped: 0 });
+        assert_eq!(handoff_rx.recv().await, Some(1));
+
+        // a full target does not hold up the shutdown
+        let (handoff_tx, mut handoff_rx) = mpsc::channel(3);
+        handoff_tx.send(100).await.unwrap();
+        let (summary, _) = cancelled_with_backlog(DrainPolicy::HandoffTo(handoff_tx)).await;
+        assert_eq!(summary, ConsumeSummary { processed: 1, drained: 0, handed_off: 2, dropped: 3 });
+        assert_eq!(handoff_rx.recv().await, Some(100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drain_for_interrupts_the_handler_at_the_deadline() {
+        let ctx = Context::new();
+        let (tx, rx) = mpsc::channel(10);
+        let (started_tx, mut started_rx) = mpsc::unbounded_channel();
+        let consumer = tokio::spawn(ctx.consume(rx, move |_: u32| {
+            let started_tx = started_tx.clone();
+            async move {
+                let _ = started_tx.send(());
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }, DrainPolicy::DrainFor(Duration::from_millis(2500))));
+        let start = Instant::now();
+        tx.send(0).await.unwrap();
+        started_rx.recv().await.unwrap();
+        for msg in 1..=5 {
+            tx.send(msg).await.unwrap();
+        }
+        drop(ctx);
+        let summary = consumer.await.unwrap();
+        // the message in flight is finished, then the drain runs for 2.5s: two handled, the third interrupted
+        assert_eq!(summary, ConsumeSummary { processed: 1, drained: 2, handed_off: 0, dropped: 3 });
+        assert_eq!(start.elapsed(), Duration::from_millis(3500));
+    }
+
+    #[tokio::test]
+    async fn stops_when_senders_are_gone() {
+        let ctx = Context::new();
+        let (tx, rx) = mpsc::channel(10);
+        tx.send(1).await.unwrap();
+        drop(tx);
+        let summary = ctx.consume(rx, |_: u32| async {}, DrainPolicy::DiscardRemaining).await;
+        assert_eq!(summary.processed, 1);
+    }
+}
@@ -0,0 +1,173 @@
+use std::future::Future;
+use std::sync::{Arc, Weak};
+use tokio::time::Instant;
+
+use crate::closed::CloseState;
+use crate::{CancellationCause, Context, ContextInner, ContextLimitExceeded, SpawnError};
+
+/// Non-owning handle to a context, created by `Context::as_ref`.
+///
+/// Unlike a `Context`, dropping it does not cancel anything, and it does not keep the context alive: once the context
+/// and everything running under it are gone, the handle only reports cancellation. Meant to be stored in request
+/// structs and passed through library APIs.
+#[derive(Clone)]
+pub struct ContextRef {
+    inner: Weak<ContextInner>,
+    pub(crate) close: Arc<CloseState>,
+}
+
+impl ContextRef {
+    pub(crate) fn upgrade(&self) -> Option<Arc<ContextInner>> {
+        self.inner.upgrade()
+    }
+
+    /// A handle to no context, which only reports cancellation
+    pub(crate) fn gone() -> ContextRef {
+        ContextRef { inner: Weak::new(), close: CloseState::gone() }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn same_context(&self, other: &ContextRef) -> bool {
+        self.inner.ptr_eq(&other.inner)
+    }
+
+    /// True if the context is cancelled or already gone
+    pub fn is_cancelled(&self) -> bool {
+        self.upgrade().is_none_or(|inner| inner.is_cancelled())
+    }
+
+    /// Resolves once the context is cancelled, right away if it is already gone
+    pub fn cancelled(&self) -> impl Future<Output = ()> + Send + Unpin + 'static {
+        let cancelled = self.upgrade().map(|inner| inner.cancelled());
+        Box::pin(async move {
+            if let Some(cancelled) = cancelled {
+                cancelled.await;
+            }
+        })
+    }
+
+    /// The earliest deadline of the context and its ancestors, None if it has none or is gone
+    pub fn deadline(&self) -> Option<Instant> {
+        self.upgrade().and_then(|inner| inner.effective_deadline())
+    }
+
+    /// Spawn a task under the context. Fails with `SpawnError::ScopeClosed` if the context is gone.
+    #[track_caller]
+    pub fn spawn<T>(&self, future: T) -> Result<tokio::task::JoinHandle<Option<T::Output>>, SpawnError>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        let inner = self.upgrade().ok_or(SpawnError::ScopeClosed)?;
+        inner.task_future(None, future, None).map(|task| inner.spawn(task))
+    }
+
+    /// Same as `Context::force_cancel`. Does nothing if the context is gone.
+    pub fn force_cancel(&self) {
+        if let Some(inner) = self.upgrade() {
+            inner.force_cancel();
+        }
+    }
+
+    /// Same as `Context::emergency_cancel`, for panic hooks and watchdog threads that only hold a handle. Does
+    /// nothing if the context is gone.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let server = Context::new();
+    /// let handle = server.as_ref();
+    /// std::panic::set_hook(Box::new(move |info| {
+    ///     handle.emergency_cancel(&info.to_string());
+    /// }));
+    /// ```
+    pub fn emergency_cancel(&self, reason: &str) {
+        if let Some(inner) = self.upgrade() {
+            inner.cancel(CancellationCause::Emergency { reason: Arc::from(reason) });
+        }
+    }
+
+    /// Create a child of the context. If the context is gone the child is created already cancelled.
+    ///
+    /// Panics if the context already has as many children as `Context::with_max_children` allows, see
+    /// `try_new_child_context`.
+    pub fn new_child_context(&self) -> Context {
+        self.try_new_child_context().unwrap()
+    }
+
+    /// Create a child of the context like `new_child_context`, or fail if the limit set with
+    /// `Context::with_max_children` is reached
+    pub fn try_new_child_context(&self) -> Result<Context, ContextLimitExceeded> {
+        match self.upgrade() {
+            Some(inner) => Context::try_create(Some(&inner), Context::builder()),
+            None => {
+                let child = Context::new();
+                child.inner.cancel(CancellationCause::Parent);
+                Ok(child)
+            }
+        }
+    }
+}
+
+impl From<&Context> for ContextRef {
+    fn from(ctx: &Context) -> Self {
+        ctx.as_ref()
+    }
+}
+
+impl Context {
+    /// A handle to this context that neither cancels it on drop nor keeps it alive
+    /// ```rust, no_run
+    /// use tokio_tree_context::{Context, ContextRef};
+    ///
+    /// struct Request {
+    ///     scope: ContextRef,
+    /// }
+    ///
+    /// let ctx = Context::new();
+    /// let request = Request { scope: ctx.as_ref() };
+    /// let _ = request.scope.spawn(async move { /* handle the request */ });
+    /// ```
+    pub fn as_ref(&self) -> ContextRef {
+        ContextRef {
+            inner: Arc::downgrade(&self.inner),
+            close: self.inner.close_state(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn context_ref_does_not_own_the_context() {
+        let ctx = Context::new();
+        let scope = ctx.as_ref();
+        drop(scope.clone());
+        assert!(!scope.is_cancelled());
+        let handle = scope.spawn(async { 1 }).unwrap();
+        assert_eq!(handle.await.unwrap(), Some(1));
+        let child = scope.new_child_context();
+        assert!(!child.is_cancelled());
+
+        drop(ctx);
+        assert!(scope.is_cancelled());
+        assert!(child.is_cancelled());
+        scope.cancelled().await;
+        drop(child);
+        assert_eq!(scope.spawn(async {}).unwrap_err(), SpawnError::ScopeClosed);
+        assert!(scope.new_child_context().is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn emergency_cancel_from_another_thread() {
+        let mut ctx = Context::new();
+        let child = ctx.new_child_context();
+        let waiting = child.as_ref().cancelled();
+        let scope = ctx.as_ref();
+        std::thread::spawn(move || scope.emergency_cancel("watchdog")).join().unwrap();
+        waiting.await;
+        assert_eq!(ctx.cancellation_cause(), Some(CancellationCause::Emergency { reason: Arc::from("watchdog") }));
+        assert_eq!(child.cancellation_cause(), Some(CancellationCause::Parent));
+    }
+}
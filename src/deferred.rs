@@ -0,0 +1,125 @@
+use std::future::Future;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::Context;
+
+impl Context {
+    /// Spawn a task that starts running `future` once `delay` has passed or `signal` resolves, whichever comes first.
+    ///
+    /// If the context is cancelled before either happens, `future` is never polled and the handle resolves to None.
+    /// ```rust, no_run
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    /// use tokio::sync::Notify;
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut ctx = Context::new();
+    /// let buffer_full = Arc::new(Notify::new());
+    /// let notify = buffer_full.clone();
+    /// ctx.spawn_after_delay_or_signal(Duration::from_secs(5), async move { notify.notified().await }, async move {
+    ///     // flush the buffer
+    /// });
+    /// ```
+    #[track_caller]
+    pub fn spawn_after_delay_or_signal<T>(
+        &mut self,
+        delay: Duration,
+        signal: impl Future<Output = ()> + Send + 'static,
+        future: T,
+    ) -> JoinHandle<Option<T::Output>>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        self.spawn(async move {
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {},
+                _ = signal => {},
+            }
+            future.await
+        })
+    }
+
+    /// Spawn a task that starts running `future` once `dep` resolves, e.g. once the initialization of another task is
+    /// done.
+    ///
+    /// If the context is cancelled before `dep` resolves, `future` is never polled and the handle resolves to None.
+    /// To let several tasks wait for the same dependency, pass each of them a clone of a `futures::future::Shared`.
+    /// ```rust, no_run
+    /// use tokio::sync::oneshot;
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut ctx = Context::new();
+    /// let (ready_tx, ready_rx) = oneshot::channel::<()>();
+    /// ctx.spawn(async move {
+    ///     // warm up the cache
+    ///     let _ = ready_tx.send(());
+    /// });
+    /// ctx.spawn_with_dependencies(async move { let _ = ready_rx.await; }, async move {
+    ///     // serve from the warm cache
+    /// });
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_dependencies<D, T>(&mut self, dep: D, future: T) -> JoinHandle<Option<T::Output>>
+    where
+        D: Future<Output = ()> + Send + 'static,
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        self.spawn(async move {
+            dep.await;
+            future.await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::oneshot;
+    use tokio::time::Instant;
+
+    #[tokio::test(start_paused = true)]
+    async fn first_trigger_starts_the_task() {
+        let mut root = Context::new();
+        let started = Instant::now();
+        let delay = Duration::from_secs(5);
+        let by_delay = root.spawn_after_delay_or_signal(delay, std::future::pending(), async move { started.elapsed() });
+        assert_eq!(by_delay.await.unwrap(), Some(delay));
+
+        let (tx, rx) = oneshot::channel::<()>();
+        let started = Instant::now();
+        let by_signal = root.spawn_after_delay_or_signal(delay, async move { let _ = rx.await; }, async move { started.elapsed() });
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        tx.send(()).unwrap();
+        assert_eq!(by_signal.await.unwrap(), Some(Duration::from_secs(1)));
+
+        let mut ctx = root.new_child_context();
+        let (ran_tx, mut ran_rx) = oneshot::channel();
+        let never = ctx.spawn_after_delay_or_signal(delay, std::future::pending(), async move { ran_tx.send(()).unwrap() });
+        drop(ctx);
+        assert_eq!(never.await.unwrap(), None);
+        assert!(ran_rx.try_recv().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dependent_task_waits_for_its_dependency() {
+        let mut ctx = Context::new();
+        let (init_tx, init_rx) = oneshot::channel::<()>();
+        let started = Instant::now();
+        let dependent = ctx.spawn_with_dependencies(async move { let _ = init_rx.await; }, async move { started.elapsed() });
+        ctx.spawn(async move {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            init_tx.send(()).unwrap();
+        });
+        assert_eq!(dependent.await.unwrap(), Some(Duration::from_secs(2)));
+
+        let mut child = ctx.new_child_context();
+        let (ran_tx, mut ran_rx) = oneshot::channel();
+        let never = child.spawn_with_dependencies(std::future::pending(), async move { ran_tx.send(()).unwrap() });
+        drop(child);
+        assert_eq!(never.await.unwrap(), None);
+        assert!(ran_rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,274 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::{Context, ContextId, ContextInner};
+
+/// Tasks of a context by how they ended, since the context was created. See `Context::stats_epoch`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TaskCounts {
+    pub spawned: u64,
+    pub completed: u64,
+    /// Stopped by the cancellation of the context, their own cancellation future, or an abort
+    pub cancelled: u64,
+    pub timed_out: u64,
+    pub panicked: u64,
+    /// Refused before they were spawned, and not counted in `spawned`: by a cancelled context, the admission hook,
+    /// the capacity or the deadline of the context
+    pub rejected: u64,
+}
+
+impl TaskCounts {
+    fn minus(self, earlier: TaskCounts) -> TaskCounts {
+        TaskCounts {
+            spawned: self.spawned.saturating_sub(earlier.spawned),
+            completed: self.completed.saturating_sub(earlier.completed),
+            cancelled: self.cancelled.saturating_sub(earlier.cancelled),
+            timed_out: self.timed_out.saturating_sub(earlier.timed_out),
+            panicked: self.panicked.saturating_sub(earlier.panicked),
+            rejected: self.rejected.saturating_sub(earlier.rejected),
+        }
+    }
+
+    pub(crate) fn plus(self, other: TaskCounts) -> TaskCounts {
+        TaskCounts {
+            spawned: self.spawned + other.spawned,
+            completed: self.completed + other.completed,
+            cancelled: self.cancelled + other.cancelled,
+            timed_out: self.timed_out + other.timed_out,
+            panicked: self.panicked + other.panicked,
+            rejected: self.rejected + other.rejected,
+        }
+    }
+}
+
+/// Lifetime counters of a context, updated on the task start and end path
+#[derive(Default)]
+pub(crate) struct TaskCounters {
+    spawned: AtomicU64,
+    completed: AtomicU64,
+    cancelled: AtomicU64,
+    timed_out: AtomicU64,
+    panicked: AtomicU64,
+    rejected: AtomicU64,
+    /// Most live tasks at once since the last snapshot
+    peak: AtomicUsize,
+}
+
+/// How a task spawned on a context ended, kept in its `TaskGuard` until it is dropped
+pub(crate) struct OutcomeSlot(AtomicU8);
+
+impl OutcomeSlot {
+    /// Work guards and transferable tasks, which are not counted
+    pub(crate) const UNTRACKED: u8 = 0;
+    pub(crate) const COMPLETED: u8 = 1;
+    /// Also what a task that is aborted or dropped ends with
+    pub(crate) const CANCELLED: u8 = 2;
+    pub(crate) const TIMED_OUT: u8 = 3;
+    pub(crate) const PANICKED: u8 = 4;
+
+    pub(crate) fn untracked() -> OutcomeSlot {
+        OutcomeSlot(AtomicU8::new(OutcomeSlot::UNTRACKED))
+    }
+
+    pub(crate) fn set(&self, outcome: u8) {
+        self.0.store(outcome, Ordering::Relaxed);
+    }
+
+    pub(crate) fn get(&self) -> u8 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl TaskCounters {
+    pub(crate) fn task_started(&self, live: usize) {
+        self.peak.fetch_max(live, Ordering::Relaxed);
+    }
+
+    /// Count a spawned task, whose outcome is then recorded by `task_ended`
+    pub(crate) fn track(&self, outcome: &OutcomeSlot) {
+        self.spawned.fetch_add(1, Ordering::Relaxed);
+        outcome.set(OutcomeSlot::CANCELLED);
+    }
+
+    pub(crate) fn task_rejected(&self) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn task_ended(&self, outcome: &OutcomeSlot) {
+        let counter = match outcome.get() {
+            OutcomeSlot::COMPLETED => &self.completed,
+            OutcomeSlot::CANCELLED => &self.cancelled,
+            OutcomeSlot::TIMED_OUT => &self.timed_out,
+            OutcomeSlot::PANICKED => &self.panicked,
+            _ => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn counts(&self) -> TaskCounts {
+        TaskCounts {
+            spawned: self.spawned.load(Ordering::Relaxed),
+            completed: self.completed.load(Ordering::Relaxed),
+            cancelled: self.cancelled.load(Ordering::Relaxed),
+            timed_out: self.timed_out.load(Ordering::Relaxed),
+            panicked: self.panicked.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counters of a context at one point in time, taken by `Context::stats_epoch`
+#[derive(Debug, Clone)]
+pub struct StatsSnapshot {
+    pub context: ContextId,
+    pub name: Option<Arc<str>>,
+    pub taken_at: Instant,
+    pub counts: TaskCounts,
+    pub live_tasks: usize,
+    /// Most live tasks at once since the previous snapshot of the context
+    pub peak_tasks: usize,
+    /// Tasks of the descendants that are gone, folded into the context when they were dropped
+    pub closed_children: TaskCounts,
+    /// Snapshots of the live children, filled by `Context::stats_epoch_recursive`
+    pub children: Vec<StatsSnapshot>,
+}
+
+/// What happened in a context between two snapshots, see `StatsSnapshot::diff`
+#[derive(Debug, Clone)]
+pub struct StatsDelta {
+    pub context: ContextId,
+    pub name: Option<Arc<str>>,
+    pub interval: Duration,
+    /// Tasks of the context itself
+    pub counts: TaskCounts,
+    /// Tasks of the context and the children in `children`
+    pub subtree: TaskCounts,
+    /// Most live tasks of the context itself at once during the interval
+    pub peak_tasks: usize,
+    /// Tasks of the descendants that were dropped, counted since they were created rather than for the interval
+    pub closed_children: TaskCounts,
+    pub children: Vec<StatsDelta>,
+}
+
+impl StatsSnapshot {
+    /// Changes from `earlier` to this snapshot, per child as well. A child without an earlier snapshot counts from
+    /// zero. Children that were gone by this snapshot are left out.
+    pub fn diff(&self, earlier: &StatsSnapshot) -> StatsDelta {
+        let children: Vec<StatsDelta> = self
+            .children
+            .iter()
+            .map(|child| match earlier.children.iter().find(|before| before.context == child.context) {
+                Some(before) => child.diff(before),
+                None => child.diff(&StatsSnapshot {
+                counts: TaskCounts::default(),
+                closed_children: TaskCounts::default(),
+                children: Vec::new(),
+                ..child.clone()
+            }),
+            })
+            .collect();
+        let counts = self.counts.minus(earlier.counts);
+        StatsDelta {
+            context: self.context,
+            name: self.name.clone(),
+            interval: self.taken_at.saturating_duration_since(earlier.taken_at),
+            counts,
+            subtree: children.iter().fold(counts, |total, child| total.plus(child.subtree)),
+            peak_tasks: self.peak_tasks,
+            closed_children: self.closed_children.minus(earlier.closed_children),
+            children,
+        }
+    }
+}
+
+impl ContextInner {
+    fn stats_snapshot(&self, now: Instant, recursive: bool) -> StatsSnapshot {
+        let live_tasks = self.active_tasks.load(Ordering::SeqCst);
+        // restarted from the live tasks, so the next snapshot reports the peak of its own interval
+        let peak_tasks = self.task_counters.peak.swap(live_tasks, Ordering::Relaxed).max(live_tasks);
+        let children = match recursive {
+            true => self.live_children().iter().map(|child| child.stats_snapshot(now, true)).collect(),
+            false => Vec::new(),
+        };
+        StatsSnapshot {
+            context: self.id,
+            name: self.name.clone(),
+            taken_at: now,
+            counts: self.task_counters.counts(),
+            live_tasks,
+            peak_tasks,
+            closed_children: self.closed_historical(),
+            children,
+        }
+    }
+}
+
+impl Context {
+    /// Snapshot the task counters of this context, to `diff` against a later snapshot.
+    ///
+    /// Taking a snapshot restarts the peak of live tasks, so each scrape sees the peak of its own interval. Have one
+    /// reporter take the snapshots of a context, or the peaks are split between them.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(ctx: Context) {
+    /// let mut last = ctx.stats_epoch_recursive();
+    /// loop {
+    ///     tokio::time::sleep(Duration::from_secs(15)).await;
+    ///     let now = ctx.stats_epoch_recursive();
+    ///     let delta = now.diff(&last);
+    ///     println!("{} tasks done in the subtree, peak {}", delta.subtree.completed, delta.peak_tasks);
+    ///     for child in &delta.children {
+    ///         println!("  {:?}: {} panicked", child.name, child.subtree.panicked);
+    ///     }
+    ///     last = now;
+    /// }
+    /// # }
+    /// ```
+    pub fn stats_epoch(&self) -> StatsSnapshot {
+        self.inner.stats_snapshot(Instant::now(), false)
+    }
+
+    /// `stats_epoch` of this context and all its live descendants
+    pub fn stats_epoch_recursive(&self) -> StatsSnapshot {
+        self.inner.stats_snapshot(Instant::now(), true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn diff_counts_the_interval_and_resets_the_peak() {
+        let mut root = Context::new();
+        let mut child = root.new_child_context();
+        root.spawn(async {}).await.unwrap();
+        let first = root.stats_epoch_recursive();
+        assert_eq!(first.counts.completed, 1);
+
+        let slow: Vec<_> = (0..3).map(|_| child.spawn(tokio::time::sleep(Duration::from_secs(1)))).collect();
+        child.spawn_with_timeout(std::future::pending::<()>(), Some(Duration::from_millis(10))).await.unwrap();
+        root.spawn(async { panic!("boom") }).await.unwrap_err();
+        let stopped = root.spawn(std::future::pending::<()>());
+        stopped.abort();
+        let _ = stopped.await;
+        for task in slow {
+            task.await.unwrap();
+        }
+        let second = root.stats_epoch_recursive();
+        let delta = second.diff(&first);
+        assert_eq!(delta.interval, Duration::from_secs(1));
+        assert_eq!(delta.counts, TaskCounts { spawned: 2, completed: 0, cancelled: 1, timed_out: 0, panicked: 1, rejected: 0 });
+        assert_eq!(delta.children[0].counts, TaskCounts { spawned: 4, completed: 3, cancelled: 0, timed_out: 1, panicked: 0, rejected: 0 });
+        assert_eq!(delta.subtree.spawned, 6);
+        assert_eq!(delta.children[0].peak_tasks, 4);
+
+        let third = root.stats_epoch_recursive();
+        assert_eq!(third.diff(&second).children[0].peak_tasks, 0);
+    }
+}
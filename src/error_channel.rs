@@ -0,0 +1,62 @@
+use std::future::Future;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::Context;
+
+/// Number of errors `Context::spawn_with_error_channel` buffers before `send` waits for the receiver
+pub const ERROR_CHANNEL_CAPACITY: usize = 64;
+
+impl Context {
+    /// Spawn a task that reports non-fatal errors while it runs.
+    ///
+    /// `factory` is given the sending half of a bounded channel and returns the future to run. Errors sent by the task
+    /// reach the returned receiver right away, and the receiver sees the end of the stream once the task finishes or
+    /// is cancelled.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example() {
+    /// let mut ctx = Context::new();
+    /// let (handle, mut errors) = ctx.spawn_with_error_channel(|errors| async move {
+    ///     for attempt in 1..=3 {
+    ///         let _ = errors.send(format!("attempt {attempt} failed, retrying")).await;
+    ///     }
+    ///     "done"
+    /// });
+    /// while let Some(error) = errors.recv().await {
+    ///     eprintln!("{error}");
+    /// }
+    /// println!("{:?}", handle.await);
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_error_channel<E, F, Fut>(&mut self, factory: F) -> (JoinHandle<Option<Fut::Output>>, mpsc::Receiver<E>)
+    where
+        F: FnOnce(mpsc::Sender<E>) -> Fut,
+        Fut: Future + Send + 'static,
+        Fut::Output: Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel(ERROR_CHANNEL_CAPACITY);
+        (self.spawn(factory(tx)), rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn errors_are_streamed_until_cancelled() {
+        let mut root = Context::new();
+        let mut ctx = root.new_child_context();
+        let (handle, mut errors) = ctx.spawn_with_error_channel(|errors| async move {
+            errors.send("retrying").await.unwrap();
+            std::future::pending::<()>().await
+        });
+        assert_eq!(errors.recv().await, Some("retrying"));
+        drop(ctx);
+        assert_eq!(errors.recv().await, None);
+        assert_eq!(handle.await.unwrap(), None);
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt;
+use std::future::Future;
+use std::panic::Location;
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::task::JoinHandle;
+
+use crate::{Context, ContextInner, TaskOptions};
+
+/// How a task reported to the handlers of `Context::with_error_handler` failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskErrorKind {
+    Panic,
+    /// The task was spawned with `Context::spawn_fallible` and returned an error
+    Error,
+}
+
+/// A task that panicked or returned an error, as passed to the handlers of `Context::with_error_handler`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskError {
+    pub task_id: u64,
+    pub name: Option<Arc<str>>,
+    pub kind: TaskErrorKind,
+    /// The panic message, or the error formatted with `Display`
+    pub message: String,
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = match self.kind {
+            TaskErrorKind::Panic => "panicked",
+            TaskErrorKind::Error => "failed",
+        };
+        match &self.name {
+            Some(name) => write!(f, "task {} ({}) {}: {}", self.task_id, name, failed, self.message),
+            None => write!(f, "task {} {}: {}", self.task_id, failed, self.message),
+        }
+    }
+}
+
+impl std::error::Error for TaskError {}
+
+type ErrorHandler = Arc<dyn Fn(TaskError) + Send + Sync>;
+
+/// Handlers registered with `Context::with_error_handler`
+#[derive(Default)]
+pub(crate) struct ErrorHandlers {
+    handlers: Mutex<Vec<ErrorHandler>>,
+}
+
+/// Filled in by a task spawned with `spawn_fallible` when it returns an error
+pub(crate) type ErrorSlot = Arc<OnceLock<String>>;
+
+impl ContextInner {
+    /// Pass a failed task to the error handlers, on the thread that completes the task
+    pub(crate) fn report_error(&self, task_id: u64, name: Option<&Arc<str>>, kind: TaskErrorKind, message: String) {
+        let handlers = self.error_handlers.handlers.lock().unwrap().clone();
+        if handlers.is_empty() {
+            return;
+        }
+        let error = TaskError { task_id, name: name.cloned(), kind, message };
+        for handler in handlers {
+            handler(error.clone());
+        }
+    }
+}
+
+impl Context {
+    /// Call `handler` whenever a task of this context panics, or a task spawned with `spawn_fallible` returns an
+    /// error. Handlers run synchronously in the task as it completes, so they should be quick. Each call adds a
+    /// handler, all of them are called.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example() {
+    /// let mut ctx = Context::new().with_error_handler(|error| eprintln!("{error}"));
+    /// ctx.spawn_fallible(async { "42x".parse::<u32>() });
+    /// # }
+    /// ```
+    pub fn with_error_handler(self, handler: impl Fn(TaskError) + Send + Sync + 'static) -> Context {
+        self.inner.error_handlers.handlers.lock().unwrap().push(Arc::new(handler));
+        self
+    }
+
+    /// Spawn a task whose errors are reported to the handlers registered with `with_error_handler`. The result still
+    /// reaches the `JoinHandle`.
+    #[track_caller]
+    pub fn spawn_fallible<T, R, E>(&mut self, future: T) -> JoinHandle<Option<Result<R, E>>>
+    where
+        T: Future<Output = Result<R, E>> + Send + 'static,
+        R: Send + 'static,
+        E: fmt::Display + Send + 'static,
+    {
+        let slot = ErrorSlot::default();
+        let failed = slot.clone();
+        let future = async move {
+            let result = future.await;
+            if let Err(error) = &result {
+                let _ = failed.set(error.to_string());
+            }
+            result
+        };
+        let options = TaskOptions { error: Some(slot), ..Default::default() };
+        self.inner.spawn_or_none(self.inner.task_future_at(None, future, Location::caller(), options))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn panics_and_errors_reach_every_handler() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let counted = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let count = counted.clone();
+        let mut ctx = Context::new()
+            .with_error_handler(move |error| tx.send(error).unwrap())
+            .with_error_handler(move |_| {
+                count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            });
+
+        assert!(ctx.spawn_named("crash", async { panic!("boom") }).await.unwrap_err().is_panic());
+        let error = rx.recv().await.unwrap();
+        assert_eq!((error.name.as_deref(), error.kind, error.message.as_str()), (Some("crash"), TaskErrorKind::Panic, "boom"));
+
+        let parsed = ctx.spawn_fallible(async { "x".parse::<u32>() }).await.unwrap().unwrap();
+        assert!(parsed.is_err());
+        let error = rx.recv().await.unwrap();
+        assert_eq!((error.kind, error.message), (TaskErrorKind::Error, "invalid digit found in string".to_string()));
+
+        assert_eq!(ctx.spawn_fallible(async { "7".parse::<u32>() }).await.unwrap().unwrap(), Ok(7));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(counted.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+}
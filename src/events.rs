@@ -0,0 +1,229 @@
+use std::future::Future;
+use std::panic::Location;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::tree::SpawnLocation;
+use crate::{CancellationCause, Context, ContextId, ContextInner, SpawnError, StalledTask};
+
+/// Number of events a context buffers for its subscribers. Subscribers that fall further behind skip the oldest
+/// events.
+pub const EVENT_CAPACITY: usize = 256;
+
+/// Something that happened in a context tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub enum ContextEvent {
+    TaskSpawned {
+        context: ContextId,
+        task_id: u64,
+        name: Option<Arc<str>>,
+        spawned_at: SpawnLocation,
+    },
+    ContextCancelled {
+        context: ContextId,
+        cause: CancellationCause,
+    },
+    /// A task was refused before it was spawned, see `Context::spawn`
+    TaskRejected {
+        context: ContextId,
+        error: SpawnError,
+    },
+    /// A task has not been polled for longer than the stall threshold of its context. Published once per stall.
+    TaskStalled {
+        context: ContextId,
+        task_id: u64,
+        spawned_at: SpawnLocation,
+        stalled_for: Duration,
+    },
+}
+
+type Recv = Pin<Box<dyn Future<Output = (Result<ContextEvent, broadcast::error::RecvError>, broadcast::Receiver<ContextEvent>)> + Send>>;
+
+/// Stream of `ContextEvent`s, created by `Context::subscribe_events` and `Context::subscribe_to_parent_events`.
+///
+/// It does not keep any context alive, and ends once the context it subscribed to is gone.
+pub struct EventStream {
+    recv: Option<Recv>,
+}
+
+fn recv(mut rx: broadcast::Receiver<ContextEvent>) -> Recv {
+    Box::pin(async move { (rx.recv().await, rx) })
+}
+
+impl EventStream {
+    /// Receive the next event, or None once the context is gone. Events skipped after lagging behind are lost.
+    /// Cancel safe: the pending receive is kept in the stream, so no event is lost by dropping the future.
+    pub async fn recv(&mut self) -> Option<ContextEvent> {
+        std::future::poll_fn(|cx| self.poll_event(cx)).await
+    }
+
+    fn poll_event(&mut self, cx: &mut TaskContext<'_>) -> Poll<Option<ContextEvent>> {
+        loop {
+            let Some(pending) = self.recv.as_mut() else {
+                return Poll::Ready(None);
+            };
+            let (res, rx) = std::task::ready!(pending.as_mut().poll(cx));
+            match res {
+                Ok(event) => {
+                    self.recv = Some(recv(rx));
+                    return Poll::Ready(Some(event));
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => self.recv = Some(recv(rx)),
+                Err(broadcast::error::RecvError::Closed) => self.recv = None,
+            }
+        }
+    }
+}
+
+impl futures_core::Stream for EventStream {
+    type Item = ContextEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<ContextEvent>> {
+        self.get_mut().poll_event(cx)
+    }
+}
+
+impl ContextInner {
+    /// Publish `event` to the subscribers of this context and of all its ancestors
+    fn emit(&self, event: impl FnOnce() -> ContextEvent) {
+        let mut event = LazyEvent::new(event);
+        let mut context = Some(self);
+        while let Some(current) = context {
+            if let Some(sender) = current.events.get().filter(|sender| sender.receiver_count() > 0) {
+                let _ = sender.send(event.get().clone());
+            }
+            context = current.parent.as_deref();
+        }
+    }
+
+    pub(crate) fn emit_task_spawned(&self, task_id: u64, name: &Option<Arc<str>>, location: &'static Location<'static>) {
+        self.emit(|| ContextEvent::TaskSpawned {
+            context: self.id,
+            task_id,
+            name: name.clone(),
+            spawned_at: location.into(),
+        });
+    }
+
+    pub(crate) fn emit_cancelled(&self) {
+        self.emit(|| ContextEvent::ContextCancelled {
+            context: self.id,
+            cause: self.cause().unwrap_or(CancellationCause::Explicit),
+        });
+    }
+
+    pub(crate) fn emit_task_rejected(&self, error: &SpawnError) {
+        self.emit(|| ContextEvent::TaskRejected {
+            context: self.id,
+            error: error.clone(),
+        });
+    }
+
+    pub(crate) fn emit_task_stalled(&self, task: &StalledTask) {
+        self.emit(|| ContextEvent::TaskStalled {
+            context: self.id,
+            task_id: task.id,
+            spawned_at: task.spawned_at,
+            stalled_for: task.stalled_for,
+        });
+    }
+
+    fn subscribe_events(&self) -> EventStream {
+        let sender = self.events.get_or_init(|| broadcast::channel(EVENT_CAPACITY).0);
+        EventStream {
+            recv: Some(recv(sender.subscribe())),
+        }
+    }
+}
+
+/// Builds the event only if somebody is subscribed
+struct LazyEvent<F> {
+    build: Option<F>,
+    event: Option<ContextEvent>,
+}
+
+impl<F: FnOnce() -> ContextEvent> LazyEvent<F> {
+    fn new(build: F) -> Self {
+        LazyEvent { build: Some(build), event: None }
+    }
+
+    fn get(&mut self) -> &ContextEvent {
+        let build = &mut self.build;
+        self.event.get_or_insert_with(|| (build.take().unwrap())())
+    }
+}
+
+impl Context {
+    /// Events of this context and all its descendants
+    pub fn subscribe_events(&self) -> EventStream {
+        self.inner.subscribe_events()
+    }
+
+    /// Events of the whole tree this context belongs to, including those of all its ancestors.
+    ///
+    /// This subscribes at the root of the tree, so a monitoring task can observe everything without holding each
+    /// context.
+    /// ```rust, no_run
+    /// use tokio_tree_context::{Context, ContextEvent};
+    ///
+    /// # async fn example(ctx: &Context) {
+    /// let mut events = ctx.subscribe_to_parent_events();
+    /// while let Some(event) = events.recv().await {
+    ///     if let ContextEvent::ContextCancelled { context, cause } = event {
+    ///         println!("{} cancelled: {:?}", context, cause);
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn subscribe_to_parent_events(&self) -> EventStream {
+        let mut root = &self.inner;
+        while let Some(parent) = &root.parent {
+            root = parent;
+        }
+        root.subscribe_events()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn events_propagate_to_ancestor_subscribers() {
+        let mut root = Context::new();
+        let mut child = root.new_child_context();
+        let mut grandchild = child.new_child_context();
+        let mut all = grandchild.subscribe_to_parent_events();
+        let mut subtree = child.subscribe_events();
+
+        root.spawn(async {});
+        grandchild.spawn_named("leaf", async {});
+        let grandchild_id = grandchild.id();
+        drop(grandchild);
+
+        assert!(matches!(all.recv().await, Some(ContextEvent::TaskSpawned { context, .. }) if context == root.id()));
+        for events in [&mut all, &mut subtree] {
+            match events.recv().await {
+                Some(ContextEvent::TaskSpawned { context, name, .. }) => {
+                    assert_eq!(context, grandchild_id);
+                    assert_eq!(name.as_deref(), Some("leaf"));
+                }
+                event => panic!("unexpected event {:?}", event),
+            }
+            let cancelled = ContextEvent::ContextCancelled {
+                context: grandchild_id,
+                cause: CancellationCause::Explicit,
+            };
+            assert_eq!(events.recv().await, Some(cancelled));
+        }
+        drop(child);
+        drop(root);
+        // the stream ends once the root is gone, after the remaining events
+        while all.recv().await.is_some() {}
+    }
+}
@@ -0,0 +1,215 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context as TaskContext, Poll, Waker};
+use tokio::task::{AbortHandle, JoinHandle};
+
+use crate::Context;
+
+/// Tasks spawned with `Context::spawn_exclusive`, keyed by the type of the key and then by the key itself
+#[derive(Default)]
+pub(crate) struct ExclusiveTasks {
+    tasks: Mutex<HashMap<TypeId, Box<dyn Any + Send>>>,
+}
+
+struct ExclusiveTask {
+    /// Weak, so the last caller holding the handle can take the `JoinHandle` back
+    handle: Weak<dyn Any + Send + Sync>,
+    /// The `Outcome` of the task, for callers that join it
+    outcome: Arc<dyn Any + Send + Sync>,
+    abort_handle: AbortHandle,
+    /// Held by the task of the key while it runs
+    turn: Arc<tokio::sync::Mutex<()>>,
+}
+
+/// The task of a key, returned by `Context::spawn_exclusive`. Derefs to the `JoinHandle` of the task.
+///
+/// Every caller that got the task can await its handle, which resolves to a clone of the output, or to None if the
+/// task did not complete.
+pub struct ExclusiveHandle<T> {
+    handle: Arc<JoinHandle<Option<T>>>,
+    outcome: Arc<Outcome<T>>,
+    spawned: bool,
+}
+
+/// Output of a task, shared by every handle to it
+struct Outcome<T> {
+    state: Mutex<OutcomeState<T>>,
+}
+
+struct OutcomeState<T> {
+    /// Set once the task is gone, to None if it did not complete
+    output: Option<Option<T>>,
+    wakers: Vec<Waker>,
+}
+
+impl<T> Outcome<T> {
+    fn set(&self, output: Option<T>) {
+        let mut state = self.state.lock().unwrap();
+        if state.output.is_none() {
+            state.output = Some(output);
+            state.wakers.drain(..).for_each(Waker::wake);
+        }
+    }
+}
+
+/// Sets the outcome to None if the task is dropped before it completed
+struct Publish<T>(Arc<Outcome<T>>);
+
+impl<T> Drop for Publish<T> {
+    fn drop(&mut self) {
+        self.0.set(None);
+    }
+}
+
+impl<T> ExclusiveHandle<T> {
+    /// False if the call joined the task that was already running for the key
+    pub fn spawned(&self) -> bool {
+        self.spawned
+    }
+
+    /// The handle shared by every caller that got this task
+    pub fn shared(&self) -> &Arc<JoinHandle<Option<T>>> {
+        &self.handle
+    }
+
+    /// The `JoinHandle`, to await the task, if no other caller holds it anymore
+    pub fn into_join_handle(self) -> Result<JoinHandle<Option<T>>, Self> {
+        let ExclusiveHandle { handle, outcome, spawned } = self;
+        Arc::try_unwrap(handle).map_err(|handle| ExclusiveHandle { handle, outcome, spawned })
+    }
+}
+
+impl<T: Clone> Future for ExclusiveHandle<T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<T>> {
+        let mut state = self.outcome.state.lock().unwrap();
+        if let Some(output) = &state.output {
+            return Poll::Ready(output.clone());
+        }
+        if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+impl<T> Deref for ExclusiveHandle<T> {
+    type Target = JoinHandle<Option<T>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.handle
+    }
+}
+
+impl Context {
+    /// Spawn `future` as the task of `key`, unless a task of the same key is still running under this context, in
+    /// which case its handle is returned and `future` is dropped.
+    ///
+    /// Unlike `spawn_once`, tasks of a key never overlap: should the running task have a different output type, or
+    /// every handle to it be dropped, the new task is spawned but only starts once the running one has finished.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut ctx = Context::new();
+    /// let compaction = ctx.spawn_exclusive("compact", async move { /* compact the log */ });
+    /// if !compaction.spawned() {
+    ///     println!("compaction already running");
+    /// }
+    /// ```
+    #[track_caller]
+    pub fn spawn_exclusive<K, F>(&mut self, key: K, future: F) -> ExclusiveHandle<F::Output>
+    where
+        K: Hash + Eq + Send + Sync + 'static,
+        F: Future + Send + 'static,
+        F::Output: Clone + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        let mut tasks = inner.exclusive_tasks.tasks.lock().unwrap();
+        let by_key = tasks
+            .entry(TypeId::of::<K>())
+            .or_insert_with(|| Box::new(HashMap::<K, ExclusiveTask>::new()))
+            .downcast_mut::<HashMap<K, ExclusiveTask>>()
+            .unwrap();
+        by_key.retain(|_, task| !task.abort_handle.is_finished());
+        let turn = match by_key.get(&key) {
+            Some(task) => {
+                let handle = task.handle.upgrade().and_then(|handle| handle.downcast::<JoinHandle<Option<F::Output>>>().ok());
+                let outcome = task.outcome.clone().downcast::<Outcome<F::Output>>().ok();
+                match handle.zip(outcome) {
+                    Some((handle, outcome)) => return ExclusiveHandle { handle, outcome, spawned: false },
+                    None => task.turn.clone(),
+                }
+            }
+            None => Default::default(),
+        };
+        let waiting = turn.clone().try_lock_owned().map_err(|_| turn.clone());
+        let outcome = Arc::new(Outcome { state: Mutex::new(OutcomeState { output: None, wakers: Vec::new() }) });
+        let publish = Publish(outcome.clone());
+        let handle = Arc::new(self.spawn(async move {
+            let _turn = match waiting {
+                Ok(turn) => turn,
+                Err(turn) => turn.lock_owned().await,
+            };
+            let output = future.await;
+            publish.0.set(Some(output.clone()));
+            output
+        }));
+        let shared: Arc<dyn Any + Send + Sync> = handle.clone();
+        by_key.insert(key, ExclusiveTask {
+            handle: Arc::downgrade(&shared),
+            outcome: outcome.clone(),
+            abort_handle: handle.abort_handle(),
+            turn,
+        });
+        ExclusiveHandle { handle, outcome, spawned: true }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn tasks_of_a_key_never_overlap() {
+        let mut ctx = Context::new();
+        let running = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let counter = running.clone();
+        let first = ctx.spawn_exclusive("compact", async move {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let _ = rx.await;
+            counter.fetch_sub(1, Ordering::SeqCst);
+        });
+        let again = ctx.spawn_exclusive("compact", async { unreachable!() });
+        assert!(first.spawned() && !again.spawned());
+        assert!(Arc::ptr_eq(first.shared(), again.shared()));
+
+        // a different output type waits for its turn
+        let counter = running.clone();
+        let typed = ctx.spawn_exclusive("compact", async move { counter.load(Ordering::SeqCst) });
+        assert!(typed.spawned());
+        tokio::task::yield_now().await;
+        assert!(!typed.is_finished());
+        tx.send(()).unwrap();
+        assert_eq!(typed.into_join_handle().ok().unwrap().await.unwrap(), Some(0));
+
+        // every caller sees the output, without waiting for the others to drop their handles
+        assert_eq!(again.await, Some(()));
+        assert_eq!(first.await, Some(()));
+        let fresh = ctx.spawn_exclusive("compact", async {});
+        assert!(fresh.spawned());
+
+        let stuck = ctx.spawn_exclusive("stuck", std::future::pending::<u32>());
+        let joined = ctx.spawn_exclusive("stuck", async { 1u32 });
+        assert!(!joined.spawned());
+        stuck.abort();
+        assert_eq!(joined.await, None);
+    }
+}
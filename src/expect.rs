@@ -0,0 +1,108 @@
+use std::panic::Location;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+use crate::{Context, ContextId, ContextInner};
+
+/// Set by `Context::expect_explicit_cancel`, cleared by an explicit cancel
+pub(crate) struct ExpectedCancel {
+    location: &'static Location<'static>,
+    panic_in_debug: bool,
+}
+
+pub(crate) type ExpectedCancelSlot = Mutex<Option<ExpectedCancel>>;
+
+impl ContextInner {
+    /// Report of a drop without an explicit cancel while tasks are live, if that was not expected
+    pub(crate) fn unexpected_drop(&self) -> Option<UnexpectedDrop> {
+        let expected = self.expected_cancel.lock().unwrap().take()?;
+        let live = self.active_tasks.load(Ordering::SeqCst);
+        if live == 0 {
+            return None;
+        }
+        let report = format!(
+            "{} dropped without cancel() with {} live tasks ({}), expect_explicit_cancel was called at {}:{}",
+            self.id,
+            live,
+            crate::work::outstanding(self).join(", "),
+            expected.location.file(),
+            expected.location.line(),
+        );
+        Some(UnexpectedDrop {
+            context: self.id,
+            report,
+            panic_in_debug: expected.panic_in_debug,
+        })
+    }
+}
+
+/// Collected before a dropped context cancels and reported after, so a panic does not keep it from cancelling
+pub(crate) struct UnexpectedDrop {
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    context: ContextId,
+    report: String,
+    panic_in_debug: bool,
+}
+
+impl UnexpectedDrop {
+    pub(crate) fn report(self) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(context = %self.context, "{}", self.report);
+        #[cfg(not(feature = "tracing"))]
+        eprintln!("warning: {}", self.report);
+        if cfg!(debug_assertions) && self.panic_in_debug && !std::thread::panicking() {
+            panic!("{}", self.report);
+        }
+    }
+}
+
+impl Context {
+    /// Warn loudly if this context is dropped while it has live tasks, unless it is dropped with `cancel()`,
+    /// `cancel_and_wait()` or after `disarm_expectation()`. For contexts that are meant to be handed off, where a
+    /// plain drop is a bug.
+    ///
+    /// The warning goes to stderr, or is a `tracing` warning event with the `tracing` feature. With `panic_in_debug`,
+    /// debug builds also panic.
+    #[track_caller]
+    pub fn expect_explicit_cancel(&self, panic_in_debug: bool) {
+        *self.inner.expected_cancel.lock().unwrap() = Some(ExpectedCancel {
+            location: Location::caller(),
+            panic_in_debug,
+        });
+    }
+
+    /// Let this context be dropped without a warning after `expect_explicit_cancel`
+    pub fn disarm_expectation(&self) {
+        self.inner.expected_cancel.lock().unwrap().take();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn unexpected_drop_is_reported() {
+        let mut ctx = Context::new();
+        ctx.expect_explicit_cancel(false);
+        let line = line!() - 1;
+        ctx.spawn_named("session", std::future::pending::<()>());
+        let report = ctx.inner.unexpected_drop().unwrap().report;
+        assert!(report.contains("1 live tasks (session)"), "{report}");
+        assert!(report.ends_with(&format!("{}:{}", file!(), line)), "{report}");
+
+        ctx.expect_explicit_cancel(false);
+        ctx.disarm_expectation();
+        assert!(ctx.inner.unexpected_drop().is_none());
+
+        ctx.expect_explicit_cancel(true);
+        let scope = ctx.as_ref();
+        assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(ctx))).is_err());
+        assert!(scope.is_cancelled());
+
+        let mut ctx = Context::new();
+        ctx.expect_explicit_cancel(true);
+        ctx.spawn(std::future::pending::<()>());
+        ctx.cancel();
+    }
+}
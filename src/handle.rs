@@ -0,0 +1,169 @@
+use std::future::Future;
+use std::panic::Location;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use tokio::task::{AbortHandle, JoinError};
+
+use crate::{CancellationCause, Cancelled, Context, ContextInner, SpawnLocation};
+
+type Joined<T> = Pin<Box<dyn Future<Output = Result<Option<T>, JoinError>> + Send>>;
+
+/// Handle of a task spawned with `Context::spawn_handle`, with helpers that turn a cancelled task into an error.
+///
+/// Awaiting it gives the same result as awaiting the `JoinHandle` of `Context::spawn`.
+pub struct TaskHandle<T> {
+    joined: Joined<T>,
+    abort: AbortHandle,
+    inner: Arc<ContextInner>,
+    location: &'static Location<'static>,
+}
+
+impl<T: Send + 'static> TaskHandle<T> {
+    /// Abort the task without waiting for it
+    pub fn abort(&self) {
+        self.abort.abort();
+    }
+
+    /// Turn the output of the task into `Err(Cancelled)` if it was cancelled, timed out or aborted. A panic of the
+    /// task is resumed.
+    ///
+    /// Not cancel safe, since the handle is consumed: dropping the future detaches the task and its output is lost.
+    /// In a `select!`, await `&mut handle` instead.
+    pub async fn into_result(self) -> Result<T, Cancelled> {
+        match self.joined.await {
+            Ok(Some(output)) => Ok(output),
+            Ok(None) => Err(Cancelled),
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(_) => Err(Cancelled),
+        }
+    }
+
+    /// The output of the task, or None if it did not complete. A panic of the task is resumed.
+    pub async fn ok(self) -> Option<T> {
+        self.into_result().await.ok()
+    }
+
+    /// The output of the task. Panics if it did not complete, naming the context, the spawn location and why the
+    /// context and its ancestors were cancelled. Meant for tests.
+    pub fn unwrap_completed(self) -> impl Future<Output = T> + Send + 'static {
+        let (inner, location) = (self.inner.clone(), self.location);
+        async move {
+            match self.into_result().await {
+                Ok(output) => output,
+                Err(Cancelled) => panic!(
+                    "task of {} spawned at {} did not complete: {}",
+                    describe(&inner),
+                    SpawnLocation::from(location),
+                    cancel_reasons(&inner)
+                ),
+            }
+        }
+    }
+
+    /// A handle whose output is `f` applied to the output of this one
+    pub fn map<U, F>(self, f: F) -> TaskHandle<U>
+    where
+        F: FnOnce(T) -> U + Send + 'static,
+        U: Send + 'static,
+    {
+        let joined = self.joined;
+        TaskHandle {
+            joined: Box::pin(async move { joined.await.map(|output| output.map(f)) }),
+            abort: self.abort,
+            inner: self.inner,
+            location: self.location,
+        }
+    }
+}
+
+impl<T> Future for TaskHandle<T> {
+    type Output = Result<Option<T>, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        self.joined.as_mut().poll(cx)
+    }
+}
+
+fn describe(inner: &ContextInner) -> String {
+    match &inner.name {
+        Some(name) => format!("{} ({})", inner.id, name),
+        None => inner.id.to_string(),
+    }
+}
+
+/// The causes of the context and of the ancestors it inherited its cancellation from, nearest first
+fn cancel_reasons(inner: &ContextInner) -> String {
+    let mut reasons = Vec::new();
+    let mut context = Some(inner);
+    while let Some(inner) = context {
+        let Some(cause) = inner.cause() else {
+            break;
+        };
+        reasons.push(format!("{} cancelled: {:?}", describe(inner), cause));
+        context = match cause {
+            CancellationCause::Parent => inner.parent.as_deref(),
+            _ => None,
+        };
+    }
+    if reasons.is_empty() {
+        return "timed out or aborted, the context is not cancelled".to_string();
+    }
+    reasons.join(" <- ")
+}
+
+impl Context {
+    /// Spawn a task like `spawn`, returning a `TaskHandle` with helpers such as `into_result` and
+    /// `unwrap_completed`.
+    /// ```rust, no_run
+    /// use tokio_tree_context::{Cancelled, Context};
+    ///
+    /// # async fn example() -> Result<(), Cancelled> {
+    /// let mut ctx = Context::new();
+    /// let doubled = ctx.spawn_handle(async { 21 }).map(|n| n * 2).into_result().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn spawn_handle<T>(&mut self, future: T) -> TaskHandle<T::Output>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        let handle = self.spawn(future);
+        TaskHandle {
+            abort: handle.abort_handle(),
+            joined: Box::pin(handle),
+            inner: self.inner.clone(),
+            location: Location::caller(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn handle_outcomes_map_to_errors() {
+        let mut root = Context::builder().name("root").build();
+        assert_eq!(root.spawn_handle(async { 21 }).map(|n| n * 2).into_result().await, Ok(42));
+        assert_eq!(root.spawn_handle(async { 1 }).unwrap_completed().await, 1);
+
+        let mut child = root.new_child_context();
+        let stuck = child.spawn_handle(std::future::pending::<()>());
+        let aborted = child.spawn_handle(std::future::pending::<()>());
+        aborted.abort();
+        assert_eq!(aborted.ok().await, None);
+        drop(child);
+        assert_eq!(stuck.into_result().await, Err(Cancelled));
+
+        let mut request = root.new_child_context();
+        let never = request.spawn_handle(std::future::pending::<()>());
+        drop(root);
+        let message = *tokio::spawn(never.unwrap_completed()).await.unwrap_err().into_panic().downcast::<String>().unwrap();
+        assert!(message.contains("src/handle.rs"), "{}", message);
+        assert!(message.contains("cancelled: Parent <- "), "{}", message);
+        assert!(message.ends_with("(root) cancelled: Explicit"), "{}", message);
+    }
+}
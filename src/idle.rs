@@ -0,0 +1,135 @@
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::{broadcast, Notify};
+use tokio::time::Instant;
+
+use crate::{CancellationCause, ContextInner};
+
+/// Cancels a context once it has had no live tasks for `timeout`
+pub(crate) struct IdleTimer {
+    timeout: Duration,
+    pub(crate) include_descendants: bool,
+    state: Mutex<IdleState>,
+    changed: Notify,
+}
+
+struct IdleState {
+    live: usize,
+    /// When `live` last dropped to zero
+    idle_since: Instant,
+}
+
+impl IdleTimer {
+    pub(crate) fn new(timeout: Duration, include_descendants: bool) -> IdleTimer {
+        IdleTimer {
+            timeout,
+            include_descendants,
+            state: Mutex::new(IdleState {
+                live: 0,
+                idle_since: Instant::now(),
+            }),
+            changed: Notify::new(),
+        }
+    }
+
+    /// Count a new task, unless `task_context` was cancelled first.
+    ///
+    /// The check happens under the same lock the timer cancels under, so a spawn either disarms the timer or sees
+    /// the cancellation: when both happen at the same instant, whichever takes the lock first wins.
+    pub(crate) fn task_started(&self, task_context: &ContextInner) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if task_context.is_cancelled() {
+            return false;
+        }
+        state.live += 1;
+        self.changed.notify_one();
+        true
+    }
+
+    pub(crate) fn task_finished(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.live -= 1;
+        if state.live == 0 {
+            state.idle_since = Instant::now();
+        }
+        self.changed.notify_one();
+    }
+}
+
+/// Cancel the context once it has been idle for long enough. Exits when the context is cancelled or gone.
+pub(crate) async fn monitor(inner: Weak<ContextInner>, timer: Arc<IdleTimer>, mut cancel_receiver: broadcast::Receiver<()>) {
+    loop {
+        let changed = timer.changed.notified();
+        let expires = {
+            let state = timer.state.lock().unwrap();
+            match inner.upgrade() {
+                Some(inner) if !inner.is_cancelled() => {}
+                _ => return,
+            }
+            (state.live == 0).then(|| state.idle_since + timer.timeout)
+        };
+        tokio::select! {
+            _ = changed => {},
+            _ = tokio::time::sleep_until(expires.unwrap_or_else(Instant::now)), if expires.is_some() => {
+                let state = timer.state.lock().unwrap();
+                if state.live == 0 && Instant::now() >= state.idle_since + timer.timeout {
+                    if let Some(inner) = inner.upgrade() {
+                        inner.cancel(CancellationCause::Idle);
+                    }
+                    return;
+                }
+            },
+            _ = cancel_receiver.recv() => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CancellationCause, Context, SpawnError};
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_context_cancels_itself() {
+        let mut ctx = Context::builder().idle_timeout(Duration::from_secs(10)).build();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        // a task disarms the timer for as long as it runs
+        ctx.spawn(tokio::time::sleep(Duration::from_secs(20))).await.unwrap();
+        tokio::time::sleep(Duration::from_secs(9)).await;
+        assert!(!ctx.is_cancelled());
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(ctx.cancellation_cause(), Some(CancellationCause::Idle));
+        assert_eq!(ctx.try_spawn(async {}).unwrap_err(), SpawnError::Cancelled);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn spawn_at_expiry_wins_when_it_gets_in_first() {
+        let mut ctx = Context::builder().idle_timeout(Duration::from_secs(10)).build();
+        let mut child = ctx.new_child_context();
+        tokio::time::advance(Duration::from_secs(10)).await;
+        // the timer has expired but the monitor has not run yet
+        let task = ctx.try_spawn(tokio::time::sleep(Duration::from_secs(1))).unwrap();
+        tokio::task::yield_now().await;
+        assert!(!ctx.is_cancelled());
+        // tasks of children only count when asked for
+        child.spawn(std::future::pending::<()>());
+        assert_eq!(task.await.unwrap(), Some(()));
+        tokio::time::sleep(Duration::from_secs(11)).await;
+        assert_eq!(ctx.cancellation_cause(), Some(CancellationCause::Idle));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn descendant_tasks_can_keep_context_alive() {
+        let mut ctx = Context::builder()
+            .idle_timeout(Duration::from_secs(10))
+            .idle_includes_descendants(true)
+            .build();
+        let mut child = ctx.new_child_context();
+        let task = child.spawn(tokio::time::sleep(Duration::from_secs(30)));
+        tokio::time::sleep(Duration::from_secs(25)).await;
+        assert!(!ctx.is_cancelled());
+        assert_eq!(task.await.unwrap(), Some(()));
+        tokio::time::sleep(Duration::from_secs(11)).await;
+        assert_eq!(ctx.cancellation_cause(), Some(CancellationCause::Idle));
+    }
+}
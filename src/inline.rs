@@ -0,0 +1,118 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll, Waker};
+use tokio::task::{JoinError, JoinHandle};
+
+use crate::Context;
+
+/// Handle of a task started with `Context::spawn_or_inline`
+///
+/// Awaiting it gives the same result as awaiting the `JoinHandle` of `Context::spawn`.
+pub struct InlineHandle<T> {
+    state: InlineState<T>,
+}
+
+enum InlineState<T> {
+    Ready(Option<Option<T>>),
+    Spawned(JoinHandle<Option<T>>),
+}
+
+// the output is only ever moved out, never pinned
+impl<T> Unpin for InlineHandle<T> {}
+
+impl<T> InlineHandle<T> {
+    /// Whether the future completed on its first poll and no task was spawned
+    pub fn is_inline(&self) -> bool {
+        matches!(self.state, InlineState::Ready(_))
+    }
+}
+
+impl<T> Future for InlineHandle<T> {
+    type Output = Result<Option<T>, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        match &mut self.state {
+            InlineState::Ready(output) => Poll::Ready(Ok(output.take().expect("polled after completion"))),
+            InlineState::Spawned(handle) => Pin::new(handle).poll(cx),
+        }
+    }
+}
+
+impl Context {
+    /// Poll `future` once right here, and only spawn it like `spawn` if it is not ready yet.
+    ///
+    /// Meant for futures that are often ready right away, such as cache hits: those skip the cancellation
+    /// subscription and the task spawn. A future that is ready on its first poll never becomes a task, so it is not
+    /// counted, listed in `tree()`, checked by the admission hook or reported to telemetry. On a cancelled context
+    /// the future is never polled and the handle resolves to None. A panic during the first poll surfaces from the
+    /// handle and goes through the panic policy, like a panic of a spawned task.
+    ///
+    /// The first poll runs in the tracing span a task would run in, and with the `poll-time` feature counts towards
+    /// the poll time of the context. Stall detection does not watch it, since it runs on the caller's task.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn lookup(key: u64) -> Option<String> { None }
+    /// # async fn example() {
+    /// let mut ctx = Context::new();
+    /// let user = ctx.spawn_or_inline(lookup(42)).await;
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn spawn_or_inline<T>(&mut self, future: T) -> InlineHandle<T::Output>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        if self.inner.is_cancelled() {
+            return InlineHandle { state: InlineState::Ready(Some(None)) };
+        }
+        let mut future = Box::pin(future);
+        let mut cx = TaskContext::from_waker(Waker::noop());
+        let polled = {
+            #[cfg(feature = "tracing")]
+            let _span = self.inner.task_span().entered();
+            #[cfg(feature = "poll-time")]
+            let started = std::time::Instant::now();
+            let polled = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| future.as_mut().poll(&mut cx)));
+            #[cfg(feature = "poll-time")]
+            self.inner.poll_time.record(started.elapsed());
+            polled
+        };
+        let state = match polled {
+            Ok(Poll::Ready(output)) => InlineState::Ready(Some(Some(output))),
+            Ok(Poll::Pending) => InlineState::Spawned(self.spawn(future)),
+            // panic again inside a task, which reports it and resolves the handle like any task that panicked
+            Err(payload) => InlineState::Spawned(self.spawn(async move { std::panic::resume_unwind(payload) })),
+        };
+        InlineHandle { state }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CancellationCause, PanicPolicy};
+
+    #[tokio::test(start_paused = true)]
+    async fn ready_futures_skip_the_task() {
+        let mut ctx = Context::builder().panic_policy(PanicPolicy::CancelContext).build();
+        let hit = ctx.spawn_or_inline(async { 1 });
+        assert!(hit.is_inline());
+        #[cfg(feature = "poll-time")]
+        assert_eq!(ctx.stats().poll.polls, 1);
+        assert_eq!(hit.await.unwrap(), Some(1));
+
+        let miss = ctx.spawn_or_inline(async {
+            tokio::task::yield_now().await;
+            2
+        });
+        assert!(!miss.is_inline());
+        assert_eq!(miss.await.unwrap(), Some(2));
+
+        let broken = ctx.spawn_or_inline(async { panic!("bad cache entry") });
+        assert!(broken.await.unwrap_err().is_panic());
+        assert!(matches!(ctx.cancellation_cause(), Some(CancellationCause::Panic { .. })));
+        assert_eq!(ctx.spawn_or_inline(async { 3 }).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+use std::panic::Location;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::AbortHandle;
+use tokio::time::Instant;
+
+use crate::tree::SpawnLocation;
+use crate::work::WorkGuard;
+use crate::{Context, ContextInner};
+
+/// Returned by `Context::keep_alive` once the context was force cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepAliveRefused;
+
+impl fmt::Display for KeepAliveRefused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "context is being force cancelled")
+    }
+}
+
+impl std::error::Error for KeepAliveRefused {}
+
+/// A released `KeepAlive`, as listed by `DrainTimedOut::keep_alives`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepAliveUse {
+    /// Where `keep_alive` was called
+    pub acquired_at: SpawnLocation,
+    pub held_for: Duration,
+    /// True if the guard was still held when its budget ran out
+    pub expired: bool,
+}
+
+/// Keep-alives of a context, held and released
+#[derive(Default)]
+pub(crate) struct KeepAlives {
+    state: Mutex<KeepAliveState>,
+    released: Notify,
+}
+
+#[derive(Default)]
+struct KeepAliveState {
+    held: usize,
+    uses: Vec<KeepAliveUse>,
+}
+
+impl ContextInner {
+    /// Resolves once no keep-alive of this context is held. Each one is bounded by its budget.
+    pub(crate) async fn keep_alives_released(&self) {
+        loop {
+            let released = self.keep_alives.released.notified();
+            if self.keep_alives.state.lock().unwrap().held == 0 {
+                return;
+            }
+            released.await;
+        }
+    }
+
+    pub(crate) fn keep_alive_uses(&self) -> Vec<KeepAliveUse> {
+        self.keep_alives.state.lock().unwrap().uses.clone()
+    }
+}
+
+/// Delays the end of a cancellation while held, see `Context::keep_alive`. Dropping it releases it.
+pub struct KeepAlive {
+    hold: Arc<Hold>,
+    expiry: AbortHandle,
+}
+
+struct Hold {
+    inner: Arc<ContextInner>,
+    acquired_at: SpawnLocation,
+    since: Instant,
+    work: Mutex<Option<WorkGuard>>,
+}
+
+impl Hold {
+    fn release(&self, expired: bool) {
+        let Some(work) = self.work.lock().unwrap().take() else {
+            return;
+        };
+        let keep_alives = &self.inner.keep_alives;
+        {
+            let mut state = keep_alives.state.lock().unwrap();
+            state.held -= 1;
+            state.uses.push(KeepAliveUse { acquired_at: self.acquired_at, held_for: self.since.elapsed(), expired });
+        }
+        keep_alives.released.notify_waiters();
+        drop(work);
+    }
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        self.expiry.abort();
+        self.hold.release(false);
+    }
+}
+
+impl Context {
+    /// Ask for up to `budget` to finish the current unit of work after this context is cancelled.
+    ///
+    /// While the guard is held, and for at most `budget`, `cancel_and_wait` keeps waiting past its drain timeout and
+    /// tasks spawned with `CancellationGranularity::AtCheckpoints` are not stopped when `but_force_after` expires.
+    /// `force_cancel` is not delayed, and once it was called no new keep-alive is granted.
+    ///
+    /// The report of a `cancel_and_wait` that timed out lists the keep-alives and how long they were held.
+    ///
+    /// Must be called within a tokio runtime.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(ctx: Context) {
+    /// if let Ok(_keep) = ctx.keep_alive(Duration::from_secs(2)) {
+    ///     // flush the batch that was already accepted
+    /// }
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn keep_alive(&self, budget: Duration) -> Result<KeepAlive, KeepAliveRefused> {
+        let location = Location::caller();
+        let inner = &self.inner;
+        if inner.is_forced() {
+            return Err(KeepAliveRefused);
+        }
+        inner.keep_alives.state.lock().unwrap().held += 1;
+        let work = inner.register_work(Arc::from(format!("keep-alive at {}", SpawnLocation::from(location))), location);
+        let hold = Arc::new(Hold {
+            inner: inner.clone(),
+            acquired_at: location.into(),
+            since: Instant::now(),
+            work: Mutex::new(Some(work)),
+        });
+        let expiring = hold.clone();
+        let expiry = inner.spawn(async move {
+            tokio::time::sleep(budget).await;
+            expiring.release(true);
+        });
+        Ok(KeepAlive { hold, expiry: expiry.abort_handle() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CancellationGranularity;
+
+    #[tokio::test(start_paused = true)]
+    async fn keep_alives_extend_the_drain_until_their_budget() {
+        let mut ctx = Context::new();
+        let _forgotten = ctx.register_work("forgotten");
+        let granularity = CancellationGranularity::AtCheckpoints { but_force_after: Some(Duration::from_secs(1)) };
+        let keep = ctx.keep_alive(Duration::from_secs(3)).unwrap();
+        let stuck = ctx.spawn_with_granularity(granularity, std::future::pending::<()>());
+        let start = Instant::now();
+        let report = ctx.cancel_and_wait(Duration::from_secs(2)).await.unwrap_err();
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+        assert!(report.outstanding.contains(&"forgotten".to_string()));
+        assert_eq!(stuck.await.unwrap(), None);
+        assert_eq!(report.keep_alives.len(), 1);
+        assert!(report.keep_alives[0].expired);
+        assert_eq!(report.keep_alives[0].held_for, Duration::from_secs(3));
+        drop(keep);
+
+        let root = Context::new();
+        root.force_cancel();
+        assert_eq!(root.keep_alive(Duration::from_secs(1)).err(), Some(KeepAliveRefused));
+    }
+}
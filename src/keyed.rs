@@ -0,0 +1,206 @@
+use std::any::Any;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+use crate::{Context, ContextBuilder, ContextRef};
+
+/// Locks the children of a key are spread over, so lookups for different keys rarely contend
+const SHARDS: usize = 16;
+
+/// Child contexts created by `Context::keyed_child`, by key
+pub(crate) struct KeyedChildren {
+    hasher: RandomState,
+    shards: Box<[Mutex<Shard>]>,
+}
+
+#[derive(Default)]
+struct Shard {
+    /// Keys with the same hash share a bucket
+    buckets: HashMap<u64, Vec<Entry>>,
+    entries: usize,
+    /// Number of entries after the last sweep, the next one happens once it doubled
+    swept_at: usize,
+}
+
+struct Entry {
+    key: Box<dyn Any + Send + Sync>,
+    child: Context,
+}
+
+impl Entry {
+    fn matches<K: Eq + 'static>(&self, key: &K) -> bool {
+        self.key.downcast_ref::<K>() == Some(key)
+    }
+
+    /// Cancelled and without tasks that outlive the cancellation
+    fn is_closed(&self) -> bool {
+        self.child.inner.is_cancelled() && self.child.inner.active_tasks.load(Ordering::SeqCst) == 0
+    }
+}
+
+impl Default for KeyedChildren {
+    fn default() -> Self {
+        KeyedChildren {
+            hasher: RandomState::new(),
+            shards: (0..SHARDS).map(|_| Mutex::default()).collect(),
+        }
+    }
+}
+
+impl KeyedChildren {
+    fn shard<K: Hash>(&self, key: &K) -> (u64, &Mutex<Shard>) {
+        let hash = self.hasher.hash_one(key);
+        (hash, &self.shards[hash as usize % SHARDS])
+    }
+
+    /// Drop every child, called once the parent is cancelled so the children no longer keep it alive
+    pub(crate) fn clear(&self) {
+        let taken: Vec<_> = self.shards.iter().map(|shard| std::mem::take(&mut *shard.lock().unwrap())).collect();
+        drop(taken);
+    }
+}
+
+impl Shard {
+    fn sweep(&mut self) {
+        self.buckets.retain(|_, bucket| {
+            bucket.retain(|entry| !entry.is_closed());
+            !bucket.is_empty()
+        });
+        self.entries = self.buckets.values().map(Vec::len).sum();
+        self.swept_at = self.entries;
+    }
+}
+
+impl Context {
+    /// The child context for `key`, created on first use and the same for every later call with an equal key.
+    ///
+    /// Useful to keep all work of one user or connection under one scope, which can be cancelled on its own with
+    /// `cancel_key`. The child lives as long as this context, or until it is cancelled and its last task is done,
+    /// after which the next call creates a new one. Lookups of different keys rarely contend.
+    ///
+    /// If a new child would exceed the limit set with `with_max_children`, the returned handle refers to no context
+    /// and only reports cancellation.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(user_id: u64) {
+    /// let router = Context::new();
+    /// let user = router.keyed_child(user_id);
+    /// user.spawn(async move { /* handle a message of the user */ }).unwrap();
+    /// router.cancel_key(&user_id);
+    /// # }
+    /// ```
+    pub fn keyed_child<K>(&self, key: K) -> ContextRef
+    where
+        K: Hash + Eq + Send + Sync + 'static,
+    {
+        self.keyed_child_with(key, Context::builder)
+    }
+
+    /// `keyed_child`, building a missing child with the builder returned by `builder`.
+    ///
+    /// With millions of distinct keys, give the children an idle timeout, so children of keys that are no longer
+    /// used close themselves and do not pile up.
+    pub fn keyed_child_with<K>(&self, key: K, builder: impl FnOnce() -> ContextBuilder) -> ContextRef
+    where
+        K: Hash + Eq + Send + Sync + 'static,
+    {
+        let keyed = self.inner.keyed.get_or_init(Default::default);
+        let (hash, shard) = keyed.shard(&key);
+        let mut shard = shard.lock().unwrap();
+        let bucket = shard.buckets.entry(hash).or_default();
+        if let Some(entry) = bucket.iter().find(|entry| entry.matches(&key) && !entry.child.inner.is_cancelled()) {
+            return entry.child.as_ref();
+        }
+        let before = bucket.len();
+        bucket.retain(|entry| !entry.matches(&key));
+        shard.entries -= before - bucket.len();
+        let Ok(child) = Context::try_create(Some(&self.inner), builder()) else {
+            return ContextRef::gone();
+        };
+        let child_ref = child.as_ref();
+        // a cancelled parent cleared the map already, keeping the child would keep the parent alive
+        if self.inner.is_cancelled() {
+            return child_ref;
+        }
+        shard.buckets.entry(hash).or_default().push(Entry { key: Box::new(key), child });
+        shard.entries += 1;
+        if shard.entries >= (2 * shard.swept_at).max(SHARDS) {
+            shard.sweep();
+        }
+        child_ref
+    }
+
+    /// Cancel the child created by `keyed_child` for `key`. Returns false if there is none.
+    pub fn cancel_key<K>(&self, key: &K) -> bool
+    where
+        K: Hash + Eq + Send + Sync + 'static,
+    {
+        let Some(keyed) = self.inner.keyed.get() else {
+            return false;
+        };
+        let (hash, shard) = keyed.shard(key);
+        let removed = {
+            let mut shard = shard.lock().unwrap();
+            let Some(bucket) = shard.buckets.get_mut(&hash) else {
+                return false;
+            };
+            let Some(position) = bucket.iter().position(|entry| entry.matches(key)) else {
+                return false;
+            };
+            let removed = bucket.swap_remove(position);
+            if bucket.is_empty() {
+                shard.buckets.remove(&hash);
+            }
+            shard.entries -= 1;
+            removed
+        };
+        let cancelled = !removed.child.inner.is_cancelled();
+        drop(removed);
+        cancelled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SpawnError;
+
+    #[tokio::test(start_paused = true)]
+    async fn keys_share_a_child_until_cancelled() {
+        let router = Context::new();
+        let alice = router.keyed_child("alice");
+        let task = alice.spawn(std::future::pending::<()>()).unwrap();
+        assert!(router.keyed_child("alice").same_context(&alice));
+        assert!(!router.keyed_child("bob").same_context(&alice));
+        // keys of different types never match
+        assert!(!router.keyed_child(String::from("alice")).same_context(&alice));
+
+        assert!(router.cancel_key(&"alice"));
+        assert!(!router.cancel_key(&"alice"));
+        assert_eq!(task.await.unwrap(), None);
+        assert!(alice.is_cancelled());
+        assert!(!router.keyed_child("alice").same_context(&alice));
+
+        // closed children are swept as new keys come in
+        for user in 0..1000u32 {
+            router.keyed_child(user).force_cancel();
+        }
+        let entries: usize = router.inner.keyed.get().unwrap().shards.iter().map(|shard| shard.lock().unwrap().entries).sum();
+        assert!(entries < SHARDS * SHARDS + 3);
+    }
+
+    #[tokio::test]
+    async fn keys_beyond_the_child_limit_get_a_cancelled_ref() {
+        let router = Context::new().with_max_children(1);
+        let alice = router.keyed_child("alice");
+        let bob = router.keyed_child("bob");
+        assert!(!alice.is_cancelled());
+        assert!(bob.is_cancelled());
+        assert_eq!(bob.spawn(async {}).unwrap_err(), SpawnError::ScopeClosed);
+        assert!(router.keyed_child("alice").same_context(&alice));
+    }
+}
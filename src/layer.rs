@@ -0,0 +1,252 @@
+//! Tower middleware that runs every request under its own child context.
+//!
+//! `ContextLayer` creates a child of a parent context per request, with an optional per-request timeout. Shutting
+//! down the parent cancels all requests in flight. Inner services find the request context with `Context::current()`.
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use std::time::Duration;
+
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{CancellationCause, Context, ContextInner};
+
+/// Error type of `ContextService`, the same as `tower::BoxError`
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+tokio::task_local! {
+    static CURRENT: Arc<ContextInner>;
+}
+
+/// Why a request was stopped before the inner service answered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequestAborted {
+    /// The request context was cancelled, usually because the parent context was
+    Cancelled(CancellationCause),
+    /// The per-request timeout elapsed
+    Elapsed,
+}
+
+impl fmt::Display for RequestAborted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestAborted::Cancelled(cause) => write!(f, "request cancelled: {:?}", cause),
+            RequestAborted::Elapsed => write!(f, "request timed out"),
+        }
+    }
+}
+
+impl std::error::Error for RequestAborted {}
+
+/// Names the context of a request, see `ContextLayer::name_with`
+pub trait RequestNamer<Req> {
+    fn name(&self, request: &Req) -> Option<String>;
+}
+
+impl<Req> RequestNamer<Req> for () {
+    fn name(&self, _request: &Req) -> Option<String> {
+        None
+    }
+}
+
+impl<Req, F> RequestNamer<Req> for F
+where
+    F: Fn(&Req) -> String,
+{
+    fn name(&self, request: &Req) -> Option<String> {
+        Some(self(request))
+    }
+}
+
+/// Layer producing `ContextService`
+/// ```rust, no_run
+/// use std::time::Duration;
+/// use tokio_tree_context::{Context, ContextLayer};
+///
+/// let root = Context::new();
+/// let layer = ContextLayer::new(&root)
+///     .timeout(Duration::from_secs(30))
+///     .name_with(|request: &String| format!("request {}", request));
+/// ```
+#[derive(Clone)]
+pub struct ContextLayer<N = ()> {
+    parent: Arc<ContextInner>,
+    timeout: Option<Duration>,
+    namer: N,
+}
+
+impl ContextLayer {
+    /// Run requests under children of `parent`. The layer does not keep `parent` from being cancelled.
+    ///
+    /// Once `parent` has as many children as `Context::with_max_children` allows, requests fail with
+    /// `ContextLimitExceeded` without reaching the inner service.
+    pub fn new(parent: &Context) -> ContextLayer {
+        ContextLayer {
+            parent: parent.inner.clone(),
+            timeout: None,
+            namer: (),
+        }
+    }
+}
+
+impl<N> ContextLayer<N> {
+    /// Give every request a deadline of `timeout` after it was called
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Name the context of each request, shown in `tree()` snapshots
+    pub fn name_with<F>(self, namer: F) -> ContextLayer<F> {
+        ContextLayer {
+            parent: self.parent,
+            timeout: self.timeout,
+            namer,
+        }
+    }
+}
+
+impl<S, N: Clone> Layer<S> for ContextLayer<N> {
+    type Service = ContextService<S, N>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ContextService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Service running each request of the inner service under a child context. Created by `ContextLayer`.
+#[derive(Clone)]
+pub struct ContextService<S, N = ()> {
+    inner: S,
+    layer: ContextLayer<N>,
+}
+
+impl<S, N, Req> Service<Req> for ContextService<S, N>
+where
+    S: Service<Req>,
+    S::Error: Into<BoxError>,
+    S::Future: Send + 'static,
+    N: RequestNamer<Req>,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut TaskContext<'_>) -> Poll<Result<(), BoxError>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: Req) -> Self::Future {
+        let mut builder = Context::builder();
+        if let Some(name) = self.layer.namer.name(&request) {
+            builder = builder.name(name);
+        }
+        if let Some(timeout) = self.layer.timeout {
+            builder = builder.deadline(tokio::time::Instant::now() + timeout);
+        }
+        let context = match Context::try_create(Some(&self.layer.parent), builder) {
+            Ok(context) => context,
+            Err(e) => return Box::pin(async move { Err(e.into()) }),
+        };
+        let inner = context.inner.clone();
+        let response = CURRENT.sync_scope(inner.clone(), || self.inner.call(request));
+        // subscribe before checking the flag, so a cancel that happens in between is still received
+        let mut rx = inner.subscribe();
+        Box::pin(CURRENT.scope(inner.clone(), async move {
+            let _context = context;
+            let cancelled = async {
+                if !inner.is_cancelled() {
+                    let _ = rx.recv().await;
+                }
+            };
+            tokio::select! {
+                response = response => response.map_err(Into::into),
+                _ = cancelled => Err(match inner.cause().unwrap_or(CancellationCause::Explicit) {
+                    CancellationCause::Deadline => RequestAborted::Elapsed,
+                    cause => RequestAborted::Cancelled(cause),
+                }
+                .into()),
+            }
+        }))
+    }
+}
+
+impl Context {
+    /// A new child of the context the current request runs under, when called by a service behind `ContextLayer`.
+    ///
+    /// Dropping the returned context only cancels the child, not the request. None outside of a request, or if the
+    /// request context already has as many children as `with_max_children` allows.
+    pub fn current() -> Option<Context> {
+        CURRENT
+            .try_with(|inner| Context::try_create(Some(inner), Context::builder()).ok())
+            .ok()
+            .flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Echoes requests after a delay, reporting the name of the request context
+    struct Slow(Duration);
+
+    impl Service<String> for Slow {
+        type Response = Option<String>;
+        type Error = std::convert::Infallible;
+        type Future = Pin<Box<dyn Future<Output = Result<Option<String>, Self::Error>> + Send>>;
+
+        fn poll_ready(&mut self, _cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _request: String) -> Self::Future {
+            let delay = self.0;
+            Box::pin(async move {
+                tokio::time::sleep(delay).await;
+                let current = Context::current().unwrap();
+                let request = current.inner.parent.as_ref().unwrap();
+                Ok(request.name.as_deref().map(str::to_string))
+            })
+        }
+    }
+
+    fn aborted(error: BoxError) -> RequestAborted {
+        error.downcast_ref::<RequestAborted>().unwrap().clone()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_run_under_child_contexts() {
+        let root = Context::new();
+        let layer = ContextLayer::new(&root).timeout(Duration::from_secs(5)).name_with(|request: &String| request.clone());
+        let mut service = layer.layer(Slow(Duration::from_secs(1)));
+        assert_eq!(service.call("hello".to_string()).await.unwrap(), Some("hello".to_string()));
+        assert!(Context::current().is_none());
+
+        let mut slow = layer.layer(Slow(Duration::from_secs(10)));
+        assert_eq!(aborted(slow.call("late".to_string()).await.unwrap_err()), RequestAborted::Elapsed);
+
+        let in_flight = tokio::spawn(slow.call("in flight".to_string()));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        drop(root);
+        let error = in_flight.await.unwrap().unwrap_err();
+        assert_eq!(aborted(error), RequestAborted::Cancelled(CancellationCause::Parent));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_over_the_child_limit_fail() {
+        let root = Context::new().with_max_children(1);
+        let mut service = ContextLayer::new(&root).layer(Slow(Duration::from_secs(1)));
+        let first = service.call("first".to_string());
+        let error = service.call("second".to_string()).await.unwrap_err();
+        assert_eq!(error.downcast_ref::<crate::ContextLimitExceeded>().map(|e| e.limit), Some(1));
+        assert_eq!(first.await.unwrap(), None);
+    }
+}
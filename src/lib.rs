@@ -1,7 +1,158 @@
+use std::collections::HashMap;
+use std::panic::Location;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
 use std::{future::Future, time::Duration};
-use tokio::sync::broadcast::Sender;
 use tokio::{sync::broadcast, time::Instant};
 
+#[cfg(feature = "cpu-affinity")]
+mod affinity;
+mod admission;
+mod blocking;
+mod budget;
+mod builder;
+mod call;
+mod cancel_scope;
+mod capacity;
+mod cancellation;
+mod channel;
+mod checkpoint;
+mod cleanup;
+mod closed;
+mod collect;
+mod compact;
+mod consume;
+mod context_ref;
+mod deferred;
+mod epoch;
+mod error_channel;
+mod error_handler;
+mod events;
+mod exclusive;
+mod expect;
+mod handle;
+mod idle;
+mod inline;
+mod keep_alive;
+mod keyed;
+#[cfg(feature = "tower")]
+mod layer;
+mod local;
+mod max_children;
+mod max_tasks;
+mod memory;
+mod messages;
+mod name_stats;
+mod named;
+mod naming;
+#[cfg(feature = "net")]
+mod net;
+mod notify;
+mod nursery;
+mod once;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+mod output;
+mod owned;
+mod panic;
+mod parallel;
+#[cfg(feature = "poll-time")]
+mod poll_time;
+mod progress;
+mod race;
+mod region;
+mod result;
+mod resource;
+mod retry;
+mod runtime;
+mod scoped;
+#[cfg(feature = "signal")]
+mod signal;
+#[cfg(feature = "sink")]
+mod sink;
+mod slot;
+mod stall;
+mod stats;
+mod stream;
+mod sync;
+#[cfg(feature = "metrics")]
+mod task_metrics;
+mod telemetry;
+mod trace;
+mod transfer;
+mod tree;
+mod values;
+mod wait;
+mod work;
+
+#[cfg(feature = "cpu-affinity")]
+pub use affinity::{AffinityNotSupported, CpuSet};
+pub use admission::{Admission, AdmissionHook, SpawnError, TaskMeta};
+pub use builder::ContextBuilder;
+pub use call::CallError;
+pub use cancel_scope::CancelScope;
+pub use capacity::CapacityStats;
+pub use cancellation::{CancelSignal, CancellationSignal};
+pub use channel::{
+    Closed, ScopedOneshotReceiver, ScopedOneshotSender, ScopedReceiver, ScopedSender, ScopedWatchReceiver,
+    ScopedWatchSender,
+};
+pub use checkpoint::CancellationGranularity;
+pub use cleanup::{CleanupOutcome, CleanupReport, CleanupRun};
+pub use closed::CloseInfo;
+pub use collect::{CollectingHandle, UnorderedResults};
+pub use compact::ClosedChild;
+pub use consume::{ConsumeSummary, DrainPolicy};
+pub use context_ref::ContextRef;
+pub use epoch::{StatsDelta, StatsSnapshot, TaskCounts};
+pub use error_channel::ERROR_CHANNEL_CAPACITY;
+pub use error_handler::{TaskError, TaskErrorKind};
+pub use events::{ContextEvent, EventStream, EVENT_CAPACITY};
+pub use exclusive::ExclusiveHandle;
+pub use handle::TaskHandle;
+pub use inline::InlineHandle;
+pub use keep_alive::{KeepAlive, KeepAliveRefused, KeepAliveUse};
+#[cfg(feature = "tower")]
+pub use layer::{BoxError, ContextLayer, ContextService, RequestAborted, RequestNamer};
+pub use local::LocalContext;
+pub use max_children::ContextLimitExceeded;
+pub use max_tasks::{MaxTasksContext, SpawnLimitExceeded};
+pub use memory::{MemoryLimitAction, MemoryLimitExceeded, MemoryReservation, MemoryStats};
+pub use messages::{Messages, MESSAGE_CAPACITY};
+pub use name_stats::TaskNameStats;
+pub use named::NamedGroup;
+pub use naming::NamingStrategy;
+#[cfg(feature = "net")]
+pub use net::ConnectError;
+pub use notify::{Cancelled, ScopedNotify};
+pub use nursery::{Nursery, NurseryError};
+#[cfg(feature = "opentelemetry")]
+pub use otel::TraceContext;
+pub use owned::OwnedHandle;
+pub use panic::PanicPolicy;
+#[cfg(feature = "poll-time")]
+pub use poll_time::{PollStats, TaskPollStats};
+pub use progress::{Progress, ProgressSender, ProgressValue, MAX_PROGRESS_STATE_LEN};
+pub use region::SyncRegionGuard;
+pub use resource::{ResourceGuard, ScopedResource, DEFAULT_RESOURCE_GRACE};
+pub use result::TaskResult;
+#[cfg(feature = "sink")]
+pub use sink::{CancellableSink, SinkError, DEFAULT_CLOSE_TIMEOUT};
+pub use slot::{ChildSlot, SlotRef};
+pub use stall::StalledTask;
+pub use stats::ContextStats;
+pub use stream::{TakeUntilCancelled, TakeUntilCancelledExt};
+#[cfg(feature = "metrics")]
+pub use task_metrics::{TASKS_CANCELLED, TASKS_COMPLETED, TASKS_SPAWNED, TASK_DURATION};
+pub use telemetry::TelemetryRecorder;
+pub use trace::TraceId;
+pub use transfer::TransferError;
+pub use tree::{ContextId, ContextNode, ContextStatus, ContextTree, SpawnLocation, TaskNode, TREE_SCHEMA_VERSION};
+pub use values::{ContextKeyErase, ContextLocalKey, KeyId};
+pub use wait::{WaitError, WaitOutcome};
+pub use work::{DrainTimedOut, WorkGuard};
+
 /// A context that can be used to spawn tokio tasks
 /// Cancelling the context (or dropping it) will cancel all async tasks spawn by this context
 /// You can create child context too.
@@ -46,24 +197,720 @@ use tokio::{sync::broadcast, time::Instant};
 ///    }
 /// ```
 pub struct Context {
-    cancel_sender: Sender<()>,
+    inner: Arc<ContextInner>,
+}
+
+/// Why a context was cancelled
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub enum CancellationCause {
+    /// The context was cancelled with `cancel()` or dropped
+    Explicit,
+    /// An ancestor context was cancelled
+    Parent,
+    /// The deadline of the context was reached
+    Deadline,
+    /// The tasks of the context used up its time budget
+    BudgetExhausted,
+    /// The process received a shutdown signal
+    Signal,
+    /// The context had no live tasks for its idle timeout
+    Idle,
+    /// A task panicked under `PanicPolicy::CancelContext`. `context` is the context the task was spawned on, which
+    /// differs from the cancelled context when the panic was escalated.
+    Panic {
+        context: ContextId,
+        task_id: u64,
+        spawned_at: SpawnLocation,
+    },
+    /// A memory reservation took the context past its memory limit, see `MemoryLimitAction::Cancel`
+    MemoryLimit,
+    /// The context was cancelled with `emergency_cancel`
+    Emergency { reason: Arc<str> },
+}
+
+impl CancellationCause {
+    /// Name of the variant, such as `"Panic"`, which is how the cause appears in the serialized tree
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CancellationCause::Explicit => "Explicit",
+            CancellationCause::Parent => "Parent",
+            CancellationCause::Deadline => "Deadline",
+            CancellationCause::BudgetExhausted => "BudgetExhausted",
+            CancellationCause::Signal => "Signal",
+            CancellationCause::Idle => "Idle",
+            CancellationCause::Panic { .. } => "Panic",
+            CancellationCause::MemoryLimit => "MemoryLimit",
+            CancellationCause::Emergency { .. } => "Emergency",
+        }
+    }
+}
+
+/// State of a context that is shared with its parent, its children and its tasks
+struct ContextInner {
+    id: ContextId,
+    name: Option<Arc<str>>,
+    parent: Option<Arc<ContextInner>>,
+    state: sync::Mutex<CancelState>,
+    /// Mirrors `state.cause.is_some()` for lock free checks, only ever set while holding `state`
+    cancelled: sync::AtomicBool,
+    /// Notified when the context is cancelled, for `Context::cancelled_blocking`
+    cancel_condvar: sync::Condvar,
+    active_tasks: AtomicUsize,
+    /// Notified whenever `active_tasks` changes
+    tasks_changed: tokio::sync::Notify,
+    /// Live `WorkGuard`s, which are counted in `active_tasks` but cannot be aborted
+    registered_work: AtomicUsize,
+    /// Live tasks by task id
+    tasks: Mutex<HashMap<u64, tree::TaskInfo>>,
+    messages: messages::MessageChannels,
+    admission_hook: Mutex<Option<Arc<dyn AdmissionHook>>>,
+    deadline: Option<Instant>,
+    /// The deadline was set with a timeout, see `Context::is_timed_out`
+    deadline_is_timeout: bool,
+    /// Set for contexts created with `with_owned_runtime` and their descendants
+    runtime: Option<tokio::runtime::Handle>,
+    /// Set with `Context::with_max_children`, `usize::MAX` if there is no limit
+    max_children: AtomicUsize,
+    /// Set with `ContextBuilder::capacity`
+    capacity: Option<capacity::Capacity>,
+    budget: Option<Arc<budget::TimeBudget>>,
+    memory: memory::MemoryUsage,
+    task_counters: epoch::TaskCounters,
+    name_stats: name_stats::NameStats,
+    idle: Option<Arc<idle::IdleTimer>>,
+    stall: Option<Arc<stall::StallDetector>>,
+    panic_policy: PanicPolicy,
+    inherit_deadline: bool,
+    /// Set with `ContextBuilder::cancel_batch_size`, or inherited from the parent
+    cancel_batch_size: Option<usize>,
+    /// Created on first subscription, receives the events of this context and its descendants
+    events: std::sync::OnceLock<broadcast::Sender<events::ContextEvent>>,
+    trace_id: trace::TraceSlot,
+    inherit_span: trace::InheritSpanSlot,
+    telemetry: telemetry::TelemetrySlot,
+    expected_cancel: expect::ExpectedCancelSlot,
+    #[cfg(feature = "poll-time")]
+    poll_time: poll_time::PollCounters,
+    naming: naming::TaskNaming,
+    completion_cancels: race::CompletionCancels,
+    error_handlers: error_handler::ErrorHandlers,
+    keep_alives: keep_alive::KeepAlives,
+    /// Created on first use by `Context::defer`
+    cleanups: std::sync::OnceLock<Box<cleanup::Cleanups>>,
+    /// Created on first use by `Context::as_ref`, shared with the `ContextRef`s to this context
+    close: std::sync::OnceLock<Arc<closed::CloseState>>,
+    /// Created once the first child is gone, counters and summaries of the children that are gone
+    closed: std::sync::OnceLock<Box<compact::ClosedChildren>>,
+    /// Created on first use by `Context::keyed_child`
+    keyed: std::sync::OnceLock<keyed::KeyedChildren>,
+    /// Created on first use by `Context::register_resource`
+    resources: std::sync::OnceLock<Arc<resource::Resources>>,
+    once_tasks: once::OnceTasks,
+    exclusive_tasks: exclusive::ExclusiveTasks,
+    values: values::Values,
+}
+
+/// The waiters and children of a context that was just marked cancelled, see `ContextInner::mark_cancelled`
+type Marked = (Option<broadcast::Sender<()>>, Vec<Weak<ContextInner>>);
+
+/// Everything involved in delivering a cancellation, kept under one lock so that registering a child or a
+/// subscriber either happens before the cancellation (and is notified by it) or sees it.
+#[derive(Default)]
+struct CancelState {
+    cause: Option<CancellationCause>,
+    children: Vec<Weak<ContextInner>>,
+    /// Created on first subscription, so contexts that never spawn do not allocate a channel
+    sender: Option<broadcast::Sender<()>>,
+    /// Set by `force_cancel`, which sends a second message on `sender`
+    forced: bool,
+    /// Threads in `Context::cancelled_blocking`, so the condvar is only notified when somebody waits on it
+    blocking_waiters: usize,
+}
+
+impl ContextInner {
+    /// Child contexts that are still alive
+    fn live_children(&self) -> Vec<Arc<ContextInner>> {
+        self.state.lock().unwrap().children.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(sync::Ordering::SeqCst)
+    }
+
+    fn cause(&self) -> Option<CancellationCause> {
+        self.state.lock().unwrap().cause.clone()
+    }
+
+    /// Receiver that is woken once the context is cancelled.
+    ///
+    /// Check `is_cancelled` after subscribing: the context may have been cancelled before the subscription.
+    fn subscribe(&self) -> broadcast::Receiver<()> {
+        let mut state = self.state.lock().unwrap();
+        state.sender.get_or_insert_with(|| broadcast::channel(1).0).subscribe()
+    }
+
+    /// The earliest deadline of this context and its ancestors
+    fn effective_deadline(&self) -> Option<Instant> {
+        let mut deadline = self.deadline;
+        let mut ancestor = self.parent.as_ref();
+        while let Some(inner) = ancestor {
+            deadline = match (deadline, inner.deadline) {
+                (Some(own), Some(other)) => Some(own.min(other)),
+                (own, other) => own.or(other),
+            };
+            ancestor = inner.parent.as_ref();
+        }
+        deadline
+    }
+
+    /// Resolves once the context is cancelled
+    fn cancelled(&self) -> impl Future<Output = ()> + Send + 'static {
+        // subscribe before checking the flag, so a cancel that happens in between is still received
+        let mut rx = self.subscribe();
+        let cancelled = self.is_cancelled();
+        async move {
+            if !cancelled {
+                let _ = rx.recv().await;
+            }
+        }
+    }
+
+    /// Resolves once the context has no live tasks
+    async fn tasks_done(self: Arc<Self>) {
+        loop {
+            let done = self.tasks_changed.notified();
+            if self.active_tasks.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            done.await;
+        }
+    }
+
+    /// Cancel the context and all its descendants. Only the first cancellation has an effect.
+    ///
+    /// The whole subtree is marked cancelled before any waiter is woken, and the waiters are then woken level by
+    /// level, so in a wide tree the last child does not learn about the cancellation long after the first one.
+    fn cancel(&self, cause: CancellationCause) {
+        let Some((sender, children)) = self.mark_cancelled(cause) else {
+            return;
+        };
+        let mut descendants = Vec::new();
+        let mut level = children;
+        while !level.is_empty() {
+            let mut next = Vec::new();
+            for child in level.iter().filter_map(Weak::upgrade) {
+                if let Some((sender, children)) = child.mark_cancelled(CancellationCause::Parent) {
+                    next.extend(children);
+                    descendants.push((child, sender));
+                }
+            }
+            level = next;
+        }
+        let senders = std::iter::once(&sender).chain(descendants.iter().map(|(_, sender)| sender));
+        let senders: Vec<_> = senders.flatten().cloned().collect();
+        self.wake_cancelled(senders);
+        let contexts = std::iter::once(self).chain(descendants.iter().map(|(child, _)| &**child));
+        for inner in contexts {
+            inner.emit_cancelled();
+            if let Some(keyed) = inner.keyed.get() {
+                keyed.clear();
+            }
+            inner.check_closed();
+        }
+    }
+
+    /// Wake the waiters of a cancelled subtree, in batches of `cancel_batch_size` with a yield in between if it is set
+    fn wake_cancelled(&self, senders: Vec<broadcast::Sender<()>>) {
+        let batch = self.cancel_batch_size.filter(|batch| senders.len() > *batch);
+        let runtime = batch.and_then(|_| tokio::runtime::Handle::try_current().ok().or_else(|| self.runtime.clone()));
+        let (Some(batch), Some(runtime)) = (batch, runtime) else {
+            senders.iter().for_each(|sender| drop(sender.send(())));
+            return;
+        };
+        senders[..batch].iter().for_each(|sender| drop(sender.send(())));
+        runtime.spawn(async move {
+            for chunk in senders[batch..].chunks(batch) {
+                tokio::task::yield_now().await;
+                chunk.iter().for_each(|sender| drop(sender.send(())));
+            }
+        });
+    }
+
+    /// Record the cancellation of this context alone, returning what is needed to notify its waiters and reach its
+    /// children, or None if it was already cancelled
+    fn mark_cancelled(&self, cause: CancellationCause) -> Option<Marked> {
+        let mut state = self.state.lock().unwrap();
+        if state.cause.is_some() {
+            return None;
+        }
+        state.cause = Some(cause);
+        self.cancelled.store(true, sync::Ordering::SeqCst);
+        if state.blocking_waiters > 0 {
+            self.cancel_condvar.notify_all();
+        }
+        // kept after cancellation, so `force_cancel` can reach the descendants
+        Some((state.sender.clone(), state.children.clone()))
+    }
+
+    fn is_forced(&self) -> bool {
+        self.state.lock().unwrap().forced
+    }
+
+    /// Resolves once `force_cancel` was called on the context or an ancestor
+    fn forced(self: &Arc<Self>) -> impl Future<Output = ()> + Send + 'static {
+        // subscribe before checking the flag, so a force that happens in between is still received
+        let mut rx = self.subscribe();
+        let inner = self.clone();
+        async move {
+            while !inner.is_forced() {
+                let _ = rx.recv().await;
+            }
+        }
+    }
+
+    /// Cancel the context if it is not cancelled yet, then stop all tasks of it and its descendants right away,
+    /// including tasks that outlive the cancellation through their `CancelScope`.
+    fn force_cancel(&self) {
+        self.cancel(CancellationCause::Explicit);
+        let (sender, children) = {
+            let mut state = self.state.lock().unwrap();
+            if state.forced {
+                return;
+            }
+            state.forced = true;
+            (state.sender.clone(), state.children.clone())
+        };
+        if let Some(sender) = sender {
+            let _ = sender.send(());
+        }
+        for child in children.iter().filter_map(Weak::upgrade) {
+            child.force_cancel();
+        }
+    }
+
+    /// Register a new child, pruning children that are gone once in a while. A child added to a cancelled context
+    /// is cancelled right away.
+    fn add_child(&self, child: &Arc<ContextInner>) -> Result<(), ContextLimitExceeded> {
+        self.add_children(std::slice::from_ref(child))
+    }
+
+    /// `add_child` for many children under a single lock acquisition. Fails without adding any of them if that would
+    /// exceed the limit set with `Context::with_max_children`.
+    fn add_children(&self, new: &[Arc<ContextInner>]) -> Result<(), ContextLimitExceeded> {
+        let mut state = self.state.lock().unwrap();
+        if state.cause.is_some() {
+            drop(state);
+            new.iter().for_each(|child| child.cancel(CancellationCause::Parent));
+            return Ok(());
+        }
+        let limit = self.max_children.load(Ordering::SeqCst);
+        let children = &mut state.children;
+        if children.len() + new.len() > children.capacity().min(limit) {
+            children.retain(|child| child.strong_count() > 0);
+        }
+        if children.len() + new.len() > limit {
+            return Err(ContextLimitExceeded { children: children.len(), limit });
+        }
+        children.extend(new.iter().map(Arc::downgrade));
+        Ok(())
+    }
+
+    /// Wrap `future` so it stops when the context is cancelled or the timeout is reached, after consulting the
+    /// admission hook. Every spawn variant ends up here.
+    #[track_caller]
+    fn task_future<T>(self: &Arc<Self>, name: Option<String>, future: T, timeout: Option<Duration>) -> Result<impl Future<Output = Option<T::Output>>, SpawnError>
+    where
+        T: Future,
+    {
+        self.task_future_at(name, future, Location::caller(), TaskOptions { timeout, ..Default::default() })
+    }
+
+    /// `task_future` for callers that captured the spawn location themselves, such as async spawn methods. A task
+    /// that is refused is counted in `TaskCounts::rejected` and published as `ContextEvent::TaskRejected`.
+    fn task_future_at<T>(
+        self: &Arc<Self>,
+        name: Option<String>,
+        future: T,
+        location: &'static Location<'static>,
+        options: TaskOptions,
+    ) -> Result<impl Future<Output = Option<T::Output>>, SpawnError>
+    where
+        T: Future,
+    {
+        let task = self.build_task(name, future, location, options);
+        if let Err(error) = &task {
+            self.task_counters.task_rejected();
+            self.emit_task_rejected(error);
+        }
+        task
+    }
+
+    /// Spawn `task`, or in place of a task that was refused, one that resolves to None
+    fn spawn_or_none<T, F>(&self, task: Result<F, SpawnError>) -> tokio::task::JoinHandle<Option<T>>
+    where
+        F: Future<Output = Option<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        match task {
+            Ok(task) => self.spawn(task),
+            Err(_) => self.spawn(async { None }),
+        }
+    }
+
+    fn build_task<T>(
+        self: &Arc<Self>,
+        name: Option<String>,
+        future: T,
+        location: &'static Location<'static>,
+        options: TaskOptions,
+    ) -> Result<impl Future<Output = Option<T::Output>>, SpawnError>
+    where
+        T: Future,
+    {
+        let TaskOptions {
+            timeout,
+            progress,
+            scope,
+            granularity,
+            cost,
+            error,
+            cancel,
+            transfer,
+            priority,
+            #[cfg(feature = "metrics")]
+            metrics_label,
+        } = options;
+        let capacity = match &self.capacity {
+            Some(capacity) => {
+                let cost = cost.unwrap_or(1);
+                capacity.check(cost)?;
+                Some(capacity.acquire(cost))
+            }
+            None => None,
+        };
+        // with inherit_deadline, tasks are bounded by the remaining time of the context
+        let deadline = self.inherit_deadline.then(|| self.effective_deadline()).flatten();
+        if deadline.is_some_and(|deadline| deadline <= Instant::now()) {
+            return Err(SpawnError::DeadlineExceeded);
+        }
+        // a transferable task follows the context it currently belongs to instead
+        let listener = match &transfer {
+            Some(_) if self.is_cancelled() => return Err(SpawnError::Cancelled),
+            Some(_) => None,
+            None => Some(self.cancel_listener(scope)?),
+        };
+        let follow = transfer.clone().map(transfer::TransferSlot::cancelled);
+        let cancelled = granularity.deliver(async move {
+            match (listener, follow) {
+                (Some(listener), _) => listener.await,
+                (None, Some(follow)) => follow.await,
+                (None, None) => std::future::pending().await,
+            }
+        }, self.clone());
+        let name = name.or_else(|| self.naming.name(location));
+        let delay = match self.admit(name.clone(), priority) {
+            Admission::Admit => None,
+            Admission::Delay(delay) => Some(delay),
+            Admission::Reject(reason) => return Err(SpawnError::Rejected(reason)),
+        };
+        let name: Option<Arc<str>> = name.map(Arc::from);
+        let guard = TaskGuard::new(self.clone(), name.clone(), location, progress, scope)?;
+        self.task_counters.track(&guard.outcome);
+        if let Some(name) = &name {
+            self.name_stats.task_spawned(name);
+        }
+        let guard = match transfer {
+            Some(slot) => HeldGuard::Transferable(slot.hold(guard)),
+            None => HeldGuard::Fixed(guard),
+        };
+        let mut telemetry = telemetry::TaskTelemetry::spawned(self, name.as_deref(), priority);
+        #[cfg(feature = "metrics")]
+        let mut metrics = task_metrics::TaskMetrics::spawned(self, metrics_label);
+        #[cfg(feature = "tracing")]
+        let span = self.task_span();
+        Ok(async move {
+            let mut cancelled = std::pin::pin!(cancelled);
+            if let Some(delay) = delay {
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {},
+                    _ = &mut cancelled => return None,
+                }
+            }
+            let _units = match capacity {
+                // cancellation first, so units freed by the cancellation are not picked up by cancelled tasks
+                Some(capacity) => tokio::select! {
+                    biased;
+                    _ = &mut cancelled => return None,
+                    units = capacity => Some(units),
+                },
+                None => None,
+            };
+            guard.with(TaskGuard::start_running);
+            let timeout = async move {
+                let until = match (timeout.map(|duration| Instant::now() + duration), deadline) {
+                    (Some(timeout), Some(deadline)) => Some(timeout.min(deadline)),
+                    (timeout, deadline) => timeout.or(deadline),
+                };
+                match until {
+                    Some(until) => tokio::time::sleep_until(until).await,
+                    None => std::future::pending().await,
+                }
+            };
+            #[cfg(feature = "tracing")]
+            let future = tracing::Instrument::instrument(future, span);
+            #[cfg(feature = "poll-time")]
+            let future = poll_time::measure(future, |busy| {
+                guard.with(|guard| {
+                    guard.poll_time.record(busy);
+                    guard.inner.poll_time.record(busy);
+                })
+            });
+            let mut future = std::pin::pin!(future);
+            let future = std::future::poll_fn(|cx| {
+                guard.with(|guard| {
+                    if let (Some(stall), Some(last_poll)) = (&guard.inner.stall, &guard.last_poll) {
+                        stall.stamp(last_poll);
+                    }
+                });
+                match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+                    Ok(poll) => poll,
+                    Err(payload) => {
+                        let (inner, id) = guard.with(|guard| (guard.inner.clone(), guard.id));
+                        inner.handle_panic(id, location, &*payload);
+                        let message = result::panic_message(&*payload);
+                        inner.report_error(id, name.as_ref(), TaskErrorKind::Panic, message);
+                        guard.with(|guard| guard.outcome.set(epoch::OutcomeSlot::PANICKED));
+                        std::panic::resume_unwind(payload)
+                    }
+                }
+            });
+            let cancel = async move {
+                match cancel {
+                    Some(cancel) => cancel.await,
+                    None => std::future::pending().await,
+                }
+            };
+            let output = tokio::select! {
+                biased;
+                res = future => Some(res),
+                // checked before cancellation, so a task bounded by the deadline times out rather than being cancelled
+                _ = timeout => {
+                    guard.with(|guard| guard.outcome.set(epoch::OutcomeSlot::TIMED_OUT));
+                    None
+                }
+                _ = cancelled => None,
+                _ = cancel => None,
+            };
+            if let (Some(telemetry), Some(_)) = (&mut telemetry, &output) {
+                telemetry.completed();
+            }
+            #[cfg(feature = "metrics")]
+            if output.is_some() {
+                metrics.completed();
+            }
+            let (inner, id) = guard.with(|guard| {
+                if output.is_some() {
+                    guard.outcome.set(epoch::OutcomeSlot::COMPLETED);
+                }
+                (guard.inner.clone(), guard.id)
+            });
+            if output.is_some() {
+                inner.completion_cancels.completed();
+            }
+            if let Some(message) = error.and_then(|slot| slot.get().cloned()) {
+                inner.report_error(id, name.as_ref(), TaskErrorKind::Error, message);
+            }
+            output
+        })
+    }
+}
+
+/// How a task is run, besides its name and spawn location
+#[derive(Default)]
+struct TaskOptions {
+    timeout: Option<Duration>,
+    progress: Option<progress::ProgressReceiver>,
+    scope: CancelScope,
+    granularity: CancellationGranularity,
+    /// Units of the context's capacity the task holds while it runs, one if not set
+    cost: Option<u32>,
+    /// Set for tasks spawned with `Context::spawn_fallible`
+    error: Option<error_handler::ErrorSlot>,
+    /// Set for tasks spawned with `Context::spawn_with_cancellation_future`
+    cancel: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    /// Set for tasks spawned with `Context::spawn_transferable`, which hold their guard in it
+    transfer: Option<Arc<transfer::TransferSlot>>,
+    /// Shown to the admission hook, set with `Context::try_spawn_with_priority`
+    priority: Option<i32>,
+    /// Tags the metrics of the task instead of the context name
+    #[cfg(feature = "metrics")]
+    metrics_label: Option<Arc<str>>,
+}
+
+/// The guard a task holds while it runs
+enum HeldGuard {
+    Fixed(TaskGuard),
+    /// Swapped for a guard of another context by `Context::transfer_task`
+    Transferable(Arc<transfer::TransferSlot>),
+}
+
+impl HeldGuard {
+    fn with<R>(&self, f: impl FnOnce(&TaskGuard) -> R) -> R {
+        match self {
+            HeldGuard::Fixed(guard) => f(guard),
+            HeldGuard::Transferable(slot) => slot.with(f),
+        }
+    }
+}
+
+/// Keeps the live task count and task registry of a context up to date for as long as the task exists
+struct TaskGuard {
+    inner: Arc<ContextInner>,
+    id: u64,
+    /// Idle timers of the context and of ancestors that count this task
+    idle_timers: Vec<Arc<idle::IdleTimer>>,
+    /// Shared with the task registry when the context detects stalls
+    last_poll: Option<Arc<AtomicU64>>,
+    /// Shared with the task registry
+    #[cfg(feature = "poll-time")]
+    poll_time: Arc<poll_time::PollCounters>,
+    /// Counted in the `TaskCounts` of the context when the guard is dropped
+    outcome: epoch::OutcomeSlot,
+    spawned_at: Instant,
+    /// Set once the task holds its capacity units and uses up the time budget of the context
+    running: AtomicBool,
+}
+
+impl TaskGuard {
+    /// Fails if an idle timer cancelled the context before the task could disarm it
+    fn new(
+        inner: Arc<ContextInner>,
+        name: Option<Arc<str>>,
+        location: &'static Location<'static>,
+        progress: Option<progress::ProgressReceiver>,
+        cancel_scope: CancelScope,
+    ) -> Result<TaskGuard, SpawnError> {
+        let mut idle_timers = Vec::new();
+        let mut context = Some(&inner);
+        while let Some(current) = context {
+            if let Some(timer) = current.idle.as_ref().filter(|timer| Arc::ptr_eq(current, &inner) || timer.include_descendants) {
+                if !timer.task_started(&inner) {
+                    idle_timers.into_iter().for_each(|timer: Arc<idle::IdleTimer>| timer.task_finished());
+                    return Err(SpawnError::Cancelled);
+                }
+                idle_timers.push(timer.clone());
+            }
+            context = current.parent.as_ref();
+        }
+        static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(1);
+        let id = NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed);
+        let last_poll = inner.stall.as_ref().map(|stall| {
+            let last_poll = Arc::new(AtomicU64::new(0));
+            stall.stamp(&last_poll);
+            last_poll
+        });
+        #[cfg(feature = "poll-time")]
+        let poll_time = Arc::new(poll_time::PollCounters::default());
+        inner.emit_task_spawned(id, &name, location);
+        inner.tasks.lock().unwrap().insert(
+            id,
+            tree::TaskInfo {
+                name,
+                location,
+                last_poll: last_poll.clone(),
+                progress,
+                cancel_scope,
+                transfer: None,
+                #[cfg(feature = "poll-time")]
+                poll_time: poll_time.clone(),
+            },
+        );
+        let live = inner.active_tasks.fetch_add(1, Ordering::SeqCst) + 1;
+        inner.task_counters.task_started(live);
+        inner.tasks_changed.notify_waiters();
+        Ok(TaskGuard {
+            inner,
+            id,
+            idle_timers,
+            last_poll,
+            #[cfg(feature = "poll-time")]
+            poll_time,
+            outcome: epoch::OutcomeSlot::untracked(),
+            spawned_at: Instant::now(),
+            running: AtomicBool::new(false),
+        })
+    }
+
+    /// Start counting the task against the time budget of the context, once it is past admission and capacity
+    fn start_running(&self) {
+        if !self.running.swap(true, Ordering::SeqCst) {
+            if let Some(budget) = &self.inner.budget {
+                budget.task_started();
+            }
+        }
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        if let (Some(budget), true) = (&self.inner.budget, *self.running.get_mut()) {
+            budget.task_finished();
+        }
+        self.inner.task_counters.task_ended(&self.outcome);
+        // unregistered first, so a drain woken below does not report the task as outstanding
+        let info = self.inner.tasks.lock().unwrap().remove(&self.id);
+        self.inner.active_tasks.fetch_sub(1, Ordering::SeqCst);
+        self.inner.tasks_changed.notify_waiters();
+        if let Some(name) = info.and_then(|info| info.name) {
+            self.inner.name_stats.task_ended(&name, &self.outcome, self.spawned_at.elapsed());
+        }
+        for timer in &self.idle_timers {
+            timer.task_finished();
+        }
+        self.inner.check_closed();
+    }
 }
 
 impl Context {
     /// Cancel all tasks under this context
-    pub fn cancel(self) {}
+    pub fn cancel(self) {
+        self.disarm_expectation();
+    }
+
+    /// Escalate a cancellation: cancel this context if it is not cancelled yet, then abort all tasks of it and its
+    /// descendants right away, including those that keep running after cancellation through their `CancelScope`.
+    ///
+    /// A `cancel_and_wait` of the context stops waiting and reports the work that was still outstanding. Work
+    /// registered with `register_work` cannot be aborted.
+    pub fn force_cancel(&self) {
+        self.inner.force_cancel();
+    }
+
+    /// Cancel this context with `CancellationCause::Emergency` from a thread that does not own it, usually through
+    /// `ContextRef::emergency_cancel`.
+    ///
+    /// Synchronous and callable from any thread, with or without a runtime: the cancelled flag is set and all waiting
+    /// tasks are woken before it returns. Not async-signal-safe, since it takes locks; from a raw signal handler, set
+    /// a flag and cancel from a thread that watches it.
+    pub fn emergency_cancel(&self, reason: &str) {
+        self.inner.cancel(CancellationCause::Emergency { reason: Arc::from(reason) });
+    }
 
     /// Create a new context
     pub fn new() -> Context {
-        let (tx, _) = broadcast::channel(1);
-        Context {
-            cancel_sender: tx,
-        }
+        Context::builder().build()
+    }
+
+    /// Create a builder to configure a new context
+    pub fn builder() -> ContextBuilder {
+        ContextBuilder::default()
     }
 
     /// Create a new Context from a parent. Same as `parent.new_child_context()`
     pub fn with_parent(parent: &mut Context) -> Context {
-        return parent.new_child_context();
+        parent.new_child_context()
     }
 
     /// Create a new child context, where cancelling the parent context, will also cancel the child context.
@@ -71,18 +918,277 @@ impl Context {
     /// 
     /// The new context has a logical relationship with the parent. Cancelling parent will cancel child too.
     pub fn new_child_context(&mut self) -> Context {
-        let (new_tx, _) = broadcast::channel(1);
-        let new_tx_clone = new_tx.clone();
-        let wsender= new_tx_clone.downgrade();
-        drop(new_tx_clone);
-        let mut rx = self.cancel_sender.subscribe();
-        tokio::spawn(async move {
-            let _ = rx.recv().await;
-            wsender.upgrade().map(|x| x.send(()))
-        });
-        Context {
-            cancel_sender: new_tx
+        self.try_new_child_context().unwrap()
+    }
+
+    /// Create `n` child contexts at once. The same as calling `new_child_context` `n` times, except that the children
+    /// are registered with this context under a single lock: if that would exceed the limit set with
+    /// `with_max_children`, the call panics without adding any of them. Each child is still allocated on its own, so
+    /// this is not meaningfully faster than a loop.
+    pub fn new_child_contexts(&mut self, n: usize) -> Vec<Context> {
+        self.new_children((0..n).map(|_| Context::builder()))
+    }
+
+    /// `new_child_contexts` where child `i` is named `name(i)`, as shown in its `tree()` snapshot
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut root = Context::new();
+    /// let shards = root.new_named_child_contexts(512, |shard| format!("shard-{shard}"));
+    /// ```
+    pub fn new_named_child_contexts(&mut self, n: usize, name: impl Fn(usize) -> String) -> Vec<Context> {
+        self.new_children((0..n).map(|i| Context::builder().name(name(i))))
+    }
+
+    fn new_children(&mut self, builders: impl Iterator<Item = ContextBuilder>) -> Vec<Context> {
+        let children: Vec<_> = builders.map(|builder| Context::new_inner(Some(&self.inner), builder)).collect();
+        self.inner.add_children(&children).unwrap();
+        children.into_iter().map(Context::start).collect()
+    }
+
+    /// Create a child context that is cancelled with `CancellationCause::Deadline` at `deadline`.
+    /// Must be called inside a tokio runtime.
+    pub fn with_deadline(&mut self, deadline: Instant) -> Context {
+        Context::builder().deadline(deadline).build_child(self)
+    }
+
+    /// Create a child context whose deadline is `duration` from now. Same as
+    /// `with_deadline(Instant::now() + duration)`.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut ctx = Context::new();
+    /// let mut request = ctx.with_deadline_from_duration(Duration::from_secs(30));
+    /// request.spawn(async move {
+    ///     // handle the request
+    /// });
+    /// ```
+    pub fn with_deadline_from_duration(&mut self, duration: Duration) -> Context {
+        self.with_deadline(Instant::now() + duration)
+    }
+
+    /// Create a child context that times out after `timeout`, like Go's `context.WithTimeout`. The same as
+    /// `with_deadline_from_duration`, except that the expiry is reported by `is_timed_out()`.
+    pub fn with_timeout(&mut self, timeout: Duration) -> Context {
+        Context::builder().timeout(timeout).build_child(self)
+    }
+
+    fn create(parent: Option<&Arc<ContextInner>>, builder: ContextBuilder) -> Context {
+        Context::try_create(parent, builder).unwrap()
+    }
+
+    fn try_create(parent: Option<&Arc<ContextInner>>, builder: ContextBuilder) -> Result<Context, ContextLimitExceeded> {
+        let inner = Context::new_inner(parent, builder);
+        if let Some(parent) = parent {
+            parent.add_child(&inner)?;
         }
+        Ok(Context::start(inner))
+    }
+
+    /// A context that is not registered with its parent yet
+    fn new_inner(parent: Option<&Arc<ContextInner>>, builder: ContextBuilder) -> Arc<ContextInner> {
+        Arc::new(ContextInner {
+            id: ContextId::next(),
+            name: builder.name,
+            parent: parent.cloned(),
+            state: Default::default(),
+            cancelled: sync::AtomicBool::new(false),
+            cancel_condvar: sync::Condvar::new(),
+            active_tasks: AtomicUsize::new(0),
+            tasks_changed: tokio::sync::Notify::new(),
+            registered_work: AtomicUsize::new(0),
+            tasks: Default::default(),
+            messages: Default::default(),
+            admission_hook: Default::default(),
+            deadline: builder.deadline,
+            deadline_is_timeout: builder.deadline_is_timeout,
+            runtime: builder.runtime.or_else(|| parent.and_then(|parent| parent.runtime.clone())),
+            capacity: builder.capacity.map(capacity::Capacity::new),
+            max_children: AtomicUsize::new(usize::MAX),
+            budget: builder.time_budget.map(|limit| Arc::new(budget::TimeBudget::new(limit))),
+            memory: memory::MemoryUsage::new(builder.memory_limit),
+            task_counters: Default::default(),
+            name_stats: Default::default(),
+            idle: builder.idle_timeout.map(|timeout| Arc::new(idle::IdleTimer::new(timeout, builder.idle_includes_descendants))),
+            panic_policy: builder.panic_policy,
+            inherit_deadline: builder.inherit_deadline,
+            cancel_batch_size: builder.cancel_batch_size.or_else(|| parent.and_then(|parent| parent.cancel_batch_size)),
+            events: Default::default(),
+            trace_id: Default::default(),
+            inherit_span: Default::default(),
+            telemetry: Default::default(),
+            expected_cancel: Default::default(),
+            #[cfg(feature = "poll-time")]
+            poll_time: Default::default(),
+            naming: Default::default(),
+            completion_cancels: Default::default(),
+            error_handlers: Default::default(),
+            keep_alives: Default::default(),
+            stall: builder.stall_threshold.map(|threshold| Arc::new(stall::StallDetector::new(threshold, builder.on_stall))),
+            cleanups: Default::default(),
+            close: Default::default(),
+            closed: Default::default(),
+            keyed: Default::default(),
+            resources: Default::default(),
+            once_tasks: Default::default(),
+            exclusive_tasks: Default::default(),
+            values: Default::default(),
+        })
+    }
+
+    /// Start the monitors of a context registered with its parent
+    fn start(inner: Arc<ContextInner>) -> Context {
+        if let Some(deadline) = inner.deadline {
+            let winner = Arc::downgrade(&inner);
+            let mut rx = inner.subscribe();
+            if !inner.is_cancelled() {
+                inner.spawn(async move {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(deadline) => {
+                            if let Some(inner) = winner.upgrade() {
+                                inner.cancel(CancellationCause::Deadline);
+                            }
+                        },
+                        _ = rx.recv() => {},
+                    }
+                });
+            }
+        }
+        if let Some(idle) = &inner.idle {
+            inner.spawn(idle::monitor(Arc::downgrade(&inner), idle.clone(), inner.subscribe()));
+        }
+        if let Some(stall) = &inner.stall {
+            inner.spawn(stall::monitor(Arc::downgrade(&inner), stall.clone(), inner.subscribe()));
+        }
+        if let Some(budget) = &inner.budget {
+            inner.spawn(budget::monitor(Arc::downgrade(&inner), budget.clone(), inner.subscribe()));
+        }
+        Context { inner }
+    }
+
+    /// Whether this context, or any of its ancestors, has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.is_cancelled()
+    }
+
+    /// Fails with `Cancelled` if this context is cancelled. `ctx.require_active()?` works in any function whose error
+    /// type implements `From<Cancelled>`.
+    /// ```rust, no_run
+    /// use tokio_tree_context::{Cancelled, Context};
+    ///
+    /// enum JobError {
+    ///     Cancelled,
+    ///     Failed(String),
+    /// }
+    ///
+    /// impl From<Cancelled> for JobError {
+    ///     fn from(_: Cancelled) -> Self {
+    ///         JobError::Cancelled
+    ///     }
+    /// }
+    ///
+    /// fn step(ctx: &Context) -> Result<(), JobError> {
+    ///     ctx.require_active()?;
+    ///     // do the step
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn require_active(&self) -> Result<(), Cancelled> {
+        if self.is_cancelled() {
+            return Err(Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Whether this context was cancelled because the timeout of `with_timeout` or `ContextBuilder::timeout` fired.
+    /// A context cancelled because an ancestor timed out reports false.
+    pub fn is_timed_out(&self) -> bool {
+        self.inner.deadline_is_timeout && self.inner.cause() == Some(CancellationCause::Deadline)
+    }
+
+    /// Whether this context was cancelled because the deadline of `with_deadline` or `ContextBuilder::deadline` was
+    /// reached. A context cancelled because an ancestor reached its deadline reports false.
+    pub fn is_deadline_exceeded(&self) -> bool {
+        !self.inner.deadline_is_timeout && self.inner.cause() == Some(CancellationCause::Deadline)
+    }
+
+    /// The earliest deadline of this context and its ancestors, if any of them has one
+    pub fn deadline(&self) -> Option<Instant> {
+        self.inner.effective_deadline()
+    }
+
+    /// Why this context was cancelled, or None if it is not cancelled
+    pub fn cancellation_cause(&self) -> Option<CancellationCause> {
+        self.inner.cause()
+    }
+
+    /// Resolves once this context has no live tasks, without cancelling it.
+    ///
+    /// Only tasks spawned directly on this context count. If tasks keep being spawned the future may never resolve,
+    /// so race it against a timeout where that matters.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(ctx: Context) {
+    /// tokio::select! {
+    ///     _ = ctx.when_all_tasks_done() => println!("all work finished"),
+    ///     _ = tokio::time::sleep(Duration::from_secs(10)) => println!("still busy"),
+    /// }
+    /// # }
+    /// ```
+    pub fn when_all_tasks_done(&self) -> impl Future<Output = ()> + Send + Unpin + 'static {
+        Box::pin(self.inner.clone().tasks_done())
+    }
+
+    /// Resolves to true once at least `n` tasks of this context are live at the same time, or to false if the context
+    /// is cancelled first. Handy in tests, to wait until all workers have started.
+    pub fn await_n_tasks_active(&self, n: usize) -> impl Future<Output = bool> + Send + Unpin + 'static {
+        let inner = self.inner.clone();
+        // subscribe before checking the flag, so a cancel that happens in between is still received
+        let mut rx = inner.subscribe();
+        Box::pin(async move {
+            loop {
+                let changed = inner.tasks_changed.notified();
+                if inner.active_tasks.load(Ordering::SeqCst) >= n {
+                    return true;
+                }
+                if inner.is_cancelled() {
+                    return false;
+                }
+                tokio::select! {
+                    _ = changed => {},
+                    _ = rx.recv() => return false,
+                }
+            }
+        })
+    }
+
+    /// Resolves once at most `n` tasks of this context are still live, immediately if that is already the case. Use it
+    /// to top up a worker pool when it drains below a threshold instead of polling `active_task_count`.
+    ///
+    /// ```no_run
+    /// # use tokio_tree_context::Context;
+    /// # async fn example(jobs: Vec<u64>) {
+    /// let mut ctx = Context::new();
+    /// for job in jobs {
+    ///     ctx.when_n_tasks_remain(7).await;
+    ///     ctx.spawn(async move { job * 2 });
+    /// }
+    /// # }
+    /// ```
+    pub fn when_n_tasks_remain(&self, n: usize) -> impl Future<Output = ()> + Send + Unpin + 'static {
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            loop {
+                let changed = inner.tasks_changed.notified();
+                if inner.active_tasks.load(Ordering::SeqCst) <= n {
+                    return;
+                }
+                changed.await;
+            }
+        })
     }
 
     /// Run a task with at timeout. If timeout is None, then no timeout is used
@@ -105,32 +1211,41 @@ impl Context {
     /// // wait sometime
     /// ctx.cancel();
     /// ```
+    #[track_caller]
     pub fn spawn_with_timeout<T>(&mut self, future: T, timeout: Option<Duration>) -> tokio::task::JoinHandle<Option<T::Output>>
     where
         T: Future + Send + 'static,
         T::Output: Send + 'static,
     {
-        let mut rx = self.cancel_sender.subscribe();
-        if let Some(duration) = timeout {
-            tokio::task::spawn(async move {
-                tokio::select! {
-                    res = future => Some(res),
-                    _ = rx.recv() => None,
-                    _ = tokio::time::sleep_until(Instant::now() + duration) => None,
-                }
-            })
-        } else {
-            tokio::task::spawn(async move {
-                tokio::select! {
-                    res = future => Some(res),
-                    _ = rx.recv() => None,
-                }
-            })
-        }
+        self.inner.spawn_or_none(self.inner.task_future(None, future, timeout))
+    }
+
+    /// Spawn a task after consulting the admission hook
+    #[track_caller]
+    fn spawn_task<T>(&mut self, name: Option<String>, future: T, timeout: Option<Duration>) -> Result<tokio::task::JoinHandle<Option<T::Output>>, SpawnError>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        let task = self.task_future(name, future, timeout)?;
+        Ok(self.inner.spawn(task))
+    }
+
+    #[track_caller]
+    fn task_future<T>(&mut self, name: Option<String>, future: T, timeout: Option<Duration>) -> Result<impl Future<Output = Option<T::Output>>, SpawnError>
+    where
+        T: Future,
+    {
+        self.inner.task_future(name, future, timeout)
     }
 
     /// Spawn task without tiemout
     /// Task is cancelled when you call this context's cancel or drop the context
+    ///
+    /// If the task is refused, because the context is cancelled, the admission hook rejects it, its capacity cannot
+    /// fit it or its deadline passed, the handle resolves to None. The refusal is counted in `TaskCounts::rejected`
+    /// and published as `ContextEvent::TaskRejected`; `try_spawn` returns the reason instead. The other spawn
+    /// variants that return a plain handle do the same.
     /// 
     /// For example
     /// ```rust, no_run
@@ -144,6 +1259,7 @@ impl Context {
     /// // wait sometime
     /// ctx.cancel();
     /// ```
+    #[track_caller]
     pub fn spawn<T>(&mut self, future: T) -> tokio::task::JoinHandle<Option<T::Output>>
     where
         T: Future + Send + 'static,
@@ -151,12 +1267,622 @@ impl Context {
     {
         self.spawn_with_timeout(future, None)
     }
+
+    /// Spawn a task whose timeout is computed by `timeout_fn` right before spawning.
+    ///
+    /// `timeout_fn` is called synchronously, exactly once. If it returns None, no per-task timeout is applied, but the
+    /// task is still cancelled with the context.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut ctx = Context::new();
+    /// let busy = true;
+    /// ctx.spawn_timeout_fn(async move {
+    ///     // do your work here
+    /// }, || if busy { Some(Duration::from_secs(1)) } else { None });
+    /// ```
+    #[track_caller]
+    pub fn spawn_timeout_fn<T, F>(&mut self, future: T, timeout_fn: F) -> tokio::task::JoinHandle<Option<T::Output>>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+        F: FnOnce() -> Option<Duration>,
+    {
+        let timeout = timeout_fn();
+        self.spawn_with_timeout(future, timeout)
+    }
+
+    /// Spawn a task with the time left until the deadline of this context as its timeout, or without a timeout if
+    /// neither the context nor an ancestor has a deadline, like `spawn`.
+    ///
+    /// This is the spawn for request-scoped work: with 300ms left, the task gets a 300ms timeout. The task would be
+    /// cancelled with the context at the deadline anyway, but this way it ends as timed out in the stats.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(mut server: Context) {
+    /// let mut request = server.with_timeout(Duration::from_millis(300));
+    /// let lookup = request.spawn_with_deadline_propagation(async move {
+    ///     // query the backend
+    /// });
+    /// if lookup.await.unwrap().is_none() {
+    ///     eprintln!("the lookup ran out of time");
+    /// }
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_deadline_propagation<T>(&mut self, future: T) -> tokio::task::JoinHandle<Option<T::Output>>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        let timeout = self.deadline().map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        self.spawn_with_timeout(future, timeout)
+    }
+
+    /// Spawn a task that also stops as soon as `cancel_future` resolves, as if its own context was cancelled.
+    ///
+    /// `cancel_future` only concerns this task: the context and its other tasks keep running.
+    /// ```rust, no_run
+    /// use tokio::sync::oneshot;
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut ctx = Context::new();
+    /// let (abandon, abandoned) = oneshot::channel::<()>();
+    /// ctx.spawn_with_cancellation_future(async move { let _ = abandoned.await; }, async move {
+    ///     // upload the file, unless the user abandons it
+    /// });
+    /// # drop(abandon);
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_cancellation_future<CF, T>(&mut self, cancel_future: CF, future: T) -> tokio::task::JoinHandle<Option<T::Output>>
+    where
+        CF: Future<Output = ()> + Send + 'static,
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        let options = TaskOptions { cancel: Some(Box::pin(cancel_future)), ..Default::default() };
+        self.inner.spawn_or_none(self.inner.task_future_at(None, future, Location::caller(), options))
+    }
+
+    /// Spawn a task that reports its own liveness.
+    ///
+    /// `heartbeat_fn` is called every `interval` for as long as the task is running. The heartbeat stops as soon as
+    /// the task completes or is cancelled, so an external system (for example a lock TTL refresher) can notice that
+    /// the task is gone.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut ctx = Context::new();
+    /// ctx.spawn_with_heartbeat(async move {
+    ///     // do your work here
+    /// }, Duration::from_secs(5), || println!("still alive"));
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_heartbeat<T, F>(&mut self, future: T, interval: Duration, heartbeat_fn: F) -> tokio::task::JoinHandle<Option<T::Output>>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+        F: Fn() + Send + 'static,
+    {
+        self.spawn(async move {
+            let heartbeat = async move {
+                let mut ticker = tokio::time::interval_at(Instant::now() + interval, interval);
+                loop {
+                    ticker.tick().await;
+                    heartbeat_fn();
+                }
+            };
+            tokio::select! {
+                res = future => res,
+                _ = heartbeat => unreachable!(),
+            }
+        })
+    }
+
+    /// Spawn a task and also return an `AbortHandle` for it, to cancel this one task without cancelling the context.
+    ///
+    /// An aborted task resolves its `JoinHandle` to a cancelled `JoinError`.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut ctx = Context::new();
+    /// let (handle, abort) = ctx.spawn_with_abort_handle(async move {
+    ///     // do your work here
+    /// });
+    /// abort.abort();
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_abort_handle<T>(&mut self, future: T) -> (tokio::task::JoinHandle<Option<T::Output>>, tokio::task::AbortHandle)
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        let handle = self.spawn(future);
+        let abort = handle.abort_handle();
+        (handle, abort)
+    }
+
+    /// Spawn a task that holds `mutex` for as long as it runs.
+    ///
+    /// The lock is acquired before `future` starts and released when it completes or is cancelled. If the context is
+    /// cancelled while waiting for the lock, the task resolves to None without ever acquiring it.
+    /// ```rust, no_run
+    /// use std::sync::Arc;
+    /// use tokio::sync::Mutex;
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut ctx = Context::new();
+    /// let migration_lock = Arc::new(Mutex::new(()));
+    /// ctx.spawn_with_mutex_guard(migration_lock.clone(), async move {
+    ///     // only one migration runs at a time
+    /// });
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_mutex_guard<M, T>(&mut self, mutex: Arc<tokio::sync::Mutex<M>>, future: T) -> tokio::task::JoinHandle<Option<T::Output>>
+    where
+        M: Send + 'static,
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        self.spawn(async move {
+            let _guard = mutex.lock_owned().await;
+            future.await
+        })
+    }
+
+    /// Spawn a task that owns `guard` and drops it right before the task resolves, whether `future` completed or was
+    /// cancelled.
+    ///
+    /// Useful when cleanup already lives in a `Drop` impl, such as a transaction that rolls back or a temporary file
+    /// that removes itself. If the task cannot be spawned, the guard is dropped right away.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// struct TempDir(std::path::PathBuf);
+    /// impl Drop for TempDir {
+    ///     fn drop(&mut self) {
+    ///         let _ = std::fs::remove_dir_all(&self.0);
+    ///     }
+    /// }
+    ///
+    /// let mut ctx = Context::new();
+    /// let scratch = TempDir("/tmp/job-42".into());
+    /// ctx.spawn_with_cancel_guard(async move { /* work in /tmp/job-42 */ }, scratch);
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_cancel_guard<T, G>(&mut self, future: T, guard: G) -> tokio::task::JoinHandle<Option<T::Output>>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+        G: Send + 'static,
+    {
+        self.spawn(async move {
+            let _guard = guard;
+            future.await
+        })
+    }
+
+    /// Spawn a task that keeps `guard_tx` alive until it completes or is cancelled.
+    ///
+    /// Give every such task a clone of the same sender and drop the original: `recv()` on the receiver returns None
+    /// once all of them are done, even when they run under different contexts.
+    /// ```rust, no_run
+    /// use tokio::sync::mpsc;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(mut http: Context, mut jobs: Context) {
+    /// let (done_tx, mut done_rx) = mpsc::channel::<()>(1);
+    /// http.spawn_with_shutdown_guard(async move { /* serve */ }, done_tx.clone());
+    /// jobs.spawn_with_shutdown_guard(async move { /* run jobs */ }, done_tx);
+    /// assert!(done_rx.recv().await.is_none());
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_shutdown_guard<T>(&mut self, future: T, guard_tx: tokio::sync::mpsc::Sender<()>) -> tokio::task::JoinHandle<Option<T::Output>>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        self.spawn_with_cancel_guard(future, guard_tx)
+    }
+
+    /// Wait at most `acquire_timeout` for a permit of `semaphore`, then spawn a task that holds the permit until it
+    /// completes or is cancelled.
+    ///
+    /// Fails with `SpawnError::AcquireTimeout` if no permit became available in time, and with `SpawnError::Cancelled`
+    /// if the context was cancelled while waiting. The task is not spawned in either case.
+    /// ```rust, no_run
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    /// use tokio::sync::Semaphore;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example() {
+    /// let mut ctx = Context::new();
+    /// let workers = Arc::new(Semaphore::new(8));
+    /// match ctx.spawn_with_semaphore_timeout(workers, Duration::from_secs(1), async move { /* work */ }).await {
+    ///     Ok(_handle) => {}
+    ///     Err(e) => println!("overloaded: {}", e),
+    /// }
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_semaphore_timeout<T>(
+        &mut self,
+        semaphore: Arc<tokio::sync::Semaphore>,
+        acquire_timeout: Duration,
+        future: T,
+    ) -> impl Future<Output = Result<tokio::task::JoinHandle<Option<T::Output>>, SpawnError>> + Send + 'static
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        let inner = self.inner.clone();
+        let location = Location::caller();
+        let cancelled = inner.cancelled();
+        async move {
+            let permit = tokio::select! {
+                biased;
+                _ = cancelled => return Err(SpawnError::Cancelled),
+                permit = tokio::time::timeout(acquire_timeout, semaphore.acquire_owned()) => match permit {
+                    Ok(Ok(permit)) => permit,
+                    Ok(Err(_)) => return Err(SpawnError::Rejected("semaphore closed".to_string())),
+                    Err(_) => return Err(SpawnError::AcquireTimeout),
+                },
+            };
+            let future = async move {
+                let _permit = permit;
+                future.await
+            };
+            inner.task_future_at(None, future, location, TaskOptions::default()).map(|task| inner.spawn(task))
+        }
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        let unexpected = self.inner.unexpected_drop();
+        self.inner.cancel(CancellationCause::Explicit);
+        if let Some(unexpected) = unexpected {
+            unexpected.report();
+        }
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[tokio::test(start_paused = true)]
+    async fn heartbeat_stops_with_task() {
+        let mut ctx = Context::new();
+        let beats = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = beats.clone();
+        let handle = ctx.spawn_with_heartbeat(async move {
+            tokio::time::sleep(Duration::from_millis(350)).await;
+        }, Duration::from_millis(100), move || {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        });
+        assert_eq!(handle.await.unwrap(), Some(()));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(beats.load(std::sync::atomic::Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout_fn_is_applied() {
+        let mut ctx = Context::new();
+        let timed_out = ctx.spawn_timeout_fn(std::future::pending::<()>(), || Some(Duration::from_secs(1)));
+        assert_eq!(timed_out.await.unwrap(), None);
+        let completed = ctx.spawn_timeout_fn(async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            1
+        }, || None);
+        assert_eq!(completed.await.unwrap(), Some(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn abort_handle_cancels_only_its_task() {
+        let mut ctx = Context::new();
+        let (aborted, abort) = ctx.spawn_with_abort_handle(std::future::pending::<()>());
+        let other = ctx.spawn(tokio::time::sleep(Duration::from_secs(1)));
+        abort.abort();
+        assert!(aborted.await.unwrap_err().is_cancelled());
+        assert!(!ctx.is_cancelled());
+        assert_eq!(other.await.unwrap(), Some(()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deadline_from_duration_is_relative_to_now() {
+        let mut ctx = Context::new();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        let child = ctx.with_deadline_from_duration(Duration::from_secs(2));
+        assert_eq!(child.deadline(), Some(Instant::now() + Duration::from_secs(2)));
+        child.cancellation_signal().await;
+        assert_eq!(child.cancellation_cause(), Some(CancellationCause::Deadline));
+        assert!(!ctx.is_cancelled());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn when_all_tasks_done_does_not_cancel() {
+        let mut ctx = Context::new();
+        ctx.when_all_tasks_done().await;
+        ctx.spawn(tokio::time::sleep(Duration::from_secs(1)));
+        ctx.spawn(tokio::time::sleep(Duration::from_secs(3)));
+        let start = Instant::now();
+        ctx.when_all_tasks_done().await;
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+        assert!(!ctx.is_cancelled());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn await_n_tasks_active_waits_for_ramp_up() {
+        let mut ctx = Context::new();
+        let ramped_up = ctx.await_n_tasks_active(2);
+        ctx.spawn(std::future::pending::<()>());
+        let spawner = ctx.spawn(async {});
+        spawner.await.unwrap();
+        ctx.spawn(std::future::pending::<()>());
+        assert!(ramped_up.await);
+
+        let not_reached = ctx.await_n_tasks_active(3);
+        ctx.cancel();
+        assert!(!not_reached.await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn when_n_tasks_remain_waits_for_drain() {
+        let mut ctx = Context::new();
+        ctx.when_n_tasks_remain(0).await;
+        for secs in 1..=3 {
+            ctx.spawn(tokio::time::sleep(Duration::from_secs(secs)));
+        }
+        let start = Instant::now();
+        ctx.when_n_tasks_remain(3).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        ctx.when_n_tasks_remain(1).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+        ctx.when_n_tasks_remain(0).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancellation_future_stops_only_its_task() {
+        let mut ctx = Context::new();
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let stopped = ctx.spawn_with_cancellation_future(async move { let _ = rx.await; }, std::future::pending::<()>());
+        let other = ctx.spawn(tokio::time::sleep(Duration::from_secs(1)));
+        tx.send(()).unwrap();
+        assert_eq!(stopped.await.unwrap(), None);
+        assert!(!ctx.is_cancelled());
+        assert_eq!(other.await.unwrap(), Some(()));
+        let finished = ctx.spawn_with_cancellation_future(std::future::pending(), async { 7 });
+        assert_eq!(finished.await.unwrap(), Some(7));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deadline_propagation_times_out_at_the_deadline() {
+        let mut root = Context::new();
+        let start = Instant::now();
+        let mut request = root.with_timeout(Duration::from_secs(1));
+        let fast = request.spawn_with_deadline_propagation(tokio::time::sleep(Duration::from_millis(10)));
+        let slow = request.spawn_with_deadline_propagation(tokio::time::sleep(Duration::from_secs(5)));
+        assert_eq!(fast.await.unwrap(), Some(()));
+        assert_eq!(slow.await.unwrap(), None);
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+
+        let unbounded = root.spawn_with_deadline_propagation(tokio::time::sleep(Duration::from_secs(60)));
+        assert_eq!(unbounded.await.unwrap(), Some(()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn mutex_guard_is_held_for_the_task() {
+        let mut ctx = Context::new();
+        let mutex = Arc::new(tokio::sync::Mutex::new(0));
+        let first = ctx.spawn_with_mutex_guard(mutex.clone(), tokio::time::sleep(Duration::from_secs(1)));
+        tokio::task::yield_now().await;
+        assert!(mutex.try_lock().is_err());
+        assert_eq!(first.await.unwrap(), Some(()));
+        assert!(mutex.try_lock().is_ok());
+
+        let held = mutex.clone().lock_owned().await;
+        let mut child = ctx.new_child_context();
+        let waiting = child.spawn_with_mutex_guard(mutex.clone(), async { panic!("must not run") });
+        tokio::task::yield_now().await;
+        drop(child);
+        assert_eq!(waiting.await.unwrap(), None);
+        drop(held);
+        assert!(mutex.try_lock().is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancel_guard_is_dropped_on_completion_and_cancellation() {
+        struct Flag(Arc<AtomicUsize>);
+        impl Drop for Flag {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        let mut ctx = Context::new();
+        let dropped = Arc::new(AtomicUsize::new(0));
+        assert_eq!(ctx.spawn_with_cancel_guard(async { 1 }, Flag(dropped.clone())).await.unwrap(), Some(1));
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+
+        let mut child = ctx.new_child_context();
+        let task = child.spawn_with_cancel_guard(std::future::pending::<()>(), Flag(dropped.clone()));
+        tokio::task::yield_now().await;
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+        drop(child);
+        assert_eq!(task.await.unwrap(), None);
+        assert_eq!(dropped.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_guards_close_the_channel_once_all_tasks_are_done() {
+        let (mut first, mut second) = (Context::new(), Context::new());
+        let (done_tx, mut done_rx) = tokio::sync::mpsc::channel::<()>(1);
+        first.spawn_with_shutdown_guard(tokio::time::sleep(Duration::from_secs(1)), done_tx.clone());
+        second.spawn_with_shutdown_guard(std::future::pending::<()>(), done_tx);
+        let start = Instant::now();
+        let cancel = async {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            second.cancel();
+        };
+        let (closed, _) = tokio::join!(done_rx.recv(), cancel);
+        assert!(closed.is_none());
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn semaphore_acquire_is_bounded() {
+        let mut ctx = Context::new();
+        let semaphore = Arc::new(tokio::sync::Semaphore::new(1));
+        let first = ctx.spawn_with_semaphore_timeout(semaphore.clone(), Duration::from_secs(1), tokio::time::sleep(Duration::from_secs(5)));
+        let first = first.await.unwrap();
+        assert_eq!(semaphore.available_permits(), 0);
+        let second = ctx.spawn_with_semaphore_timeout(semaphore.clone(), Duration::from_secs(1), async {});
+        assert_eq!(second.await.unwrap_err(), SpawnError::AcquireTimeout);
+
+        let mut child = ctx.new_child_context();
+        let waiting = child.spawn_with_semaphore_timeout(semaphore.clone(), Duration::from_secs(10), async {});
+        drop(child);
+        assert_eq!(waiting.await.unwrap_err(), SpawnError::Cancelled);
+        assert_eq!(first.await.unwrap(), Some(()));
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+
+    #[test]
+    fn require_active_converts_into_the_caller_error() {
+        #[derive(Debug, PartialEq)]
+        enum JobError {
+            Cancelled,
+        }
+        impl From<Cancelled> for JobError {
+            fn from(_: Cancelled) -> Self {
+                JobError::Cancelled
+            }
+        }
+        fn step(ctx: &Context) -> Result<u32, JobError> {
+            ctx.require_active()?;
+            Ok(1)
+        }
+        let mut root = Context::new();
+        let ctx = root.new_child_context();
+        assert_eq!(step(&ctx), Ok(1));
+        drop(root);
+        assert_eq!(step(&ctx), Err(JobError::Cancelled));
+        assert_eq!(ctx.require_active(), Err(Cancelled));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout_and_deadline_expiry_are_told_apart() {
+        let mut root = Context::new();
+        let timed = root.with_timeout(Duration::from_secs(1));
+        let dated = root.with_deadline(Instant::now() + Duration::from_secs(1));
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert!(timed.is_cancelled() && timed.is_timed_out() && !timed.is_deadline_exceeded());
+        assert!(dated.is_cancelled() && dated.is_deadline_exceeded() && !dated.is_timed_out());
+        let explicit = root.new_child_context();
+        drop(root);
+        assert!(explicit.is_cancelled() && !explicit.is_timed_out() && !explicit.is_deadline_exceeded());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn force_cancel_aborts_draining_tasks() {
+        let mut root = Context::new();
+        let mut ctx = root.new_child_context();
+        let scope = ctx.as_ref();
+        let pending = std::future::pending::<()>;
+        let janitor = ctx.spawn_with_cancel_scope(CancelScope::Ancestor(root.id()), pending(), None).unwrap();
+        let mut grandchild = ctx.new_child_context();
+        let nested = grandchild.spawn_with_cancel_scope(CancelScope::OwnContextOnly, pending(), None).unwrap();
+        let work = ctx.register_work("ffi");
+        let drain = tokio::spawn(ctx.cancel_and_wait(Duration::from_secs(60)));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(scope.is_cancelled() && !janitor.is_finished() && !nested.is_finished());
+
+        let forced_at = Instant::now();
+        scope.force_cancel();
+        assert_eq!(janitor.await.unwrap(), None);
+        assert_eq!(nested.await.unwrap(), None);
+        let report = drain.await.unwrap().unwrap_err();
+        assert!(report.forced);
+        assert_eq!(report.outstanding, vec!["ffi".to_string()]);
+        assert_eq!(forced_at.elapsed(), Duration::ZERO);
+        drop((work, grandchild));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn inherited_deadline_bounds_task_timeouts() {
+        let mut root = Context::builder().deadline(Instant::now() + Duration::from_secs(10)).build();
+        let mut ctx = Context::builder().inherit_deadline(true).build_child(&mut root);
+        let start = Instant::now();
+        let clamped = ctx.spawn_with_timeout(std::future::pending::<()>(), Some(Duration::from_secs(60)));
+        let shorter = ctx.spawn_with_timeout(std::future::pending::<()>(), Some(Duration::from_secs(2)));
+        assert_eq!(shorter.await.unwrap(), None);
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+        assert_eq!(clamped.await.unwrap(), None);
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+        // the deadline cancels the context at the same instant, the clamped task still counts as timed out
+        assert_eq!(ctx.stats_epoch().counts.timed_out, 2);
+        assert_eq!(ctx.try_spawn(async {}).unwrap_err(), SpawnError::DeadlineExceeded);
+    }
+
+    #[tokio::test]
+    async fn cancellation_wakes_in_batches() {
+        let mut root = Context::builder().cancel_batch_size(2).build();
+        let mut children = root.new_child_contexts(7);
+        let tasks: Vec<_> = children.iter_mut().map(|child| child.spawn(std::future::pending::<()>())).collect();
+        tokio::task::yield_now().await;
+        let woken: Vec<_> = children.iter().map(|child| child.as_ref().cancelled()).collect();
+        root.inner.cancel(CancellationCause::Explicit);
+        assert!(children.iter().all(|child| child.is_cancelled()));
+        // the first batch is woken in place, the rest after the yields
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        let ready = woken.into_iter().filter_map(|mut cancelled| Pin::new(&mut cancelled).poll(&mut cx).is_ready().then_some(()));
+        assert_eq!(ready.count(), 2);
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn unused_children_are_cheap_and_pruned() {
+        // no runtime is needed to create children
+        let mut ctx = Context::new();
+        let child = ctx.new_child_context();
+        for _ in 0..10_000 {
+            drop(ctx.new_child_context());
+        }
+        assert!(ctx.inner.state.lock().unwrap().children.capacity() <= 8);
+        assert!(ctx.inner.state.lock().unwrap().sender.is_none());
+        drop(ctx);
+        assert_eq!(child.cancellation_cause(), Some(CancellationCause::Parent));
+    }
+
+    #[test]
+    fn children_are_created_in_bulk() {
+        let mut ctx = Context::new();
+        let single = ctx.new_child_context();
+        let shards = ctx.new_named_child_contexts(3, |shard| format!("shard-{shard}"));
+        let children: Vec<_> = ctx.tree().root.children.into_iter().map(|child| child.name).collect();
+        assert_eq!(children, vec![None, Some("shard-0".into()), Some("shard-1".into()), Some("shard-2".into())]);
+        drop(ctx);
+        assert!(single.is_cancelled() && shards.iter().all(|shard| shard.cancellation_cause() == Some(CancellationCause::Parent)));
+        let mut cancelled = Context::new();
+        cancelled.inner.cancel(CancellationCause::Explicit);
+        assert!(cancelled.new_child_contexts(2).iter().all(Context::is_cancelled));
+    }
+
     #[tokio::test]
     async fn it_works() {
         let mut ctx = Context::new();
@@ -195,3 +1921,52 @@ mod tests {
         }
     }
 }
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+
+    #[test]
+    fn loom_cancel_during_child_creation() {
+        loom::model(|| {
+            let mut root = Context::new();
+            let mut parent = root.new_child_context();
+            let canceller = loom::thread::spawn(move || drop(root));
+            let child = parent.new_child_context();
+            canceller.join().unwrap();
+            assert!(child.is_cancelled());
+            assert_eq!(child.cancellation_cause(), Some(CancellationCause::Parent));
+        });
+    }
+
+    #[test]
+    fn loom_cancel_during_spawn() {
+        loom::model(|| {
+            let mut root = Context::new();
+            let ctx = root.new_child_context();
+            let canceller = loom::thread::spawn(move || drop(root));
+            // this is what spawning does before launching the task
+            let mut rx = ctx.inner.subscribe();
+            let seen_cancelled = ctx.is_cancelled();
+            canceller.join().unwrap();
+            assert!(seen_cancelled || rx.try_recv().is_ok());
+        });
+    }
+
+    #[test]
+    fn loom_concurrent_cancel() {
+        loom::model(|| {
+            let mut root = Context::new();
+            let mut parent = root.new_child_context();
+            let child = parent.new_child_context();
+            let mut rx = child.inner.subscribe();
+            let first = loom::thread::spawn(move || drop(root));
+            let second = loom::thread::spawn(move || drop(parent));
+            first.join().unwrap();
+            second.join().unwrap();
+            assert_eq!(child.cancellation_cause(), Some(CancellationCause::Parent));
+            assert!(rx.try_recv().is_ok());
+            assert!(rx.try_recv().is_err());
+        });
+    }
+}
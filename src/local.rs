@@ -0,0 +1,140 @@
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+use tokio::task::{JoinHandle, LocalSet};
+
+use crate::Context;
+
+/// A child context paired with the `LocalSet` its `!Send` tasks run on, created with `Context::spawn_with_local_set`.
+///
+/// Dereferences to the context, so everything else works as on any `Context`.
+pub struct LocalContext {
+    context: Context,
+    local_set: Rc<LocalSet>,
+}
+
+impl LocalContext {
+    /// Spawn a `!Send` task on the paired `LocalSet`, cancelled like `spawn`. Works from anywhere on the thread, the
+    /// task runs once the set is run.
+    #[track_caller]
+    pub fn spawn_local<T>(&mut self, future: T) -> JoinHandle<Option<T::Output>>
+    where
+        T: Future + 'static,
+        T::Output: 'static,
+    {
+        match self.context.task_future(None, future, None) {
+            Ok(task) => self.local_set.spawn_local(task),
+            Err(_) => self.local_set.spawn_local(async { None }),
+        }
+    }
+
+    /// The paired `LocalSet`
+    pub fn local_set(&self) -> &Rc<LocalSet> {
+        &self.local_set
+    }
+
+    /// The context without the pairing
+    pub fn into_context(self) -> Context {
+        self.context
+    }
+}
+
+impl Deref for LocalContext {
+    type Target = Context;
+
+    fn deref(&self) -> &Context {
+        &self.context
+    }
+}
+
+impl DerefMut for LocalContext {
+    fn deref_mut(&mut self) -> &mut Context {
+        &mut self.context
+    }
+}
+
+impl Context {
+    /// Create a child context together with a fresh `LocalSet` to run its `!Send` tasks on.
+    ///
+    /// The caller runs the `LocalSet`, typically with `LocalSet::run_until`. `LocalContext::spawn_local` always
+    /// spawns onto that set, even when called while another set is running. Cancelling the context cancels the tasks
+    /// spawned that way.
+    /// ```rust, no_run
+    /// use std::rc::Rc;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn run() {
+    /// let mut ctx = Context::new();
+    /// let (mut local_ctx, local_set) = ctx.spawn_with_local_set();
+    /// local_set.run_until(async move {
+    ///     let handle = local_ctx.spawn_local(async move {
+    ///         let not_send = Rc::new(1);
+    ///         *not_send + 1
+    ///     });
+    ///     handle.await.unwrap();
+    /// }).await;
+    /// # }
+    /// ```
+    pub fn spawn_with_local_set(&mut self) -> (LocalContext, Rc<LocalSet>) {
+        let local_set = Rc::new(LocalSet::new());
+        (LocalContext { context: self.new_child_context(), local_set: local_set.clone() }, local_set)
+    }
+
+    /// Spawn a `!Send` task on the `LocalSet` that is currently running, cancelled like `spawn`.
+    ///
+    /// Panics when not called from inside a `LocalSet`, the same as `tokio::task::spawn_local`.
+    #[track_caller]
+    pub fn spawn_local<T>(&mut self, future: T) -> JoinHandle<Option<T::Output>>
+    where
+        T: Future + 'static,
+        T::Output: 'static,
+    {
+        match self.task_future(None, future, None) {
+            Ok(task) => tokio::task::spawn_local(task),
+            Err(_) => tokio::task::spawn_local(async { None }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[tokio::test]
+    async fn local_tasks_are_cancelled_with_the_context() {
+        let mut ctx = Context::new();
+        let (mut local_ctx, local_set) = ctx.spawn_with_local_set();
+        local_set.run_until(async move {
+            let not_send = Rc::new(41);
+            let done = local_ctx.spawn_local(async move { *not_send + 1 });
+            assert_eq!(done.await.unwrap(), Some(42));
+
+            let pending = local_ctx.spawn_local(std::future::pending::<()>());
+            drop(ctx);
+            assert_eq!(pending.await.unwrap(), None);
+        }).await;
+    }
+
+    #[tokio::test]
+    async fn local_tasks_land_on_the_paired_set() {
+        let mut ctx = Context::new();
+        let (mut local_ctx, local_set) = ctx.spawn_with_local_set();
+        let ran = Rc::new(std::cell::Cell::new(false));
+        let other = LocalSet::new();
+        let mut handle = None;
+        other.run_until(async {
+            let ran = ran.clone();
+            handle = Some(local_ctx.spawn_local(async move {
+                ran.set(true);
+                1
+            }));
+            for _ in 0..10 {
+                tokio::task::yield_now().await;
+            }
+        }).await;
+        assert!(!ran.get());
+        assert_eq!(local_set.run_until(handle.unwrap()).await.unwrap(), Some(1));
+        assert!(ran.get());
+    }
+}
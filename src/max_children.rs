@@ -0,0 +1,61 @@
+use std::fmt;
+use std::sync::atomic::Ordering;
+
+use crate::Context;
+
+/// Returned by `Context::try_new_child_context` and `ContextRef::try_new_child_context` once the context has as many live children as it may have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextLimitExceeded {
+    /// Live children of the context
+    pub children: usize,
+    pub limit: usize,
+}
+
+impl fmt::Display for ContextLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "context already has {} of at most {} children", self.children, self.limit)
+    }
+}
+
+impl std::error::Error for ContextLimitExceeded {}
+
+impl Context {
+    /// Allow at most `limit` live child contexts. Children that were dropped no longer count.
+    ///
+    /// Once the limit is reached, `try_new_child_context` fails and the other ways of creating a child panic.
+    pub fn with_max_children(self, limit: usize) -> Context {
+        self.inner.max_children.store(limit, Ordering::SeqCst);
+        self
+    }
+
+    /// Create a child context like `new_child_context`, or fail if the limit set with `with_max_children` is reached
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut server = Context::new().with_max_children(1024);
+    /// match server.try_new_child_context() {
+    ///     Ok(_connection) => { /* serve the connection */ }
+    ///     Err(e) => println!("refusing connection: {}", e),
+    /// }
+    /// ```
+    pub fn try_new_child_context(&mut self) -> Result<Context, ContextLimitExceeded> {
+        Context::try_create(Some(&self.inner), Context::builder())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn children_beyond_the_limit_are_refused() {
+        let mut ctx = Context::new().with_max_children(2);
+        let first = ctx.try_new_child_context().unwrap();
+        let _second = ctx.new_child_context();
+        assert_eq!(ctx.try_new_child_context().err(), Some(ContextLimitExceeded { children: 2, limit: 2 }));
+        drop(first);
+        let _third = ctx.try_new_child_context().unwrap();
+        let scope = ctx.as_ref();
+        assert_eq!(scope.try_new_child_context().err(), Some(ContextLimitExceeded { children: 2, limit: 2 }));
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt;
+use std::future::Future;
+
+use crate::Context;
+
+/// Returned by `MaxTasksContext::spawn` once the spawn limit has been reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpawnLimitExceeded {
+    pub limit: usize,
+}
+
+impl fmt::Display for SpawnLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "spawn limit of {} tasks exceeded", self.limit)
+    }
+}
+
+impl std::error::Error for SpawnLimitExceeded {}
+
+/// A context that allows at most `limit` spawns in total, created by `Context::max_tasks`.
+///
+/// This limits the total amount of work, not concurrency: finished tasks still count until `reset` is called.
+pub struct MaxTasksContext {
+    context: Context,
+    limit: usize,
+    spawned: usize,
+}
+
+impl MaxTasksContext {
+    /// Spawn a task like `Context::spawn`, unless `limit` tasks have already been spawned
+    #[track_caller]
+    pub fn spawn<T>(&mut self, future: T) -> Result<tokio::task::JoinHandle<Option<T::Output>>, SpawnLimitExceeded>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        if self.spawned >= self.limit {
+            return Err(SpawnLimitExceeded { limit: self.limit });
+        }
+        self.spawned += 1;
+        Ok(self.context.spawn(future))
+    }
+
+    /// Number of spawns left before the limit is reached
+    pub fn remaining(&self) -> usize {
+        self.limit - self.spawned
+    }
+
+    /// Allow `limit` more spawns
+    pub fn reset(&mut self) {
+        self.spawned = 0;
+    }
+
+    /// The wrapped context
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Unwrap the context, dropping the limit
+    pub fn into_inner(self) -> Context {
+        self.context
+    }
+}
+
+impl Context {
+    /// Limit the total number of tasks that can be spawned on this context to `limit`.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut batch = Context::new().max_tasks(100);
+    /// while let Ok(_) = batch.spawn(async move { /* process one item */ }) {}
+    /// ```
+    pub fn max_tasks(self, limit: usize) -> MaxTasksContext {
+        MaxTasksContext {
+            context: self,
+            limit,
+            spawned: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn spawns_are_counted_until_reset() {
+        let mut batch = Context::new().max_tasks(2);
+        assert_eq!(batch.spawn(async { 1 }).unwrap().await.unwrap(), Some(1));
+        // finished tasks still count
+        assert!(batch.spawn(async {}).is_ok());
+        assert_eq!(batch.spawn(async {}).unwrap_err(), SpawnLimitExceeded { limit: 2 });
+        assert_eq!(batch.remaining(), 0);
+        batch.reset();
+        assert_eq!(batch.remaining(), 2);
+        assert!(batch.spawn(async {}).is_ok());
+    }
+}
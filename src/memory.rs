@@ -0,0 +1,191 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::{CancellationCause, Context, ContextId, ContextInner};
+
+/// What happens when a reservation would take a context past its memory limit, see `ContextBuilder::memory_limit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemoryLimitAction {
+    /// Fail the reservation with `MemoryLimitExceeded`
+    #[default]
+    Reject,
+    /// Grant the reservation, then cancel the context with `CancellationCause::MemoryLimit`
+    Cancel,
+}
+
+/// Returned by `Context::reserve_memory` when a context with `MemoryLimitAction::Reject` would go past its limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLimitExceeded {
+    /// The context whose limit was hit, the reserving context or one of its ancestors
+    pub context: ContextId,
+    pub limit: u64,
+    /// Bytes reserved in that context and its descendants at the time
+    pub reserved: u64,
+    pub requested: u64,
+}
+
+impl fmt::Display for MemoryLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "reserving {} bytes would exceed the memory limit of {}: {} of {} bytes reserved",
+            self.requested, self.context, self.reserved, self.limit
+        )
+    }
+}
+
+impl std::error::Error for MemoryLimitExceeded {}
+
+/// Bytes reserved in a context, see `ContextStats::memory`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MemoryStats {
+    /// Reserved through this context
+    pub reserved: u64,
+    /// Reserved through this context and its descendants
+    pub subtree_reserved: u64,
+    /// Set with `ContextBuilder::memory_limit`, applies to `subtree_reserved`
+    pub limit: Option<u64>,
+}
+
+/// Memory accounting of a context
+#[derive(Default)]
+pub(crate) struct MemoryUsage {
+    own: AtomicU64,
+    subtree: AtomicU64,
+    limit: Option<(u64, MemoryLimitAction)>,
+}
+
+impl MemoryUsage {
+    pub(crate) fn new(limit: Option<(u64, MemoryLimitAction)>) -> MemoryUsage {
+        MemoryUsage { limit, ..Default::default() }
+    }
+
+    pub(crate) fn stats(&self) -> MemoryStats {
+        MemoryStats {
+            reserved: self.own.load(Ordering::SeqCst),
+            subtree_reserved: self.subtree.load(Ordering::SeqCst),
+            limit: self.limit.map(|(limit, _)| limit),
+        }
+    }
+}
+
+/// Bytes attributed to a context by `Context::reserve_memory`, given back when dropped
+#[must_use = "the reservation is released when dropped"]
+pub struct MemoryReservation {
+    inner: Arc<ContextInner>,
+    bytes: u64,
+}
+
+impl MemoryReservation {
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl fmt::Debug for MemoryReservation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryReservation").field("context", &self.inner.id).field("bytes", &self.bytes).finish()
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.inner.memory.own.fetch_sub(self.bytes, Ordering::SeqCst);
+        release(Some(&self.inner), None, self.bytes);
+    }
+}
+
+/// Take `bytes` off the subtree totals from `from` up to, not including, `until`
+fn release(from: Option<&Arc<ContextInner>>, until: Option<&Arc<ContextInner>>, bytes: u64) {
+    let mut ancestor = from;
+    while let Some(inner) = ancestor {
+        if until.is_some_and(|until| Arc::ptr_eq(inner, until)) {
+            return;
+        }
+        inner.memory.subtree.fetch_sub(bytes, Ordering::SeqCst);
+        ancestor = inner.parent.as_ref();
+    }
+}
+
+impl Context {
+    /// Attribute `bytes` to this context until the returned reservation is dropped.
+    ///
+    /// This is cooperative bookkeeping, nothing is allocated or measured: tasks reserve what they are about to hold,
+    /// e.g. the size of a buffered request body. Reservations count for this context and every ancestor, and show in
+    /// `stats()` and the `tree()` snapshot. When the context or an ancestor has a memory limit that the reservation
+    /// would exceed, it fails with `MemoryLimitExceeded` or cancels that context, depending on its
+    /// `MemoryLimitAction`.
+    /// ```rust, no_run
+    /// use tokio_tree_context::{Context, MemoryLimitAction};
+    ///
+    /// # async fn example(mut server: Context, body: Vec<u8>) {
+    /// let mut tenant = Context::builder().memory_limit(64 << 20, MemoryLimitAction::Reject).build_child(&mut server);
+    /// match tenant.reserve_memory(body.len() as u64) {
+    ///     Ok(reservation) => {
+    ///         tenant.spawn(async move {
+    ///             let _reservation = reservation;
+    ///             /* process the body */
+    ///         });
+    ///     }
+    ///     Err(e) => eprintln!("rejected: {e}"),
+    /// }
+    /// # }
+    /// ```
+    pub fn reserve_memory(&self, bytes: u64) -> Result<MemoryReservation, MemoryLimitExceeded> {
+        let mut over_limit = Vec::new();
+        let mut ancestor = Some(&self.inner);
+        while let Some(inner) = ancestor {
+            let reserved = inner.memory.subtree.fetch_add(bytes, Ordering::SeqCst);
+            if let Some((limit, action)) = inner.memory.limit {
+                if reserved.saturating_add(bytes) > limit {
+                    match action {
+                        MemoryLimitAction::Reject => {
+                            inner.memory.subtree.fetch_sub(bytes, Ordering::SeqCst);
+                            release(Some(&self.inner), Some(inner), bytes);
+                            return Err(MemoryLimitExceeded { context: inner.id, limit, reserved, requested: bytes });
+                        }
+                        MemoryLimitAction::Cancel => over_limit.push(inner.clone()),
+                    }
+                }
+            }
+            ancestor = inner.parent.as_ref();
+        }
+        self.inner.memory.own.fetch_add(bytes, Ordering::SeqCst);
+        for inner in over_limit {
+            inner.cancel(CancellationCause::MemoryLimit);
+        }
+        Ok(MemoryReservation { inner: self.inner.clone(), bytes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reservations_roll_up_and_respect_limits() {
+        let mut root = Context::builder().memory_limit(100, MemoryLimitAction::Cancel).build();
+        let mut tenant = Context::builder().memory_limit(50, MemoryLimitAction::Reject).build_child(&mut root);
+        let request = tenant.new_child_context();
+
+        let body = request.reserve_memory(40).unwrap();
+        assert_eq!(request.stats().memory, MemoryStats { reserved: 40, subtree_reserved: 40, limit: None });
+        assert_eq!(tenant.stats().memory, MemoryStats { reserved: 0, subtree_reserved: 40, limit: Some(50) });
+        assert_eq!(root.tree().root.subtree_memory_reserved, 40);
+
+        let error = request.reserve_memory(20).unwrap_err();
+        assert_eq!(error, MemoryLimitExceeded { context: tenant.id(), limit: 50, reserved: 40, requested: 20 });
+        assert_eq!(root.stats().memory.subtree_reserved, 40);
+        drop(body);
+        assert_eq!(root.stats().memory.subtree_reserved, 0);
+
+        let cache = root.reserve_memory(90).unwrap();
+        let _large = tenant.reserve_memory(20).unwrap();
+        assert_eq!(root.cancellation_cause(), Some(CancellationCause::MemoryLimit));
+        assert_eq!(tenant.cancellation_cause(), Some(CancellationCause::Parent));
+        assert_eq!(cache.bytes(), 90);
+        assert_eq!(root.stats().memory.subtree_reserved, 110);
+    }
+}
@@ -0,0 +1,149 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+use crate::{Context, ContextInner};
+
+/// Number of messages of one type a context buffers for its subscribers.
+///
+/// A subscriber that falls further behind than this skips the oldest messages it has not seen yet and continues with
+/// the oldest message still buffered.
+pub const MESSAGE_CAPACITY: usize = 64;
+
+/// Per context broadcast channels, one per message type
+#[derive(Default)]
+pub(crate) struct MessageChannels {
+    senders: Mutex<HashMap<TypeId, Box<dyn Any + Send + Sync>>>,
+}
+
+impl MessageChannels {
+    fn sender<M: Clone + Send + 'static>(&self) -> broadcast::Sender<M> {
+        let mut senders = self.senders.lock().unwrap();
+        senders
+            .entry(TypeId::of::<M>())
+            .or_insert_with(|| Box::new(broadcast::channel::<M>(MESSAGE_CAPACITY).0))
+            .downcast_ref::<broadcast::Sender<M>>()
+            .unwrap()
+            .clone()
+    }
+
+    fn send<M: Clone + Send + 'static>(&self, msg: M) -> usize {
+        let senders = self.senders.lock().unwrap();
+        match senders.get(&TypeId::of::<M>()) {
+            Some(sender) => sender.downcast_ref::<broadcast::Sender<M>>().unwrap().send(msg).unwrap_or(0),
+            None => 0,
+        }
+    }
+}
+
+/// Subscription to the messages of type `M` broadcast to a context, created by `Context::messages`.
+///
+/// The subscription ends when the context is cancelled.
+pub struct Messages<M> {
+    rx: broadcast::Receiver<M>,
+    cancel_receiver: broadcast::Receiver<()>,
+    cancelled: bool,
+}
+
+impl<M: Clone> Messages<M> {
+    /// Receive the next message. Returns None once the context is cancelled. Cancel safe: a message is only taken
+    /// when the future completes.
+    ///
+    /// If this subscriber lagged more than `MESSAGE_CAPACITY` messages behind, the skipped messages are lost and the
+    /// oldest message still buffered is returned.
+    pub async fn recv(&mut self) -> Option<M> {
+        loop {
+            if self.cancelled {
+                return None;
+            }
+            tokio::select! {
+                biased;
+                _ = self.cancel_receiver.recv() => {
+                    self.cancelled = true;
+                }
+                res = self.rx.recv() => match res {
+                    Ok(msg) => return Some(msg),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        }
+    }
+}
+
+impl ContextInner {
+    fn broadcast<M: Clone + Send + 'static>(&self, msg: M) -> usize {
+        let mut delivered = self.messages.send(msg.clone());
+        for child in self.live_children() {
+            delivered += child.broadcast(msg.clone());
+        }
+        delivered
+    }
+}
+
+impl Context {
+    /// Broadcast a message to every subscriber of this context and of all its descendant contexts.
+    ///
+    /// Returns the number of subscribers the message was delivered to. Tasks subscribe with `Context::messages`.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// #[derive(Clone)]
+    /// struct ConfigUpdated(u32);
+    ///
+    /// let mut ctx = Context::new();
+    /// let mut messages = ctx.messages::<ConfigUpdated>();
+    /// ctx.spawn(async move {
+    ///     while let Some(ConfigUpdated(version)) = messages.recv().await {
+    ///         println!("Config is now at version {}", version);
+    ///     }
+    /// });
+    /// ctx.broadcast(ConfigUpdated(2));
+    /// ```
+    pub fn broadcast<M: Clone + Send + 'static>(&self, msg: M) -> usize {
+        self.inner.broadcast(msg)
+    }
+
+    /// Subscribe to the messages of type `M` broadcast to this context or any of its ancestors.
+    ///
+    /// Only messages broadcast after subscribing are received.
+    pub fn messages<M: Clone + Send + 'static>(&self) -> Messages<M> {
+        let cancel_receiver = self.inner.subscribe();
+        Messages {
+            rx: self.inner.messages.sender::<M>().subscribe(),
+            cancel_receiver,
+            cancelled: self.inner.is_cancelled(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct ConfigUpdated(u32);
+
+    #[tokio::test]
+    async fn messages_reach_descendants_and_end_on_cancel() {
+        let mut ctx = Context::new();
+        let child = ctx.new_child_context();
+        let mut root_messages = ctx.messages::<ConfigUpdated>();
+        let mut child_messages = child.messages::<ConfigUpdated>();
+        let mut other_messages = child.messages::<u32>();
+
+        assert_eq!(ctx.broadcast(ConfigUpdated(1)), 2);
+        assert_eq!(root_messages.recv().await, Some(ConfigUpdated(1)));
+        assert_eq!(child_messages.recv().await, Some(ConfigUpdated(1)));
+
+        // broadcasting to the child does not reach the parent
+        assert_eq!(child.broadcast(ConfigUpdated(2)), 1);
+        assert_eq!(child_messages.recv().await, Some(ConfigUpdated(2)));
+
+        drop(ctx);
+        assert_eq!(child_messages.recv().await, None);
+        assert_eq!(other_messages.recv().await, None);
+        assert_eq!(root_messages.recv().await, None);
+    }
+}
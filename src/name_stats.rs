@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::epoch::OutcomeSlot;
+use crate::Context;
+
+/// How the tasks of one name fared, see `Context::task_stats_by_name`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TaskNameStats {
+    pub spawn_count: u64,
+    pub complete_count: u64,
+    /// Tasks that were cancelled, timed out or aborted
+    pub cancel_count: u64,
+    pub panic_count: u64,
+    /// Mean time from spawn to end of the tasks that ended, 0 before any did
+    pub mean_duration_ms: f64,
+}
+
+/// Per name counters of the named tasks of a context
+#[derive(Default)]
+pub(crate) struct NameStats {
+    by_name: Mutex<HashMap<Arc<str>, NameCounters>>,
+}
+
+#[derive(Default)]
+struct NameCounters {
+    stats: TaskNameStats,
+    ended: u64,
+    total: Duration,
+}
+
+impl NameStats {
+    pub(crate) fn task_spawned(&self, name: &Arc<str>) {
+        self.by_name.lock().unwrap().entry(name.clone()).or_default().stats.spawn_count += 1;
+    }
+
+    pub(crate) fn task_ended(&self, name: &Arc<str>, outcome: &OutcomeSlot, ran_for: Duration) {
+        let outcome = outcome.get();
+        if outcome == OutcomeSlot::UNTRACKED {
+            return;
+        }
+        let mut by_name = self.by_name.lock().unwrap();
+        let counters = by_name.entry(name.clone()).or_default();
+        match outcome {
+            OutcomeSlot::COMPLETED => counters.stats.complete_count += 1,
+            OutcomeSlot::PANICKED => counters.stats.panic_count += 1,
+            _ => counters.stats.cancel_count += 1,
+        }
+        counters.ended += 1;
+        counters.total += ran_for;
+        counters.stats.mean_duration_ms = counters.total.as_secs_f64() * 1000.0 / counters.ended as f64;
+    }
+}
+
+impl Context {
+    /// Counts and mean run time of the tasks of this context, by task name, since the context was created.
+    ///
+    /// Tasks without a name, given with `spawn_named` or by the naming strategy of the context, are not included.
+    /// Tasks of child contexts are not included either.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(batch: Context) {
+    /// let mut stats: Vec<_> = batch.task_stats_by_name().into_iter().collect();
+    /// stats.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.cancel_count + stats.panic_count));
+    /// for (name, stats) in stats.iter().take(5) {
+    ///     println!("{name}: {} cancelled, {} panicked", stats.cancel_count, stats.panic_count);
+    /// }
+    /// # }
+    /// ```
+    pub fn task_stats_by_name(&self) -> HashMap<String, TaskNameStats> {
+        let by_name = self.inner.name_stats.by_name.lock().unwrap();
+        by_name.iter().map(|(name, counters)| (name.to_string(), counters.stats)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn stats_are_aggregated_by_name() {
+        let mut ctx = Context::new();
+        for secs in [1, 3] {
+            ctx.spawn_named("fetch", tokio::time::sleep(Duration::from_secs(secs))).await.unwrap();
+        }
+        ctx.spawn_named("parse", async { panic!("bad input") }).await.unwrap_err();
+        let stuck = ctx.spawn_named("fetch", std::future::pending::<()>());
+        ctx.spawn(async {}).await.unwrap();
+
+        let stats = ctx.task_stats_by_name();
+        assert_eq!(stats.len(), 2);
+        let fetch = stats["fetch"];
+        assert_eq!((fetch.spawn_count, fetch.complete_count, fetch.cancel_count), (3, 2, 0));
+        assert_eq!(fetch.mean_duration_ms, 2000.0);
+        assert_eq!(stats["parse"].panic_count, 1);
+
+        stuck.abort();
+        let _ = stuck.await;
+        assert_eq!(ctx.task_stats_by_name()["fetch"].cancel_count, 1);
+    }
+}
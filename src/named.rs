@@ -0,0 +1,132 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::panic::Location;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+
+use crate::result::CatchPanic;
+use crate::{Context, ContextInner, SpawnError, TaskOptions, TaskResult};
+
+/// A set of uniquely named tasks whose results are collected into a map, created by `Context::named_group`
+pub struct NamedGroup<T> {
+    inner: Arc<ContextInner>,
+    names: HashSet<String>,
+    tasks: JoinSet<(String, TaskResult<T>)>,
+}
+
+impl<T: Send + 'static> NamedGroup<T> {
+    /// Spawn a task under `name`. Fails with `SpawnError::DuplicateName` if the group already has a task with that
+    /// name.
+    #[track_caller]
+    pub fn spawn<F>(&mut self, name: impl Into<String>, future: F) -> Result<(), SpawnError>
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        self.spawn_at(name.into(), future, None, Location::caller())
+    }
+
+    /// Same as `spawn`, but the task times out after `timeout`
+    #[track_caller]
+    pub fn spawn_with_timeout<F>(&mut self, name: impl Into<String>, future: F, timeout: Duration) -> Result<(), SpawnError>
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        self.spawn_at(name.into(), future, Some(timeout), Location::caller())
+    }
+
+    fn spawn_at<F>(&mut self, name: String, future: F, timeout: Option<Duration>, location: &'static Location<'static>) -> Result<(), SpawnError>
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        if self.names.contains(&name) {
+            return Err(SpawnError::DuplicateName(name));
+        }
+        let task = self.inner.task_future_at(Some(name.clone()), future, location, TaskOptions { timeout, ..Default::default() })?;
+        let inner = self.inner.clone();
+        self.names.insert(name.clone());
+        let runtime = self.inner.runtime();
+        let task = async move {
+            let result = match CatchPanic(Box::pin(task)).await {
+                Ok(Some(output)) => TaskResult::Completed(output),
+                Ok(None) if inner.is_cancelled() && !deadline_passed(&inner) => TaskResult::Cancelled,
+                Ok(None) => TaskResult::TimedOut,
+                Err(message) => TaskResult::Panicked(message),
+            };
+            (name, result)
+        };
+        self.tasks.spawn_on(task, &runtime);
+        Ok(())
+    }
+
+    /// Wait for every task and return their results by name. Tasks that were cancelled, timed out or panicked are
+    /// included with the matching variant.
+    pub async fn join_named(mut self) -> HashMap<String, TaskResult<T>> {
+        let mut results = HashMap::with_capacity(self.tasks.len());
+        while let Some(joined) = self.tasks.join_next().await {
+            if let Ok((name, result)) = joined {
+                results.insert(name, result);
+            }
+        }
+        results
+    }
+}
+
+/// Whether the deadline of the context, or of an ancestor, has passed
+fn deadline_passed(inner: &ContextInner) -> bool {
+    inner.effective_deadline().is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+impl Context {
+    /// Create a group of named tasks on this context, whose results are collected by name.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(check_disk: bool) {
+    /// let ctx = Context::new();
+    /// let mut checks = ctx.named_group();
+    /// checks.spawn("network", async { true }).unwrap();
+    /// if check_disk {
+    ///     checks.spawn("disk", async { true }).unwrap();
+    /// }
+    /// for (name, result) in checks.join_named().await {
+    ///     println!("{}: {:?}", name, result);
+    /// }
+    /// # }
+    /// ```
+    pub fn named_group<T>(&self) -> NamedGroup<T> {
+        NamedGroup {
+            inner: self.inner.clone(),
+            names: HashSet::new(),
+            tasks: JoinSet::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn results_are_keyed_by_name() {
+        let mut ctx = Context::new();
+        let mut group = ctx.named_group();
+        group.spawn("fast", async { 1 }).unwrap();
+        group.spawn_with_timeout("slow", std::future::pending(), Duration::from_secs(1)).unwrap();
+        group.spawn("broken", async { panic!("broken step") }).unwrap();
+        assert_eq!(group.spawn("fast", async { 2 }), Err(SpawnError::DuplicateName("fast".to_string())));
+        let results = group.join_named().await;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results["fast"], TaskResult::Completed(1));
+        assert_eq!(results["slow"], TaskResult::TimedOut);
+        assert_eq!(results["broken"], TaskResult::Panicked("broken step".to_string()));
+
+        let child = ctx.new_child_context();
+        let mut group = child.named_group::<()>();
+        group.spawn("stuck", std::future::pending()).unwrap();
+        let results = group.join_named();
+        drop(child);
+        assert_eq!(results.await["stuck"], TaskResult::Cancelled);
+    }
+}
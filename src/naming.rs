@@ -0,0 +1,111 @@
+use std::fmt;
+use std::panic::Location;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::Context;
+
+/// How tasks spawned without a name are named, see `Context::with_task_naming_strategy`
+#[derive(Default)]
+pub enum NamingStrategy {
+    /// Tasks without a name stay unnamed
+    #[default]
+    None,
+    /// Name tasks after the `file:line` they were spawned from
+    SourceLocation,
+    /// Name tasks `task-1`, `task-2`, ... in the order they are spawned on the context
+    Counter,
+    /// Name tasks with the result of the function, given the same sequence number as `Counter`
+    Custom(Box<dyn Fn(u64) -> String + Send + Sync>),
+}
+
+impl fmt::Debug for NamingStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NamingStrategy::None => write!(f, "None"),
+            NamingStrategy::SourceLocation => write!(f, "SourceLocation"),
+            NamingStrategy::Counter => write!(f, "Counter"),
+            NamingStrategy::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/// Naming strategy of a context, with the sequence number of the next task it names
+#[derive(Default)]
+pub(crate) struct TaskNaming {
+    strategy: Mutex<NamingStrategy>,
+    next: AtomicU64,
+}
+
+impl TaskNaming {
+    /// Name for a task spawned without one at `location`
+    pub(crate) fn name(&self, location: &'static Location<'static>) -> Option<String> {
+        let strategy = self.strategy.lock().unwrap();
+        let next = || self.next.fetch_add(1, Ordering::Relaxed) + 1;
+        match &*strategy {
+            NamingStrategy::None => None,
+            NamingStrategy::SourceLocation => Some(format!("{}:{}", location.file(), location.line())),
+            NamingStrategy::Counter => Some(format!("task-{}", next())),
+            NamingStrategy::Custom(name) => Some(name(next())),
+        }
+    }
+}
+
+/// Spawn a task named after the `file:line` of the macro call, whatever the naming strategy of the context.
+/// ```rust, no_run
+/// use tokio_tree_context::{spawn_auto_named, Context};
+///
+/// let mut ctx = Context::new();
+/// let handle = spawn_auto_named!(ctx, async move { /* do your work here */ });
+/// ```
+#[macro_export]
+macro_rules! spawn_auto_named {
+    ($ctx:expr, $future:expr $(,)?) => {
+        $ctx.spawn_named(concat!(file!(), ":", line!()), $future)
+    };
+}
+
+impl Context {
+    /// Name the tasks spawned on this context without a name, such as with `spawn`, using `strategy`.
+    ///
+    /// Names show up in `tree()`, events and admission hooks. Tasks of child contexts are not affected.
+    /// ```rust, no_run
+    /// use tokio_tree_context::{Context, NamingStrategy};
+    ///
+    /// let mut ctx = Context::new().with_task_naming_strategy(NamingStrategy::Counter);
+    /// ctx.spawn(async move { /* shows up as task-1 */ });
+    /// ```
+    pub fn with_task_naming_strategy(self, strategy: NamingStrategy) -> Context {
+        *self.inner.naming.strategy.lock().unwrap() = strategy;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(ctx: &Context) -> Vec<String> {
+        ctx.tree().root.tasks.iter().map(|task| task.name.as_deref().unwrap_or("").to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn tasks_are_named_by_the_strategy() {
+        let pending = std::future::pending::<()>;
+        let mut ctx = Context::new().with_task_naming_strategy(NamingStrategy::Counter);
+        ctx.spawn(pending());
+        ctx.spawn_named("explicit", pending());
+        ctx.spawn(pending());
+        assert_eq!(names(&ctx), vec!["task-1", "explicit", "task-2"]);
+
+        let mut ctx = Context::new().with_task_naming_strategy(NamingStrategy::SourceLocation);
+        ctx.spawn(pending());
+        let line = line!() - 1;
+        spawn_auto_named!(ctx, pending());
+        assert_eq!(names(&ctx), vec![format!("{}:{}", file!(), line), format!("{}:{}", file!(), line + 2)]);
+
+        let mut ctx = Context::new().with_task_naming_strategy(NamingStrategy::Custom(Box::new(|n| format!("worker-{n}"))));
+        ctx.spawn(pending());
+        assert_eq!(names(&ctx), vec!["worker-1"]);
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::time::Instant;
+
+use crate::{CancellationCause, Context};
+
+/// Why `Context::connect` or `Context::connect_with` failed
+#[derive(Debug)]
+pub enum ConnectError {
+    /// The context was cancelled for a reason other than its deadline
+    Cancelled(CancellationCause),
+    /// The deadline of the context was reached, or the last attempt timed out
+    Timeout,
+    /// The last attempt failed, or the address could not be resolved
+    Io(io::Error),
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectError::Cancelled(cause) => write!(f, "connect cancelled: {:?}", cause),
+            ConnectError::Timeout => write!(f, "connect timed out"),
+            ConnectError::Io(error) => write!(f, "connect failed: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for ConnectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConnectError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl Context {
+    /// Resolve `addr` and connect to the resolved addresses one after the other, until one succeeds, the context is
+    /// cancelled or its effective deadline is reached.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(ctx: Context) -> Result<(), tokio_tree_context::ConnectError> {
+    /// let stream = ctx.connect("example.com:80").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn connect(&self, addr: impl ToSocketAddrs) -> Result<TcpStream, ConnectError> {
+        self.connect_attempts(addr, None).await
+    }
+
+    /// Like `connect`, but give up on each resolved address after `attempt_timeout` and move on to the next one
+    pub async fn connect_with_attempt_timeout(&self, addr: impl ToSocketAddrs, attempt_timeout: Duration) -> Result<TcpStream, ConnectError> {
+        self.connect_attempts(addr, Some(attempt_timeout)).await
+    }
+
+    async fn connect_attempts(&self, addr: impl ToSocketAddrs, attempt_timeout: Option<Duration>) -> Result<TcpStream, ConnectError> {
+        let addrs = self.connect_with(tokio::net::lookup_host(addr)).await?;
+        let mut last_error = ConnectError::Io(io::Error::new(io::ErrorKind::InvalidInput, "address resolved to nothing"));
+        for addr in addrs {
+            let attempt = async move {
+                match attempt_timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, TcpStream::connect(addr))
+                        .await
+                        .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into())),
+                    None => TcpStream::connect(addr).await,
+                }
+            };
+            match self.connect_with(attempt).await {
+                Ok(stream) => return Ok(stream),
+                Err(ConnectError::Io(error)) if error.kind() == io::ErrorKind::TimedOut => last_error = ConnectError::Timeout,
+                Err(ConnectError::Io(error)) => last_error = ConnectError::Io(error),
+                Err(error) => return Err(error),
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Run a connection step, such as a TLS handshake, until it completes, the context is cancelled or its effective
+    /// deadline is reached.
+    pub async fn connect_with<F, T>(&self, connect: F) -> Result<T, ConnectError>
+    where
+        F: Future<Output = io::Result<T>>,
+    {
+        let deadline = self.deadline();
+        let expired = async move {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            biased;
+            cause = self.cancellation_signal() => match deadline {
+                Some(deadline) if Instant::now() >= deadline => Err(ConnectError::Timeout),
+                _ => Err(ConnectError::Cancelled(cause)),
+            },
+            _ = expired => Err(ConnectError::Timeout),
+            res = connect => res.map_err(ConnectError::Io),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn connect_tries_addresses_and_reports_errors() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ctx = Context::new();
+        assert!(ctx.connect(listener.local_addr().unwrap()).await.is_ok());
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        assert!(matches!(ctx.connect(addr).await, Err(ConnectError::Io(error)) if error.kind() == io::ErrorKind::ConnectionRefused));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connect_with_is_cancelled_or_timed_out() {
+        let mut root = Context::new();
+        let child = root.with_timeout(Duration::from_secs(5));
+        let handshake = std::future::pending::<io::Result<()>>();
+        assert!(matches!(child.connect_with(handshake).await, Err(ConnectError::Timeout)));
+
+        let child = root.new_child_context();
+        let handshake = child.connect_with(std::future::pending::<io::Result<()>>());
+        drop(root);
+        assert!(matches!(handshake.await, Err(ConnectError::Cancelled(CancellationCause::Parent))));
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::{Barrier, BarrierWaitResult, Notify};
+
+use crate::{Context, ContextInner};
+
+/// The context was cancelled while waiting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "context cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// A `Notify` whose waiters all give up with `Cancelled` once its context is cancelled. Created by
+/// `Context::scoped_notify`.
+#[derive(Clone)]
+pub struct ScopedNotify {
+    notify: Arc<Notify>,
+    context: Arc<ContextInner>,
+}
+
+impl ScopedNotify {
+    /// See `Notify::notify_one`
+    pub fn notify_one(&self) {
+        self.notify.notify_one();
+    }
+
+    /// See `Notify::notify_waiters`
+    pub fn notify_waiters(&self) {
+        self.notify.notify_waiters();
+    }
+
+    /// Wait for a notification, or fail once the context is cancelled. Cancel safe: a `notify_one` received by a
+    /// future that is dropped before completing is passed on to the next waiter.
+    pub async fn notified(&self) -> Result<(), Cancelled> {
+        wait_notified(&self.context, &self.notify).await
+    }
+}
+
+async fn wait_notified(context: &ContextInner, notify: &Notify) -> Result<(), Cancelled> {
+    let cancelled = context.cancelled();
+    tokio::select! {
+        biased;
+        _ = cancelled => Err(Cancelled),
+        _ = notify.notified() => Ok(()),
+    }
+}
+
+impl Context {
+    /// Wait for `notify`, or give up once this context is cancelled. Cancel safe, like `ScopedNotify::notified`.
+    pub async fn notified(&self, notify: &Notify) -> Result<(), Cancelled> {
+        wait_notified(&self.inner, notify).await
+    }
+
+    /// Wait at `barrier`, or give up once this context is cancelled.
+    ///
+    /// If the context is already cancelled, the task does not arrive at the barrier at all. A task that arrived and
+    /// then gives up still counts as arrived, because a `Barrier` cannot take an arrival back: the remaining
+    /// waiters are released as soon as the others arrive, and the next generation starts from a clean count.
+    pub async fn barrier_wait(&self, barrier: &Barrier) -> Result<BarrierWaitResult, Cancelled> {
+        let cancelled = self.inner.cancelled();
+        if self.is_cancelled() {
+            return Err(Cancelled);
+        }
+        tokio::select! {
+            biased;
+            _ = cancelled => Err(Cancelled),
+            result = barrier.wait() => Ok(result),
+        }
+    }
+
+    /// A `Notify` owned by this context: once the context is cancelled, all its waiters are woken with `Cancelled`.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example() {
+    /// let mut ctx = Context::new();
+    /// let work_available = ctx.scoped_notify();
+    /// let waiter = work_available.clone();
+    /// ctx.spawn(async move {
+    ///     while waiter.notified().await.is_ok() {
+    ///         // pick up work
+    ///     }
+    /// });
+    /// work_available.notify_one();
+    /// # }
+    /// ```
+    pub fn scoped_notify(&self) -> ScopedNotify {
+        ScopedNotify {
+            notify: Arc::new(Notify::new()),
+            context: self.inner.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CancellationCause;
+
+    #[tokio::test]
+    async fn waiters_give_up_on_cancel() {
+        let ctx = Arc::new(Context::new());
+        let notify = Arc::new(Notify::new());
+        let wait = |ctx: Arc<Context>, notify: Arc<Notify>| tokio::spawn(async move { ctx.notified(&notify).await });
+        let notified = wait(ctx.clone(), notify.clone());
+        tokio::task::yield_now().await;
+        notify.notify_one();
+        assert_eq!(notified.await.unwrap(), Ok(()));
+
+        let plain = wait(ctx.clone(), notify.clone());
+        let scoped = ctx.scoped_notify();
+        let scoped = tokio::spawn(async move { scoped.notified().await });
+        tokio::task::yield_now().await;
+        ctx.inner.cancel(CancellationCause::Explicit);
+        assert_eq!(plain.await.unwrap(), Err(Cancelled));
+        assert_eq!(scoped.await.unwrap(), Err(Cancelled));
+    }
+
+    #[tokio::test]
+    async fn cancelled_barrier_waiters_keep_the_count_consistent() {
+        let barrier = Arc::new(Barrier::new(2));
+        let cancelled = Context::new();
+        cancelled.inner.cancel(CancellationCause::Explicit);
+        // does not arrive, so two more waiters are needed
+        assert!(cancelled.barrier_wait(&barrier).await.is_err());
+
+        let ctx = Arc::new(Context::new());
+        let arrived = tokio::spawn({
+            let (ctx, barrier) = (ctx.clone(), barrier.clone());
+            async move { ctx.barrier_wait(&barrier).await }
+        });
+        tokio::task::yield_now().await;
+        ctx.inner.cancel(CancellationCause::Explicit);
+        assert_eq!(arrived.await.unwrap().unwrap_err(), Cancelled);
+        // the cancelled waiter counts as arrived, so the next one completes the generation
+        assert!(barrier.wait().await.is_leader());
+        // and the following generation needs two waiters again
+        let (a, b) = tokio::join!(barrier.wait(), barrier.wait());
+        assert!(a.is_leader() != b.is_leader());
+    }
+}
@@ -0,0 +1,247 @@
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+use crate::result::CatchPanic;
+use crate::{CancellationCause, Context, ContextInner};
+
+/// Why a nursery did not complete successfully
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NurseryError<E> {
+    /// The body or a task returned this error
+    Failed(E),
+    /// A task panicked, with the panic message if it was a string
+    Panicked(String),
+    /// The enclosing context was cancelled
+    Cancelled,
+}
+
+impl<E: fmt::Display> fmt::Display for NurseryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NurseryError::Failed(error) => write!(f, "nursery task failed: {}", error),
+            NurseryError::Panicked(message) => write!(f, "nursery task panicked: {}", message),
+            NurseryError::Cancelled => write!(f, "nursery was cancelled"),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for NurseryError<E> {}
+
+/// Handle to spawn tasks into a nursery, see `Context::nursery`.
+///
+/// It can be cloned and moved into tasks, so they can spawn further siblings.
+pub struct Nursery<E> {
+    shared: Arc<Shared<E>>,
+}
+
+impl<E> Clone for Nursery<E> {
+    fn clone(&self) -> Self {
+        Nursery {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+struct Shared<E> {
+    /// The child context all tasks of the nursery run under
+    context: Arc<ContextInner>,
+    outstanding: AtomicUsize,
+    finished: Notify,
+    /// The first failure, which cancels the rest of the nursery
+    error: Mutex<Option<NurseryError<E>>>,
+}
+
+impl<E> Shared<E> {
+    fn fail(&self, error: NurseryError<E>) {
+        self.error.lock().unwrap().get_or_insert(error);
+        self.context.cancel(CancellationCause::Explicit);
+    }
+}
+
+/// Counts a task as outstanding until it is dropped, however it ends
+struct Outstanding<E>(Arc<Shared<E>>);
+
+impl<E> Drop for Outstanding<E> {
+    fn drop(&mut self) {
+        if self.0.outstanding.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.finished.notify_waiters();
+        }
+    }
+}
+
+impl<E: Send + 'static> Nursery<E> {
+    /// Spawn a task into the nursery. If it returns `Err` or panics, all other tasks are cancelled and the nursery
+    /// fails with that error. Tasks spawned after the nursery has been cancelled never run.
+    #[track_caller]
+    pub fn spawn<T>(&self, future: T)
+    where
+        T: Future<Output = Result<(), E>> + Send + 'static,
+    {
+        self.shared.outstanding.fetch_add(1, Ordering::SeqCst);
+        let outstanding = Outstanding(self.shared.clone());
+        if let Ok(task) = self.shared.context.task_future(None, CatchPanic(Box::pin(future)), None) {
+            self.shared.context.spawn(async move {
+                let shared = outstanding.0.clone();
+                match task.await {
+                    Some(Ok(Err(error))) => shared.fail(NurseryError::Failed(error)),
+                    Some(Err(message)) => shared.fail(NurseryError::Panicked(message)),
+                    _ => {}
+                }
+                drop(outstanding);
+            });
+        }
+    }
+}
+
+impl Context {
+    /// Run `body` with a nursery, and complete only once every task spawned into it has finished.
+    ///
+    /// The nursery runs under a child context. If the body or any task returns `Err` or panics, the remaining tasks
+    /// are cancelled and the first failure is returned. If this context is cancelled, all tasks are cancelled and
+    /// `NurseryError::Cancelled` is returned. Either way, the body is dropped at its next await.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example() -> Result<(), tokio_tree_context::NurseryError<std::io::Error>> {
+    /// let mut ctx = Context::new();
+    /// ctx.nursery(|n| async move {
+    ///     n.spawn(async { /* fetch a */ Ok(()) });
+    ///     n.spawn(async { /* fetch b */ Ok(()) });
+    ///     Ok(())
+    /// })
+    /// .await
+    /// # }
+    /// ```
+    pub fn nursery<E, F, B>(&mut self, body: F) -> impl Future<Output = Result<(), NurseryError<E>>>
+    where
+        E: Send + 'static,
+        F: FnOnce(Nursery<E>) -> B,
+        B: Future<Output = Result<(), E>>,
+    {
+        let context = self.new_child_context();
+        let shared = Arc::new(Shared {
+            context: context.inner.clone(),
+            outstanding: AtomicUsize::new(0),
+            finished: Notify::new(),
+            error: Mutex::new(None),
+        });
+        let nursery = Nursery { shared: shared.clone() };
+        let cancelled = context.inner.cancelled();
+        async move {
+            // a failed sibling or a cancelled parent stops the body as well as the tasks
+            tokio::select! {
+                result = body(nursery) => {
+                    if let Err(error) = result {
+                        shared.fail(NurseryError::Failed(error));
+                    }
+                }
+                _ = cancelled => {}
+            }
+            loop {
+                let finished = shared.finished.notified();
+                if shared.outstanding.load(Ordering::SeqCst) == 0 {
+                    break;
+                }
+                finished.await;
+            }
+            let cancelled = context.is_cancelled();
+            drop(context);
+            match shared.error.lock().unwrap().take() {
+                Some(error) => Err(error),
+                None if cancelled => Err(NurseryError::Cancelled),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Context, NurseryError};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[tokio::test(start_paused = true)]
+    async fn failing_task_cancels_siblings() {
+        let mut ctx = Context::new();
+        let completed = Arc::new(AtomicBool::new(false));
+        let flag = completed.clone();
+        let start = Instant::now();
+        let result = ctx
+            .nursery(|n| async move {
+                let sibling = n.clone();
+                n.spawn(async move {
+                    // spawn a sibling from inside a task, that is mid-await when the other one fails
+                    sibling.spawn(async move {
+                        tokio::time::sleep(Duration::from_secs(10)).await;
+                        flag.store(true, Ordering::SeqCst);
+                        Ok(())
+                    });
+                    Ok(())
+                });
+                n.spawn(async {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    Err("failed")
+                });
+                Ok(())
+            })
+            .await;
+        assert_eq!(result, Err(NurseryError::Failed("failed")));
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert!(!completed.load(Ordering::SeqCst));
+        assert!(!ctx.is_cancelled());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn nursery_waits_for_tasks_and_reports_panics() {
+        let mut ctx = Context::new();
+        let start = Instant::now();
+        let ok = ctx.nursery::<(), _, _>(|n| async move {
+            n.spawn(async {
+                tokio::time::sleep(Duration::from_secs(3)).await;
+                Ok(())
+            });
+            Ok(())
+        });
+        assert_eq!(ok.await, Ok(()));
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+
+        let panicked = ctx.nursery::<(), _, _>(|n| async move {
+            n.spawn(async { panic!("boom") });
+            n.spawn(std::future::pending());
+            Ok(())
+        });
+        assert_eq!(panicked.await, Err(NurseryError::Panicked("boom".to_string())));
+
+        let mut child = ctx.new_child_context();
+        let cancelled = child.nursery::<(), _, _>(|n| async move {
+            n.spawn(std::future::pending());
+            Ok(())
+        });
+        drop(ctx);
+        assert_eq!(cancelled.await, Err(NurseryError::Cancelled));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_failing_sibling_stops_the_body() {
+        let mut ctx = Context::new();
+        let failed = ctx.nursery(|n| async move {
+            n.spawn(async {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                Err("failed")
+            });
+            std::future::pending().await
+        });
+        assert_eq!(failed.await, Err(NurseryError::Failed("failed")));
+
+        let mut child = ctx.new_child_context();
+        let cancelled = child.nursery::<(), _, _>(|_| std::future::pending());
+        drop(ctx);
+        assert_eq!(cancelled.await, Err(NurseryError::Cancelled));
+    }
+}
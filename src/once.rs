@@ -0,0 +1,125 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::panic::Location;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::{Context, TaskOptions};
+
+/// Tasks spawned with `Context::spawn_once`, keyed by the types of the key and the output, then by the key itself
+#[derive(Default)]
+pub(crate) struct OnceTasks {
+    tasks: Mutex<HashMap<TypeId, Box<dyn Any + Send>>>,
+}
+
+type OnceHandle<T> = Arc<JoinHandle<Option<T>>>;
+
+type Task<T> = Pin<Box<dyn Future<Output = Option<T>> + Send>>;
+
+impl Context {
+    /// Spawn a task identified by `key`, unless a task with the same key and output type is still running under this
+    /// context.
+    ///
+    /// If one is running, its handle is returned and `factory` is not called. Once that task has finished, the next
+    /// call spawns a new one. This keeps concurrent callers from starting the same idempotent background work (cache
+    /// refreshes, warmups) more than once. Nothing is locked while `factory` runs and callers never wait for each
+    /// other: a concurrent call gets the handle as soon as the task is registered, before `factory` has returned.
+    /// If the task is refused or `factory` panics, the handle resolves to None.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut ctx = Context::new();
+    /// let first = ctx.spawn_once("refresh", || async move { /* refresh the cache */ });
+    /// let second = ctx.spawn_once("refresh", || async move { /* not called while the first runs */ });
+    /// ```
+    #[track_caller]
+    pub fn spawn_once<K, F, Fut>(&mut self, key: K, factory: F) -> Arc<JoinHandle<Option<Fut::Output>>>
+    where
+        K: Hash + Eq + Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future + Send + 'static,
+        Fut::Output: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel::<Task<Fut::Output>>();
+        let handle = {
+            let mut tasks = self.inner.once_tasks.tasks.lock().unwrap();
+            let by_key = tasks
+                .entry(TypeId::of::<(K, Fut::Output)>())
+                .or_insert_with(|| Box::new(HashMap::<K, OnceHandle<Fut::Output>>::new()))
+                .downcast_mut::<HashMap<K, OnceHandle<Fut::Output>>>()
+                .expect("entries are keyed by their key and output types");
+            by_key.retain(|_, handle| !handle.is_finished());
+            if let Some(handle) = by_key.get(&key) {
+                return handle.clone();
+            }
+            // runs the task once it is built, so the entry exists before any user code runs
+            let handle = Arc::new(self.inner.spawn(async move { rx.await.ok()?.await }));
+            by_key.insert(key, handle.clone());
+            handle
+        };
+        if let Ok(task) = self.inner.task_future_at(None, factory(), Location::caller(), TaskOptions::default()) {
+            let _ = tx.send(Box::pin(task));
+        }
+        handle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn running_task_is_reused_until_finished() {
+        let mut ctx = Context::new();
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let first = ctx.spawn_once("refresh", || async move {
+            let _ = rx.await;
+        });
+        let second = ctx.spawn_once("refresh", || async move {
+            unreachable!();
+        });
+        assert!(Arc::ptr_eq(&first, &second));
+        let other = ctx.spawn_once("warmup", || async {});
+        assert!(!Arc::ptr_eq(&first, &other));
+
+        tx.send(()).unwrap();
+        while !first.is_finished() {
+            tokio::task::yield_now().await;
+        }
+        let third = ctx.spawn_once("refresh", || async {});
+        assert!(!Arc::ptr_eq(&first, &third));
+    }
+
+    #[tokio::test]
+    async fn output_types_do_not_collide_and_failed_factories_are_replaced() {
+        let mut ctx = Context::new();
+        let number = ctx.spawn_once("warmup", std::future::pending::<u32>);
+        let text = ctx.spawn_once("warmup", || async { "done" });
+        assert_ne!(text.id(), number.id());
+        assert!(Arc::ptr_eq(&number, &ctx.spawn_once("warmup", || async { unreachable!() as u32 })));
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            ctx.spawn_once("cache", || -> std::future::Ready<()> { panic!("factory failed") })
+        }));
+        assert!(panicked.is_err());
+        // the task of the failed factory resolves to None right away
+        tokio::task::yield_now().await;
+        let mut called = false;
+        ctx.spawn_once("cache", || {
+            called = true;
+            async {}
+        });
+        assert!(called);
+
+        let mut child = ctx.new_child_context();
+        drop(ctx);
+        let refused = child.spawn_once("late", || async { 2 });
+        while !refused.is_finished() {
+            tokio::task::yield_now().await;
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::future::Future;
+
+use opentelemetry::trace::FutureExt;
+
+use crate::Context;
+
+/// OpenTelemetry context, with its active span and baggage, that a task runs in. See
+/// `Context::spawn_with_trace_context`.
+#[derive(Debug, Clone, Default)]
+pub struct TraceContext(pub opentelemetry::Context);
+
+impl TraceContext {
+    /// The OpenTelemetry context of the caller
+    pub fn current() -> TraceContext {
+        TraceContext(opentelemetry::Context::current())
+    }
+}
+
+impl From<opentelemetry::Context> for TraceContext {
+    fn from(context: opentelemetry::Context) -> Self {
+        TraceContext(context)
+    }
+}
+
+impl Context {
+    /// Spawn a task that runs in `trace_ctx`: every time the task is polled, `trace_ctx` is the current OpenTelemetry
+    /// context, so spans started by the task are children of its active span and the task sees its baggage.
+    /// ```rust, no_run
+    /// use tokio_tree_context::{Context, TraceContext};
+    ///
+    /// let mut ctx = Context::new();
+    /// ctx.spawn_with_trace_context(TraceContext::current(), async move {
+    ///     // spans created here are children of the caller's active span
+    /// });
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_trace_context<T>(&mut self, trace_ctx: TraceContext, future: T) -> tokio::task::JoinHandle<Option<T::Output>>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        self.spawn(future.with_context(trace_ctx.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct RequestId(u32);
+
+    #[tokio::test]
+    async fn task_runs_in_the_trace_context() {
+        let mut ctx = Context::new();
+        let trace_ctx = TraceContext::from(opentelemetry::Context::new().with_value(RequestId(7)));
+        let seen = ctx.spawn_with_trace_context(trace_ctx, async {
+            tokio::task::yield_now().await;
+            opentelemetry::Context::current().get::<RequestId>().map(|id| id.0)
+        });
+        assert_eq!(seen.await.unwrap(), Some(Some(7)));
+        assert!(opentelemetry::Context::current().get::<RequestId>().is_none());
+    }
+}
@@ -0,0 +1,66 @@
+use std::future::Future;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::Context;
+
+impl Context {
+    /// Spawn a task that produces items into a channel holding at most `limit` of them.
+    ///
+    /// `factory` is given the sending half and returns the future to run. A task that produces faster than the
+    /// receiver consumes waits in `send`, and still stops there when the context is cancelled.
+    ///
+    /// Panics if `limit` is 0.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example() {
+    /// let mut ctx = Context::new();
+    /// let (_handle, mut rows) = ctx.spawn_with_output_limit(|rows| async move {
+    ///     for row in 0.. {
+    ///         if rows.send(row).await.is_err() {
+    ///             break;
+    ///         }
+    ///     }
+    /// }, 16);
+    /// while let Some(row) = rows.recv().await {
+    ///     println!("{row}");
+    /// }
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_output_limit<T, F, Fut>(&mut self, factory: F, limit: usize) -> (JoinHandle<Option<()>>, mpsc::Receiver<T>)
+    where
+        T: Send + 'static,
+        F: FnOnce(mpsc::Sender<T>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel(limit);
+        (self.spawn(factory(tx)), rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn blocked_producer_stops_on_cancel() {
+        let mut root = Context::new();
+        let mut ctx = root.new_child_context();
+        let (handle, mut rows) = ctx.spawn_with_output_limit(|rows| async move {
+            for row in 0.. {
+                rows.send(row).await.unwrap();
+            }
+        }, 1);
+        // let the producer fill the channel and block on the second row
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(rows.capacity(), 0);
+        drop(ctx);
+        assert_eq!(handle.await.unwrap(), None);
+        assert_eq!(rows.recv().await, Some(0));
+        assert_eq!(rows.recv().await, None);
+    }
+}
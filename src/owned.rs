@@ -0,0 +1,95 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+use tokio::task::{JoinError, JoinHandle};
+
+use crate::Context;
+
+/// Handle of a task spawned with `Context::spawn_owned`. Dropping it aborts the task.
+///
+/// Awaiting it gives the same result as awaiting the `JoinHandle` of `Context::spawn`.
+pub struct OwnedHandle<T> {
+    handle: Option<JoinHandle<Option<T>>>,
+}
+
+impl<T> OwnedHandle<T> {
+    /// Let the task keep running after the handle is dropped, like a task spawned with `Context::spawn`
+    pub fn detach(mut self) -> JoinHandle<Option<T>> {
+        self.handle.take().unwrap()
+    }
+
+    /// Abort the task without waiting for it
+    pub fn abort(&self) {
+        if let Some(handle) = &self.handle {
+            handle.abort();
+        }
+    }
+}
+
+impl<T> Future for OwnedHandle<T> {
+    type Output = Result<Option<T>, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        Pin::new(self.handle.as_mut().expect("polled after detach")).poll(cx)
+    }
+}
+
+impl<T> Drop for OwnedHandle<T> {
+    fn drop(&mut self) {
+        if let Some(handle) = &self.handle {
+            handle.abort();
+        }
+    }
+}
+
+impl Context {
+    /// Spawn a task that is aborted once nobody waits for it anymore, i.e. when the returned handle is dropped.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example() {
+    /// let mut ctx = Context::new();
+    /// let lookup = ctx.spawn_owned(async move { /* query a replica */ 42 });
+    /// // the lookup stops if the request gives up before it completes
+    /// let _ = tokio::time::timeout(Duration::from_secs(1), lookup).await;
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn spawn_owned<T>(&mut self, future: T) -> OwnedHandle<T::Output>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        OwnedHandle {
+            handle: Some(self.spawn(future)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn dropped_handle_aborts_task_and_releases_it_once() {
+        let mut ctx = Context::new();
+        let dropped = ctx.spawn_owned(std::future::pending::<()>());
+        let awaited = ctx.spawn_owned(tokio::time::sleep(Duration::from_secs(1)));
+        let detached = ctx.spawn_owned(tokio::time::sleep(Duration::from_secs(2))).detach();
+        tokio::task::yield_now().await;
+        assert_eq!(ctx.tree().root.live_tasks, 3);
+
+        drop(dropped);
+        tokio::task::yield_now().await;
+        assert_eq!(ctx.tree().root.live_tasks, 2);
+        assert_eq!(awaited.await.unwrap(), Some(()));
+        assert_eq!(ctx.tree().root.live_tasks, 1);
+        // a detached task keeps running after its handle is gone
+        drop(detached);
+        ctx.when_all_tasks_done().await;
+        assert_eq!(ctx.inner.active_tasks.load(std::sync::atomic::Ordering::SeqCst), 0);
+        assert!(!ctx.is_cancelled());
+    }
+}
@@ -0,0 +1,82 @@
+use std::any::Any;
+use std::panic::Location;
+
+use crate::result::panic_message;
+use crate::tree::SpawnLocation;
+use crate::{CancellationCause, ContextInner};
+
+/// What happens when a task of a context panics, set with `ContextBuilder::panic_policy`.
+///
+/// The panic always reaches the `JoinHandle` of the task as well, whatever the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanicPolicy {
+    /// Only the task dies. The default.
+    #[default]
+    Ignore,
+    /// Report the panic on stderr, or as a `tracing` error event with the `tracing` feature
+    Log,
+    /// Cancel the context with `CancellationCause::Panic`
+    CancelContext,
+    /// Apply the policy of the parent context, walking up until a context with another policy is found. A root
+    /// context with this policy logs the panic.
+    EscalateToParent,
+}
+
+impl ContextInner {
+    /// Apply the panic policy to a task of this context that panicked
+    pub(crate) fn handle_panic(&self, task_id: u64, location: &'static Location<'static>, payload: &(dyn Any + Send)) {
+        let mut handler = self;
+        while handler.panic_policy == PanicPolicy::EscalateToParent {
+            match &handler.parent {
+                Some(parent) => handler = parent,
+                None => break,
+            }
+        }
+        match handler.panic_policy {
+            PanicPolicy::Ignore => {}
+            PanicPolicy::Log | PanicPolicy::EscalateToParent => log_panic(self, task_id, location, payload),
+            PanicPolicy::CancelContext => handler.cancel(CancellationCause::Panic {
+                context: self.id,
+                task_id,
+                spawned_at: location.into(),
+            }),
+        }
+    }
+}
+
+fn log_panic(context: &ContextInner, task_id: u64, location: &'static Location<'static>, payload: &(dyn Any + Send)) {
+    let spawned_at = SpawnLocation::from(location);
+    let message = panic_message(payload);
+    #[cfg(feature = "tracing")]
+    tracing::error!(context = %context.id, task_id, %spawned_at, "task panicked: {}", message);
+    #[cfg(not(feature = "tracing"))]
+    eprintln!("task {} of {} spawned at {} panicked: {}", task_id, context.id, spawned_at, message);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CancellationCause, Context, PanicPolicy};
+
+    #[tokio::test]
+    async fn panics_escalate_to_the_handling_context() {
+        let mut subsystem = Context::builder().panic_policy(PanicPolicy::CancelContext).build();
+        let mut worker = Context::builder().panic_policy(PanicPolicy::EscalateToParent).build_child(&mut subsystem);
+        let connection = subsystem.new_child_context();
+        let task = worker.spawn(async { panic!("compaction failed") });
+        let line = line!() - 1;
+        assert!(task.await.unwrap_err().is_panic());
+        match subsystem.cancellation_cause() {
+            Some(CancellationCause::Panic { context, spawned_at, .. }) => {
+                assert_eq!(context, worker.id());
+                assert_eq!(spawned_at.line, line);
+            }
+            cause => panic!("unexpected cause {:?}", cause),
+        }
+        assert_eq!(connection.cancellation_cause(), Some(CancellationCause::Parent));
+
+        // the default policy keeps the panic to the task
+        let mut root = Context::new();
+        assert!(root.spawn(async { panic!("ignored") }).await.unwrap_err().is_panic());
+        assert!(!root.is_cancelled());
+    }
+}
@@ -0,0 +1,185 @@
+use std::future::Future;
+use std::panic::Location;
+use tokio::task::JoinSet;
+
+use crate::Context;
+
+impl Context {
+    /// Run `f` on every item of `iter` concurrently, each in its own task, and wait for all of them.
+    ///
+    /// Resolves to None if the context is cancelled before every item was processed, and to `Some(())` otherwise.
+    /// If a task panics, the others still run to completion before the first panic is resumed here.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example() {
+    /// let mut ctx = Context::new();
+    /// let urls = vec!["a", "b", "c"];
+    /// let done = ctx.parallel_for(urls, |url| async move {
+    ///     println!("fetching {}", url);
+    /// }).await;
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn parallel_for<I, F, Fut>(&mut self, iter: I, mut f: F) -> impl Future<Output = Option<()>> + Send + 'static
+    where
+        I: IntoIterator,
+        F: FnMut(I::Item) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut tasks = JoinSet::new();
+        let mut all_spawned = true;
+        for item in iter {
+            match self.inner.task_future(None, f(item), None) {
+                Ok(task) => {
+                    tasks.spawn_on(task, &self.inner.runtime());
+                }
+                Err(_) => {
+                    all_spawned = false;
+                    break;
+                }
+            }
+        }
+        async move {
+            let mut completed = all_spawned;
+            let mut panic = None;
+            while let Some(joined) = tasks.join_next().await {
+                match joined {
+                    Ok(Some(())) => {}
+                    Ok(None) => completed = false,
+                    Err(e) if e.is_panic() => {
+                        panic.get_or_insert(e.into_panic());
+                    }
+                    Err(_) => completed = false,
+                }
+            }
+            if let Some(panic) = panic {
+                std::panic::resume_unwind(panic);
+            }
+            completed.then_some(())
+        }
+    }
+
+    /// Run `mapper` on every item of `inputs` with at most `limit` tasks at a time, and collect the results in input
+    /// order.
+    ///
+    /// Items are taken from `inputs` only as tasks finish, so large inputs are not turned into tasks all at once.
+    /// Resolves to None if the context is cancelled before every item was processed. A panic in `mapper`'s future
+    /// aborts the remaining items and is resumed here.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example() {
+    /// let mut ctx = Context::new();
+    /// let sizes = ctx.spawn_map_results(vec!["a", "b", "c"], |url| async move { url.len() }, 2).await;
+    /// assert_eq!(sizes, Some(vec![1, 1, 1]));
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn spawn_map_results<I, F, Fut, U>(&mut self, inputs: I, mut mapper: F, limit: usize) -> impl Future<Output = Option<Vec<U>>> + Send + 'static
+    where
+        I: IntoIterator,
+        I::IntoIter: Send + 'static,
+        F: FnMut(I::Item) -> Fut + Send + 'static,
+        Fut: Future<Output = U> + Send + 'static,
+        U: Send + 'static,
+    {
+        let inner = self.inner.clone();
+        let location = Location::caller();
+        let mut inputs = inputs.into_iter().enumerate();
+        async move {
+            let mut results = Vec::new();
+            let mut tasks = JoinSet::new();
+            loop {
+                while tasks.len() < limit.max(1) {
+                    let Some((index, item)) = inputs.next() else {
+                        break;
+                    };
+                    let task = inner.task_future_at(None, mapper(item), location, Default::default()).ok()?;
+                    results.push(None);
+                    tasks.spawn_on(async move { (index, task.await) }, &inner.runtime());
+                }
+                match tasks.join_next().await {
+                    Some(Ok((index, Some(output)))) => results[index] = Some(output),
+                    Some(Ok((_, None))) => return None,
+                    Some(Err(e)) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+                    Some(Err(_)) => return None,
+                    None => break,
+                }
+            }
+            results.into_iter().collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Context;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn parallel_for_processes_every_item() {
+        let mut ctx = Context::new();
+        let sum = Arc::new(AtomicUsize::new(0));
+        let done = ctx.parallel_for(1..=10, |i| {
+            let sum = sum.clone();
+            async move {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                sum.fetch_add(i, Ordering::SeqCst);
+            }
+        });
+        assert_eq!(done.await, Some(()));
+        assert_eq!(sum.load(Ordering::SeqCst), 55);
+
+        let mut child = ctx.new_child_context();
+        let cancelled = child.parallel_for(0..3, |_| tokio::time::sleep(Duration::from_secs(10)));
+        drop(child);
+        assert_eq!(cancelled.await, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn map_results_keep_input_order_within_limit() {
+        let mut ctx = Context::new();
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let (r, p) = (running.clone(), peak.clone());
+        let results = ctx.spawn_map_results(0..10u64, move |i| {
+            let (running, peak) = (r.clone(), p.clone());
+            async move {
+                peak.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_secs(10 - i)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                i * 2
+            }
+        }, 3);
+        assert_eq!(results.await, Some((0..10).map(|i| i * 2).collect()));
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+
+        let mut child = ctx.new_child_context();
+        let cancelled = child.spawn_map_results(0..3, |_| tokio::time::sleep(Duration::from_secs(10)), 2);
+        drop(child);
+        assert_eq!(cancelled.await, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn panics_surface_after_other_items_complete() {
+        let mut ctx = Context::new();
+        let finished = Arc::new(AtomicUsize::new(0));
+        let counter = finished.clone();
+        let run = ctx.parallel_for(0..3, move |i| {
+            let counter = counter.clone();
+            async move {
+                if i == 0 {
+                    panic!("item failed");
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        let error = tokio::spawn(run).await.unwrap_err();
+        assert!(error.is_panic());
+        assert_eq!(finished.load(Ordering::SeqCst), 2);
+    }
+}
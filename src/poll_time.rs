@@ -0,0 +1,110 @@
+//! Time spent polling tasks, with the `poll-time` feature.
+//!
+//! Every poll of a task is timed with two `Instant::now()` calls and recorded with relaxed atomic adds on the task
+//! and on its context. Time spent polling is close to the CPU time used by the task, unless the task blocks the
+//! thread inside a poll.
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::Context;
+
+/// Number of polls and total time spent in them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PollStats {
+    pub polls: u64,
+    pub busy: Duration,
+}
+
+#[derive(Default)]
+pub(crate) struct PollCounters {
+    polls: AtomicU64,
+    busy_ns: AtomicU64,
+}
+
+impl PollCounters {
+    pub(crate) fn record(&self, busy: Duration) {
+        self.polls.fetch_add(1, Ordering::Relaxed);
+        self.busy_ns.fetch_add(busy.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn get(&self) -> PollStats {
+        PollStats {
+            polls: self.polls.load(Ordering::Relaxed),
+            busy: Duration::from_nanos(self.busy_ns.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Poll statistics of a task spawned with `Context::spawn_measured`, readable while it runs and after it finished
+#[derive(Clone, Default)]
+pub struct TaskPollStats(Arc<PollCounters>);
+
+impl TaskPollStats {
+    pub fn get(&self) -> PollStats {
+        self.0.get()
+    }
+}
+
+/// `future`, reporting the duration of every poll to `record`
+pub(crate) fn measure<F: Future>(future: F, record: impl Fn(Duration)) -> impl Future<Output = F::Output> {
+    let mut future = Box::pin(future);
+    std::future::poll_fn(move |cx| {
+        let started = Instant::now();
+        let poll = future.as_mut().poll(cx);
+        record(started.elapsed());
+        poll
+    })
+}
+
+impl Context {
+    /// Spawn a task and return its poll statistics along with its handle
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example() {
+    /// let mut ctx = Context::new();
+    /// let (handle, stats) = ctx.spawn_measured(async move { /* do your work here */ });
+    /// handle.await.unwrap();
+    /// println!("busy for {:?} over {} polls", stats.get().busy, stats.get().polls);
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn spawn_measured<T>(&mut self, future: T) -> (tokio::task::JoinHandle<Option<T::Output>>, TaskPollStats)
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        let stats = TaskPollStats::default();
+        let counters = stats.0.clone();
+        (self.spawn(measure(future, move |busy| counters.record(busy))), stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn polls_are_counted_per_task_and_context() {
+        let mut ctx = Context::new();
+        let (handle, stats) = ctx.spawn_measured(async {
+            for _ in 0..3 {
+                tokio::task::yield_now().await;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        });
+        let idle = ctx.spawn_named("idle", std::future::pending::<()>());
+        handle.await.unwrap();
+        let task = stats.get();
+        assert_eq!(task.polls, 4);
+        assert!(task.busy >= Duration::from_millis(20));
+        let context = ctx.stats().poll;
+        assert!(context.polls >= 5 && context.busy >= task.busy);
+        tokio::task::yield_now().await;
+        assert_eq!(ctx.tree().root.tasks[0].poll_count, 1);
+        idle.abort();
+    }
+}
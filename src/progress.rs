@@ -0,0 +1,244 @@
+use std::future::Future;
+use std::panic::Location;
+use std::sync::Arc;
+use std::task::Poll;
+use tokio::sync::{mpsc, watch};
+
+use crate::{Context, TaskOptions};
+
+/// Longest state string kept by `Progress::set_state`, in bytes. Longer strings are truncated.
+pub const MAX_PROGRESS_STATE_LEN: usize = 64;
+
+pub(crate) type ProgressReceiver = watch::Receiver<ProgressValue>;
+
+/// Latest progress published by a task spawned with `Context::spawn_monitored`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProgressValue {
+    /// A counter such as items processed or bytes copied
+    pub value: u64,
+    /// A short description of what the task is doing
+    pub state: Option<Arc<str>>,
+}
+
+/// Handle given to a task spawned with `Context::spawn_monitored`, used to publish its progress
+#[derive(Clone)]
+pub struct Progress {
+    tx: Arc<watch::Sender<ProgressValue>>,
+}
+
+impl Progress {
+    /// Publish the counter, keeping the state
+    pub fn set(&self, value: u64) {
+        self.tx.send_modify(|progress| progress.value = value);
+    }
+
+    /// Publish the state, keeping the counter. Truncated to `MAX_PROGRESS_STATE_LEN` bytes.
+    pub fn set_state(&self, state: &str) {
+        let mut end = state.len().min(MAX_PROGRESS_STATE_LEN);
+        while !state.is_char_boundary(end) {
+            end -= 1;
+        }
+        let state = Arc::from(&state[..end]);
+        self.tx.send_modify(|progress| progress.state = Some(state));
+    }
+}
+
+/// Handle given to a task spawned with `Context::spawn_with_progress`, used to report how far the task got.
+///
+/// Reported values are clamped to `[0.0, 1.0]` and NaN is ignored. Reports made after the progress callback has
+/// stopped (for example because the context was cancelled) are silently discarded.
+#[derive(Clone)]
+pub struct ProgressSender {
+    tx: mpsc::UnboundedSender<f64>,
+}
+
+tokio::task_local! {
+    static CURRENT: ProgressSender;
+}
+
+impl ProgressSender {
+    /// Report the fraction of work done, between 0.0 and 1.0
+    pub fn report(&self, fraction: f64) {
+        if !fraction.is_nan() {
+            let _ = self.tx.send(fraction.clamp(0.0, 1.0));
+        }
+    }
+
+    /// The sender of the task spawned with `spawn_with_progress` or `spawn_with_progress_fn` this is called from
+    pub fn current() -> Option<ProgressSender> {
+        CURRENT.try_with(ProgressSender::clone).ok()
+    }
+}
+
+/// Run `future` with `progress` called for every value reported to `rx`. The callback runs inside the task, so it
+/// needs no task of its own.
+async fn with_progress<T: Future>(future: T, rx: mpsc::UnboundedReceiver<f64>, mut progress: impl FnMut(f64)) -> T::Output {
+    let mut future = std::pin::pin!(future);
+    let mut rx = Some(rx);
+    std::future::poll_fn(move |cx| {
+        let poll = future.as_mut().poll(cx);
+        // also registers the task for reports made by other tasks while this one waits
+        while let Some(receiver) = rx.as_mut() {
+            match receiver.poll_recv(cx) {
+                Poll::Ready(Some(fraction)) => progress(fraction),
+                Poll::Ready(None) => rx = None,
+                Poll::Pending => break,
+            }
+        }
+        poll
+    })
+    .await
+}
+
+impl Context {
+    /// Spawn a task that reports its progress back to the caller.
+    ///
+    /// The future reports through `ProgressSender::current()`. Every value it reports is passed to `progress`, which
+    /// runs inside the task between polls. The progress stream ends when the task finishes or when the context is
+    /// cancelled.
+    /// ```rust, no_run
+    /// use tokio_tree_context::{Context, ProgressSender};
+    ///
+    /// let mut ctx = Context::new();
+    /// ctx.spawn_with_progress(async move {
+    ///     let progress = ProgressSender::current().unwrap();
+    ///     for i in 1..=10 {
+    ///         // copy a chunk here
+    ///         progress.report(i as f64 / 10.0);
+    ///     }
+    /// }, |fraction| println!("{:.0}% done", fraction * 100.0));
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_progress<T, P>(&mut self, future: T, progress: P) -> tokio::task::JoinHandle<Option<T::Output>>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+        P: FnMut(f64) + Send + 'static,
+    {
+        self.spawn_with_progress_fn(|_| future, progress)
+    }
+
+    /// Same as `spawn_with_progress`, with the `ProgressSender` passed to `factory`, which returns the future to run
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut ctx = Context::new();
+    /// ctx.spawn_with_progress_fn(|progress| async move {
+    ///     for i in 1..=10 {
+    ///         progress.report(i as f64 / 10.0);
+    ///     }
+    /// }, |fraction| println!("{:.0}% done", fraction * 100.0));
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_progress_fn<F, Fut, P>(&mut self, factory: F, progress: P) -> tokio::task::JoinHandle<Option<Fut::Output>>
+    where
+        F: FnOnce(ProgressSender) -> Fut,
+        Fut: Future + Send + 'static,
+        Fut::Output: Send + 'static,
+        P: FnMut(f64) + Send + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let sender = ProgressSender { tx };
+        let future = CURRENT.scope(sender.clone(), factory(sender));
+        self.spawn(with_progress(future, rx, progress))
+    }
+
+    /// Spawn a task that publishes a progress value observers can read at any time.
+    ///
+    /// `factory` is given a `Progress` and returns the future to run. The latest value is returned as a watch
+    /// receiver, which keeps the final value after the task completed or was cancelled, and is shown next to the task
+    /// in `tree()` while it runs.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut ctx = Context::new();
+    /// let (_handle, progress) = ctx.spawn_monitored(|progress| async move {
+    ///     progress.set_state("copying");
+    ///     for chunk in 1..=10 {
+    ///         progress.set(chunk);
+    ///     }
+    /// });
+    /// println!("copied {} chunks", progress.borrow().value);
+    /// ```
+    #[track_caller]
+    pub fn spawn_monitored<F, Fut>(&mut self, factory: F) -> (tokio::task::JoinHandle<Option<Fut::Output>>, watch::Receiver<ProgressValue>)
+    where
+        F: FnOnce(Progress) -> Fut,
+        Fut: Future + Send + 'static,
+        Fut::Output: Send + 'static,
+    {
+        let (tx, rx) = watch::channel(ProgressValue::default());
+        let future = factory(Progress { tx: Arc::new(tx) });
+        let options = TaskOptions { progress: Some(rx.clone()), ..Default::default() };
+        let handle = self.inner.spawn_or_none(self.inner.task_future_at(None, future, Location::caller(), options));
+        (handle, rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn progress_is_reported_and_clamped() {
+        let mut ctx = Context::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let handle = ctx.spawn_with_progress_fn(|progress| async move {
+            progress.report(0.5);
+            progress.report(f64::NAN);
+            progress.report(2.0);
+            7
+        }, move |fraction| {
+            let _ = tx.send(fraction);
+        });
+        assert_eq!(handle.await.unwrap(), Some(7));
+        assert_eq!(rx.recv().await, Some(0.5));
+        assert_eq!(rx.recv().await, Some(1.0));
+        // the sender is gone once the task completed, so the progress stream ends
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn progress_runs_inside_the_task() {
+        let mut ctx = Context::builder().capacity(1).build();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let handle = ctx.spawn_with_progress(async {
+            let progress = ProgressSender::current().unwrap();
+            let mut child = tokio::spawn(async move { progress.report(0.25) });
+            (&mut child).await.unwrap();
+            tokio::task::yield_now().await;
+            ProgressSender::current().unwrap().report(1.0);
+        }, move |fraction| {
+            let _ = tx.send(fraction);
+        });
+        // a single unit of capacity is enough, the callback does not take one
+        assert_eq!(ctx.stats().live_tasks, 1);
+        assert_eq!(handle.await.unwrap(), Some(()));
+        assert_eq!(rx.recv().await, Some(0.25));
+        assert_eq!(rx.recv().await, Some(1.0));
+        assert_eq!(rx.recv().await, None);
+        assert!(ProgressSender::current().is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn monitored_progress_survives_cancellation() {
+        let mut ctx = Context::new();
+        let (handle, progress) = ctx.spawn_monitored(|progress| async move {
+            progress.set_state(&"x".repeat(100));
+            for i in 1..=3 {
+                progress.set(i);
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            }
+            std::future::pending::<()>().await
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(2500)).await;
+        let tree = ctx.tree();
+        assert_eq!(tree.root.tasks[0].progress.as_ref().unwrap().value, 3);
+        ctx.cancel();
+        assert_eq!(handle.await.unwrap(), None);
+        let last = progress.borrow().clone();
+        assert_eq!(last.value, 3);
+        assert_eq!(last.state.unwrap().len(), MAX_PROGRESS_STATE_LEN);
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::{Mutex, Weak};
+
+use crate::{CancellationCause, Context, ContextInner};
+
+/// Contexts to cancel once a task of the context completes, see `Context::cancel_sibling_on_first_completion`
+#[derive(Default)]
+pub(crate) struct CompletionCancels {
+    siblings: Mutex<Vec<Weak<ContextInner>>>,
+}
+
+impl CompletionCancels {
+    /// Called when a task ran to completion, as opposed to being cancelled or timing out
+    pub(crate) fn completed(&self) {
+        let siblings = std::mem::take(&mut *self.siblings.lock().unwrap());
+        for sibling in siblings.iter().filter_map(Weak::upgrade) {
+            sibling.cancel(CancellationCause::Explicit);
+        }
+    }
+}
+
+impl Context {
+    /// Cancel `sibling` as soon as any task of this context runs to completion. Tasks that are cancelled or time out
+    /// do not count.
+    ///
+    /// Races a primary path against a fallback, or stops a watchdog once the work it guards is done.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(mut root: Context) {
+    /// let mut primary = root.new_child_context();
+    /// let mut fallback = root.new_child_context();
+    /// primary.cancel_sibling_on_first_completion(&mut fallback);
+    /// primary.spawn(async { /* ask the primary replica */ });
+    /// fallback.spawn(async {
+    ///     tokio::time::sleep(Duration::from_millis(50)).await;
+    ///     /* ask the secondary replica */
+    /// });
+    /// # }
+    /// ```
+    pub fn cancel_sibling_on_first_completion(&self, sibling: &mut Context) {
+        self.inner.completion_cancels.siblings.lock().unwrap().push(std::sync::Arc::downgrade(&sibling.inner));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn sibling_is_cancelled_when_a_task_completes() {
+        let mut root = Context::new();
+        let mut primary = root.new_child_context();
+        let mut fallback = root.new_child_context();
+        primary.cancel_sibling_on_first_completion(&mut fallback);
+
+        let timed_out = primary.spawn_with_timeout(std::future::pending::<()>(), Some(Duration::from_secs(1)));
+        assert_eq!(timed_out.await.unwrap(), None);
+        assert!(!fallback.is_cancelled());
+
+        let slow = fallback.spawn(tokio::time::sleep(Duration::from_secs(5)));
+        assert_eq!(primary.spawn(tokio::time::sleep(Duration::from_secs(2))).await.unwrap(), Some(()));
+        assert!(fallback.is_cancelled());
+        assert_eq!(slow.await.unwrap(), None);
+        assert!(!root.is_cancelled());
+    }
+}
@@ -0,0 +1,125 @@
+use std::panic::Location;
+use std::sync::Arc;
+
+use crate::epoch::OutcomeSlot;
+use crate::work::WorkGuard;
+use crate::{Context, ContextInner};
+
+/// A synchronous region entered with `Context::enter_sync`. Dropping it ends the region.
+///
+/// Like a `WorkGuard`, the region counts as a live task of its context until it ends: drains wait for it and a
+/// leaked guard is reported by name when the drain times out.
+pub struct SyncRegionGuard {
+    inner: Arc<ContextInner>,
+    work: WorkGuard,
+    #[cfg(feature = "metrics")]
+    metrics: crate::task_metrics::TaskMetrics,
+}
+
+impl SyncRegionGuard {
+    /// Whether the context was cancelled, so the region can bail out early. Cheap enough to check in a loop.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.is_cancelled()
+    }
+}
+
+impl Drop for SyncRegionGuard {
+    fn drop(&mut self) {
+        let cancelled = self.is_cancelled();
+        if let Some(guard) = &self.work.guard {
+            guard.outcome.set(if cancelled { OutcomeSlot::CANCELLED } else { OutcomeSlot::COMPLETED });
+            // the region ran on its thread for all of its duration, which counts as one long poll
+            #[cfg(feature = "poll-time")]
+            {
+                let busy = guard.spawned_at.elapsed();
+                guard.poll_time.record(busy);
+                guard.inner.poll_time.record(busy);
+            }
+        }
+        #[cfg(feature = "metrics")]
+        if !cancelled {
+            self.metrics.completed();
+        }
+    }
+}
+
+impl Context {
+    /// Attribute a synchronous region, such as template rendering or compression on a blocking thread, to this
+    /// context until the returned guard is dropped. The guard can be moved to and dropped on any thread.
+    ///
+    /// While the region runs it counts as a live task named `name`: it shows in `tree()`, uses the time budget of
+    /// the context and is waited for by `cancel_and_wait`. A region that runs longer than the stall threshold is
+    /// reported as stalled, since it is never polled. Once it ends, it is counted in `stats_epoch` and its duration in
+    /// `task_stats_by_name`, as cancelled if the context was cancelled by then. With the `poll-time` feature its
+    /// duration counts as a single poll, and with the `metrics` feature it is reported like a task labelled `name`.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # fn compress(_: &[u8]) -> bool { true }
+    /// # async fn example(ctx: Context, chunks: Vec<Vec<u8>>) {
+    /// let region = ctx.enter_sync("compress");
+    /// tokio::task::spawn_blocking(move || {
+    ///     for chunk in &chunks {
+    ///         if region.is_cancelled() {
+    ///             return;
+    ///         }
+    ///         compress(chunk);
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn enter_sync(&self, name: impl Into<String>) -> SyncRegionGuard {
+        let name: Arc<str> = Arc::from(name.into());
+        let work = self.inner.register_work(name.clone(), Location::caller());
+        if let Some(guard) = &work.guard {
+            self.inner.task_counters.track(&guard.outcome);
+            self.inner.name_stats.task_spawned(&name);
+        }
+        SyncRegionGuard {
+            #[cfg(feature = "metrics")]
+            metrics: crate::task_metrics::TaskMetrics::spawned(&self.inner, Some(name)),
+            inner: self.inner.clone(),
+            work,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn sync_regions_count_as_live_work() {
+        let mut root = Context::new();
+        let child = root.new_child_context();
+        drop(child.enter_sync("compress"));
+        let region = child.enter_sync("render");
+        assert_eq!(child.stats().live_tasks, 1);
+        let worker = std::thread::spawn(move || {
+            while !region.is_cancelled() {
+                std::thread::yield_now();
+            }
+        });
+        assert_eq!(root.cancel_and_wait(Duration::from_secs(5)).await, Ok(()));
+        worker.join().unwrap();
+
+        let stats = child.task_stats_by_name();
+        assert_eq!(stats["compress"].complete_count, 1);
+        assert_eq!(stats["render"].cancel_count, 1);
+        assert_eq!(child.stats_epoch().counts.spawned, 2);
+    }
+
+    #[cfg(feature = "poll-time")]
+    #[test]
+    fn sync_regions_count_as_one_poll() {
+        let ctx = Context::new();
+        let region = ctx.enter_sync("hash");
+        std::thread::sleep(Duration::from_millis(20));
+        drop(region);
+        let poll = ctx.stats().poll;
+        assert_eq!(poll.polls, 1);
+        assert!(poll.busy >= Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::{Context, ContextInner};
+
+/// How long tasks get to react to a cancellation before the resources of their context are closed, unless changed
+/// with `Context::with_resource_grace`
+pub const DEFAULT_RESOURCE_GRACE: Duration = Duration::from_secs(5);
+
+/// Something a stuck task may be blocked on, that can be closed from the outside to unblock it, see
+/// `Context::register_resource`
+pub trait ScopedResource: Send + Sync + 'static {
+    /// Close the resource, so whoever is blocked on it returns, usually with an error
+    fn force_close(&self) -> impl Future<Output = ()> + Send;
+}
+
+impl<R: ScopedResource> ScopedResource for Arc<R> {
+    async fn force_close(&self) {
+        (**self).force_close().await
+    }
+}
+
+impl ScopedResource for tokio::sync::Semaphore {
+    async fn force_close(&self) {
+        self.close();
+    }
+}
+
+impl ScopedResource for tokio::task::AbortHandle {
+    async fn force_close(&self) {
+        self.abort();
+    }
+}
+
+/// A socket shared with the task using it, for example one cloned from a `tokio::net::TcpStream` with
+/// `Context::register_tcp_stream`. Closing shuts down both directions.
+impl ScopedResource for std::net::TcpStream {
+    async fn force_close(&self) {
+        let _ = self.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+/// Dropping the value closes it, for example a file handle or a connection that closes on drop
+impl<T: Send + 'static> ScopedResource for Mutex<Option<T>> {
+    async fn force_close(&self) {
+        let taken = self.lock().unwrap().take();
+        drop(taken);
+    }
+}
+
+type CloseFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// `ScopedResource` that can be stored as a trait object
+trait ErasedResource: Send + Sync {
+    fn close(&self) -> CloseFuture<'_>;
+}
+
+impl<R: ScopedResource> ErasedResource for R {
+    fn close(&self) -> CloseFuture<'_> {
+        Box::pin(self.force_close())
+    }
+}
+
+/// Resources registered with a context, created on first use
+pub(crate) struct Resources {
+    grace: Mutex<Duration>,
+    next_id: AtomicU64,
+    registered: Mutex<HashMap<u64, Arc<dyn ErasedResource>>>,
+    /// Set once the task closing the resources after a cancellation was spawned
+    watched: AtomicBool,
+}
+
+impl Default for Resources {
+    fn default() -> Self {
+        Resources {
+            grace: Mutex::new(DEFAULT_RESOURCE_GRACE),
+            next_id: AtomicU64::new(0),
+            registered: Default::default(),
+            watched: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Resources {
+    /// Once the context is cancelled and the grace period passed, close the resources if tasks are still running
+    fn watch(self: Arc<Self>, inner: &Arc<ContextInner>) {
+        if self.watched.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let cancelled = inner.cancelled();
+        let context = Arc::downgrade(inner);
+        inner.spawn(async move {
+            cancelled.await;
+            let grace = *self.grace.lock().unwrap();
+            tokio::time::sleep(grace).await;
+            let Some(inner) = context.upgrade() else {
+                return;
+            };
+            if inner.active_tasks.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            let registered: Vec<_> = self.registered.lock().unwrap().drain().map(|(_, resource)| resource).collect();
+            for resource in registered {
+                resource.close().await;
+            }
+        });
+    }
+}
+
+/// A resource registered with `Context::register_resource`. Dropping it deregisters the resource without closing it.
+pub struct ResourceGuard {
+    resources: Arc<Resources>,
+    id: u64,
+}
+
+impl Drop for ResourceGuard {
+    fn drop(&mut self) {
+        let removed = self.resources.registered.lock().unwrap().remove(&self.id);
+        drop(removed);
+    }
+}
+
+impl Context {
+    /// How long tasks get to stop after a cancellation before the resources registered with this context are closed.
+    /// `DEFAULT_RESOURCE_GRACE` if not set.
+    pub fn with_resource_grace(self, grace: Duration) -> Context {
+        *self.inner.resources().grace.lock().unwrap() = grace;
+        self
+    }
+
+    /// Register a resource that tasks of this context may be blocked on, such as a socket whose read only returns
+    /// once it is shut down.
+    ///
+    /// If tasks of the context are still running once it was cancelled and the grace period passed, every resource
+    /// still registered is closed with `ScopedResource::force_close`. A task that registers its resources and keeps
+    /// the guard until it returns therefore only has its own resources closed if it is one of those stuck.
+    ///
+    /// Must be called within a tokio runtime.
+    /// ```rust, no_run
+    /// use std::sync::Arc;
+    /// use tokio::sync::Semaphore;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(mut ctx: Context) {
+    /// let permits = Arc::new(Semaphore::new(4));
+    /// let _guard = ctx.register_resource(permits.clone());
+    /// # }
+    /// ```
+    pub fn register_resource(&self, resource: impl ScopedResource) -> ResourceGuard {
+        let resources = self.inner.resources();
+        let id = resources.next_id.fetch_add(1, Ordering::SeqCst);
+        resources.registered.lock().unwrap().insert(id, Arc::new(resource));
+        resources.clone().watch(&self.inner);
+        ResourceGuard { resources, id }
+    }
+
+    /// Register a clone of the socket of `stream`, which is shut down like with `register_resource`
+    #[cfg(all(feature = "net", any(unix, windows)))]
+    pub fn register_tcp_stream(&self, stream: &tokio::net::TcpStream) -> std::io::Result<ResourceGuard> {
+        #[cfg(unix)]
+        let socket = std::os::fd::AsFd::as_fd(stream).try_clone_to_owned()?;
+        #[cfg(windows)]
+        let socket = std::os::windows::io::AsSocket::as_socket(stream).try_clone_to_owned()?;
+        Ok(self.register_resource(std::net::TcpStream::from(socket)))
+    }
+}
+
+impl ContextInner {
+    fn resources(&self) -> Arc<Resources> {
+        self.resources.get_or_init(Default::default).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Semaphore;
+    use tokio::time::Instant;
+
+    #[tokio::test(start_paused = true)]
+    async fn resources_are_closed_once_the_grace_period_passed() {
+        let ctx = Context::new().with_resource_grace(Duration::from_secs(2));
+        let stuck = Arc::new(Semaphore::new(0));
+        let scope = Arc::new(Semaphore::new(0));
+        let dropped = Arc::new(Semaphore::new(0));
+        let _stuck = ctx.register_resource(stuck.clone());
+        let _scope = ctx.register_resource(scope.clone());
+        drop(ctx.register_resource(dropped.clone()));
+        let work = ctx.register_work("blocked");
+        let start = Instant::now();
+        ctx.cancel();
+        assert!(stuck.acquire().await.is_err());
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+        assert!(scope.is_closed());
+        assert!(!dropped.is_closed());
+        drop(work);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn resources_stay_open_when_tasks_stop_in_time() {
+        let ctx = Context::new();
+        let permits = Arc::new(Semaphore::new(0));
+        let _guard = ctx.register_resource(permits.clone());
+        let work = ctx.register_work("responsive");
+        ctx.cancel();
+        drop(work);
+        tokio::time::sleep(DEFAULT_RESOURCE_GRACE * 2).await;
+        assert!(!permits.is_closed());
+    }
+
+    #[cfg(all(feature = "net", any(unix, windows)))]
+    #[tokio::test]
+    async fn tcp_streams_are_shut_down() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = tokio::net::TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        let ctx = Context::new().with_resource_grace(Duration::from_millis(10));
+        let _guard = ctx.register_tcp_stream(&client).unwrap();
+        let _work = ctx.register_work("reader");
+        ctx.cancel();
+        let mut buf = [0; 1];
+        let read = loop {
+            server.readable().await.unwrap();
+            match server.try_read(&mut buf) {
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+                read => break read.unwrap(),
+            }
+        };
+        assert_eq!(read, 0);
+        drop(client);
+    }
+}
@@ -0,0 +1,181 @@
+use std::any::Any;
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::Context;
+
+/// How a task run under a context ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskResult<T> {
+    /// The future ran to completion
+    Completed(T),
+    /// The context was cancelled before the future completed
+    Cancelled,
+    /// The deadline of the context was reached before the future completed
+    TimedOut,
+    /// The future panicked, with the panic message if it was a string
+    Panicked(String),
+}
+
+impl<T> TaskResult<T> {
+    pub fn is_completed(&self) -> bool {
+        matches!(self, TaskResult::Completed(_))
+    }
+
+    /// The output of the future, if it completed
+    pub fn completed(self) -> Option<T> {
+        match self {
+            TaskResult::Completed(output) => Some(output),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "panic with a non-string payload".to_string()
+    }
+}
+
+/// Turns a panic while polling the wrapped future into `Err(message)`
+pub(crate) struct CatchPanic<F>(pub(crate) Pin<Box<F>>);
+
+impl<F: Future> Future for CatchPanic<F> {
+    type Output = Result<F::Output, String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        match catch_unwind(AssertUnwindSafe(|| self.0.as_mut().poll(cx))) {
+            Ok(poll) => poll.map(Ok),
+            Err(payload) => Poll::Ready(Err(panic_message(&*payload))),
+        }
+    }
+}
+
+impl Context {
+    /// Run `future` inline until it completes or this context is cancelled, reporting how it ended.
+    ///
+    /// Unlike the spawn methods, the future is polled by the caller, so it does not have to be `Send`. A panic in
+    /// the future is caught and returned as `TaskResult::Panicked`. Cancellation after the deadline of the context
+    /// (or of an ancestor) is reported as `TaskResult::TimedOut`. With the `tracing` feature, the future is
+    /// instrumented with the current span.
+    /// ```rust, no_run
+    /// use tokio_tree_context::{Context, TaskResult};
+    ///
+    /// # async fn example() {
+    /// let ctx = Context::new();
+    /// match ctx.run_to_completion(async { 42 }).await {
+    ///     TaskResult::Completed(answer) => println!("{}", answer),
+    ///     other => println!("did not finish: {:?}", other),
+    /// }
+    /// # }
+    /// ```
+    pub fn run_to_completion<F: Future>(&self, future: F) -> impl Future<Output = TaskResult<F::Output>> {
+        #[cfg(feature = "tracing")]
+        let future = tracing::Instrument::in_current_span(future);
+        let inner = self.inner.clone();
+        let deadline = self.deadline();
+        // subscribe before checking the flag, so a cancel that happens in between is still received
+        let mut rx = inner.subscribe();
+        async move {
+            let cancelled = async {
+                if !inner.is_cancelled() {
+                    let _ = rx.recv().await;
+                }
+            };
+            tokio::select! {
+                biased;
+                _ = cancelled => match deadline {
+                    Some(deadline) if Instant::now() >= deadline => TaskResult::TimedOut,
+                    _ => TaskResult::Cancelled,
+                },
+                res = CatchPanic(Box::pin(future)) => match res {
+                    Ok(output) => TaskResult::Completed(output),
+                    Err(message) => TaskResult::Panicked(message),
+                },
+            }
+        }
+    }
+
+    /// `run_to_completion` for the future returned by `f`, giving up with `TaskResult::TimedOut` after `duration` or
+    /// at the deadline of the context, whichever comes first. No task or context is created.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::{Context, TaskResult};
+    ///
+    /// # async fn lookup() -> u64 { 0 }
+    /// # async fn example(ctx: Context) {
+    /// if let TaskResult::TimedOut = ctx.timeout_scope_async(Duration::from_millis(100), || lookup()).await {
+    ///     println!("lookup took too long");
+    /// }
+    /// # }
+    /// ```
+    pub fn timeout_scope_async<F, Fut>(&self, duration: Duration, f: F) -> impl Future<Output = TaskResult<Fut::Output>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future,
+    {
+        let timeout = Instant::now() + duration;
+        let until = self.deadline().map_or(timeout, |deadline| deadline.min(timeout));
+        let run = self.run_to_completion(f());
+        async move {
+            tokio::select! {
+                biased;
+                result = run => result,
+                _ = tokio::time::sleep_until(until) => TaskResult::TimedOut,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Context, TaskResult};
+    use std::rc::Rc;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[tokio::test(start_paused = true)]
+    async fn run_to_completion_reports_outcome() {
+        let ctx = Context::new();
+        // !Send futures are fine, they are not spawned
+        let local = Rc::new(5);
+        assert_eq!(ctx.run_to_completion(async move { *local }).await, TaskResult::Completed(5));
+        let panicked = ctx.run_to_completion(async { panic!("boom") }).await;
+        assert_eq!(panicked, TaskResult::<()>::Panicked("boom".to_string()));
+
+        let mut timed = Context::builder().deadline(tokio::time::Instant::now() + Duration::from_secs(1)).build();
+        let child = timed.new_child_context();
+        let result = child.run_to_completion(std::future::pending::<()>()).await;
+        assert_eq!(result, TaskResult::TimedOut);
+
+        let ctx = Context::new();
+        let result = ctx.run_to_completion(tokio::time::sleep(Duration::from_secs(5)));
+        ctx.cancel();
+        assert_eq!(result.await, TaskResult::Cancelled);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout_scope_is_bounded_by_the_deadline() {
+        let ctx = Context::new();
+        let quick = ctx.timeout_scope_async(Duration::from_secs(2), || tokio::time::sleep(Duration::from_secs(1)));
+        assert_eq!(quick.await, TaskResult::Completed(()));
+        let start = Instant::now();
+        let slow = ctx.timeout_scope_async(Duration::from_secs(2), std::future::pending::<()>);
+        assert_eq!(slow.await, TaskResult::TimedOut);
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+
+        let bounded = Context::builder().timeout(Duration::from_secs(1)).build();
+        let start = Instant::now();
+        let clamped = bounded.timeout_scope_async(Duration::from_secs(10), std::future::pending::<()>);
+        assert_eq!(clamped.await, TaskResult::TimedOut);
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,124 @@
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::Context;
+
+impl Context {
+    /// Spawn a task that runs the future made by `factory`, and runs a new one right away as long as it fails, at
+    /// most `attempts` times in total.
+    ///
+    /// Resolves to the first `Ok`, or to the last `Err` once every attempt failed. Resolves to None if the context is
+    /// cancelled during any attempt. At least one attempt is made, even if `attempts` is 0.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn read_cached(key: &str) -> Result<String, std::io::Error> { Ok(key.to_string()) }
+    /// # async fn example() {
+    /// let mut ctx = Context::new();
+    /// let value = ctx.spawn_retry(3, || read_cached("user:42")).await;
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn spawn_retry<F, Fut, T, E>(&mut self, attempts: u32, factory: F) -> tokio::task::JoinHandle<Option<Result<T, E>>>
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, E>> + Send,
+        T: Send + 'static,
+        E: Send + 'static,
+    {
+        self.spawn(async move {
+            let mut attempt = 1;
+            loop {
+                match factory().await {
+                    Err(_) if attempt < attempts => attempt += 1,
+                    result => return result,
+                }
+            }
+        })
+    }
+
+    /// Spawn a task that runs the future made by `factory` again and again until it resolves to `Some`.
+    ///
+    /// There is no attempt limit: the task only stops with the value or with the context, when it resolves to None.
+    /// With `min_interval`, attempts start at least that long after each other.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn try_connect() -> Option<std::net::TcpStream> { None }
+    /// # async fn example() {
+    /// let mut ctx = Context::new();
+    /// let connection = ctx.spawn_until_success(try_connect, Some(Duration::from_secs(1))).await;
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn spawn_until_success<F, Fut, T>(&mut self, factory: F, min_interval: Option<Duration>) -> tokio::task::JoinHandle<Option<T>>
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Option<T>> + Send,
+        T: Send + 'static,
+    {
+        self.spawn(async move {
+            loop {
+                let started = Instant::now();
+                if let Some(output) = factory().await {
+                    return output;
+                }
+                match min_interval {
+                    Some(interval) => tokio::time::sleep_until(started + interval).await,
+                    // an attempt that fails right away must not keep the cancellation from being noticed
+                    None => tokio::task::yield_now().await,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_until_success_or_out_of_attempts() {
+        let mut ctx = Context::new();
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let flaky = ctx.spawn_retry(5, move || {
+            let call = counter.fetch_add(1, Ordering::SeqCst);
+            async move { if call < 2 { Err(call) } else { Ok(call) } }
+        });
+        assert_eq!(flaky.await.unwrap(), Some(Ok(2)));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let broken = ctx.spawn_retry(2, || async { Err::<(), _>("down") });
+        assert_eq!(broken.await.unwrap(), Some(Err("down")));
+
+        let mut child = ctx.new_child_context();
+        let hanging = child.spawn_retry(2, std::future::pending::<Result<(), ()>>);
+        drop(child);
+        assert_eq!(hanging.await.unwrap(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keeps_trying_until_some_or_cancelled() {
+        let mut ctx = Context::new();
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let start = Instant::now();
+        let eventually = ctx.spawn_until_success(move || {
+            let call = counter.fetch_add(1, Ordering::SeqCst);
+            async move { (call == 3).then_some(call) }
+        }, Some(Duration::from_secs(1)));
+        assert_eq!(eventually.await.unwrap(), Some(3));
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+
+        let mut child = ctx.new_child_context();
+        let never = child.spawn_until_success(|| async { None::<()> }, Some(Duration::from_secs(1)));
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        drop(child);
+        assert_eq!(never.await.unwrap(), None);
+    }
+}
@@ -0,0 +1,111 @@
+use std::future::Future;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+
+use crate::{Context, ContextBuilder, ContextInner};
+
+impl Context {
+    /// Create a root context that owns a runtime built from `builder`, driven by a thread of its own.
+    ///
+    /// Tasks of the context and of its descendants run on that runtime, so a plugin given such a context cannot starve
+    /// the host runtime. Once the context is cancelled, its tasks get `shutdown_timeout` to finish, after which the
+    /// runtime is shut down, waiting up to `shutdown_timeout` once more for blocking tasks. The context can be
+    /// created, used and dropped from sync code as well as from any runtime. IO and time drivers are enabled on
+    /// `builder`.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut plugin = Context::with_owned_runtime(&mut tokio::runtime::Builder::new_current_thread(), Duration::from_secs(5))
+    ///     .expect("plugin runtime");
+    /// plugin.spawn(async move { /* plugin work */ });
+    /// drop(plugin); // drains the plugin tasks, then shuts its runtime down
+    /// ```
+    pub fn with_owned_runtime(builder: &mut tokio::runtime::Builder, shutdown_timeout: Duration) -> std::io::Result<Context> {
+        let runtime = builder.enable_all().build()?;
+        let context = Context::create(
+            None,
+            ContextBuilder {
+                runtime: Some(runtime.handle().clone()),
+                ..Default::default()
+            },
+        );
+        let cancelled = context.inner.cancelled();
+        let inner = Arc::downgrade(&context.inner);
+        std::thread::Builder::new().name("tokio-tree-context-runtime".to_string()).spawn(move || {
+            // `block_on` has returned before the shutdown, so the runtime is never shut down from within itself
+            runtime.block_on(drain(inner, cancelled, shutdown_timeout));
+            runtime.shutdown_timeout(shutdown_timeout);
+        })?;
+        Ok(context)
+    }
+}
+
+/// Wait until the context is cancelled and its tasks and those of its descendants are done, or `timeout` passed
+async fn drain(inner: Weak<ContextInner>, cancelled: impl Future<Output = ()>, timeout: Duration) {
+    cancelled.await;
+    let Some(inner) = inner.upgrade() else {
+        return;
+    };
+    let contexts = inner.subtree();
+    drop(inner);
+    let drained = async {
+        for inner in contexts {
+            inner.tasks_done().await;
+        }
+    };
+    let _ = tokio::time::timeout(timeout, drained).await;
+}
+
+impl ContextInner {
+    /// Spawn onto the runtime owned by the context or an ancestor, or onto the current runtime
+    pub(crate) fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        match &self.runtime {
+            Some(runtime) => runtime.spawn(future),
+            None => tokio::spawn(future),
+        }
+    }
+
+    /// The runtime tasks of the context are spawned onto
+    pub(crate) fn runtime(&self) -> Handle {
+        self.runtime.clone().unwrap_or_else(Handle::current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn owned_runtime_runs_tasks_and_shuts_down_with_the_context() {
+        struct Dropped(mpsc::Sender<String>);
+        impl Drop for Dropped {
+            fn drop(&mut self) {
+                let _ = self.0.send("dropped".to_string());
+            }
+        }
+        let mut plugin = Context::with_owned_runtime(&mut tokio::runtime::Builder::new_current_thread(), Duration::from_secs(1)).unwrap();
+        let mut child = plugin.new_child_context();
+        let (tx, rx) = mpsc::channel();
+        let thread = tx.clone();
+        child.spawn(async move {
+            let _ = thread.send(std::thread::current().name().unwrap_or_default().to_string());
+            // not a task of the context, so it only goes away with the runtime
+            tokio::spawn(async move {
+                let _dropped = Dropped(tx);
+                std::future::pending::<()>().await
+            });
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok("tokio-tree-context-runtime".to_string()));
+        drop(child);
+        drop(plugin);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok("dropped".to_string()));
+    }
+}
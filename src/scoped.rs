@@ -0,0 +1,45 @@
+use std::future::Future;
+
+use crate::Context;
+
+impl Context {
+    /// Run `future` under this context without spawning it, so it may borrow from the caller.
+    ///
+    /// The future runs inline when the returned future is awaited, and stops like a spawned task: it resolves to None
+    /// if the context is cancelled first. It counts as a live task of the context while it runs.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(ctx: Context) {
+    /// let mut lines = Vec::new();
+    /// ctx.scoped_spawn(async {
+    ///     lines.push("borrowed from the caller");
+    /// }).await;
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn scoped_spawn<'a, T: Send + 'a>(&'a self, future: impl Future<Output = T> + Send + 'a) -> impl Future<Output = Option<T>> + 'a {
+        let task = self.inner.task_future(None, future, None);
+        async move { task.ok()?.await }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn scoped_spawn_borrows_and_stops_on_cancel() {
+        let mut root = Context::new();
+        let ctx = root.new_child_context();
+        let mut seen = Vec::new();
+        assert_eq!(ctx.scoped_spawn(async { seen.push(1); seen.len() }).await, Some(1));
+        assert_eq!(seen, vec![1]);
+
+        let waiting = ctx.scoped_spawn(std::future::pending::<()>());
+        drop(root);
+        assert_eq!(waiting.await, None);
+        assert_eq!(ctx.scoped_spawn(async { seen.push(2) }).await, None);
+        assert_eq!(seen, vec![1]);
+    }
+}
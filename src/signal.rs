@@ -0,0 +1,111 @@
+//! Cancel a context when the process is asked to shut down.
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+
+use crate::{CancellationCause, Context};
+
+#[cfg(unix)]
+fn shutdown_signals() -> io::Result<impl Future<Output = ()> + Send> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    Ok(async move {
+        tokio::select! {
+            _ = interrupt.recv() => {},
+            _ = terminate.recv() => {},
+        }
+    })
+}
+
+#[cfg(windows)]
+fn shutdown_signals() -> io::Result<impl Future<Output = ()> + Send> {
+    use tokio::signal::windows;
+    let mut ctrl_c = windows::ctrl_c()?;
+    let mut ctrl_break = windows::ctrl_break()?;
+    let mut ctrl_close = windows::ctrl_close()?;
+    let mut ctrl_logoff = windows::ctrl_logoff()?;
+    let mut ctrl_shutdown = windows::ctrl_shutdown()?;
+    Ok(async move {
+        tokio::select! {
+            _ = ctrl_c.recv() => {},
+            _ = ctrl_break.recv() => {},
+            _ = ctrl_close.recv() => {},
+            _ = ctrl_logoff.recv() => {},
+            _ = ctrl_shutdown.recv() => {},
+        }
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn shutdown_signals() -> io::Result<impl Future<Output = ()> + Send> {
+    Ok(async {
+        let _ = tokio::signal::ctrl_c().await;
+    })
+}
+
+impl Context {
+    /// Cancel this context with `CancellationCause::Signal` when the process receives a shutdown request.
+    ///
+    /// The signal handlers are installed before this returns, so a signal sent right afterwards is not missed. Must be
+    /// called inside a tokio runtime. Which events count as a shutdown request depends on the platform:
+    ///
+    /// * Unix: `SIGINT` (Ctrl-C) and `SIGTERM`. The process gets as much time to drain as whoever sent the signal
+    ///   allows, for example the termination grace period of a container orchestrator.
+    /// * Windows: `CTRL_C`, `CTRL_BREAK`, `CTRL_CLOSE`, `CTRL_LOGOFF` and `CTRL_SHUTDOWN`. Ctrl-C and Ctrl-Break do
+    ///   not limit the time to drain. For close, logoff and shutdown Windows terminates the process once the handler
+    ///   has returned or a system timeout (about 5 seconds for close, up to 20 seconds for logoff and shutdown)
+    ///   expired, so only a short drain is possible. Service control manager stop requests are not delivered as
+    ///   console events; services should cancel the root context from their service control handler.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let mut root = Context::new();
+    /// root.cancel_on_shutdown_signals()?;
+    /// // spawn the application under root
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn cancel_on_shutdown_signals(&self) -> io::Result<()> {
+        let signals = shutdown_signals()?;
+        let inner = Arc::downgrade(&self.inner);
+        let mut cancel_receiver = self.inner.subscribe();
+        if self.inner.is_cancelled() {
+            return Ok(());
+        }
+        self.inner.spawn(async move {
+            tokio::select! {
+                _ = signals => {
+                    if let Some(inner) = inner.upgrade() {
+                        inner.cancel(CancellationCause::Signal);
+                    }
+                },
+                _ = cancel_receiver.recv() => {},
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn sigterm_cancels_context() {
+        let mut ctx = Context::new();
+        ctx.cancel_on_shutdown_signals().unwrap();
+        let task = ctx.spawn(std::future::pending::<()>());
+        let status = std::process::Command::new("kill")
+            .arg("-TERM")
+            .arg(std::process::id().to_string())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let outcome = tokio::time::timeout(Duration::from_secs(5), task).await.unwrap();
+        assert_eq!(outcome.unwrap(), None);
+        assert_eq!(ctx.cancellation_cause(), Some(CancellationCause::Signal));
+    }
+}
@@ -0,0 +1,220 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+use std::time::Duration;
+
+use futures_sink::Sink;
+use tokio::time::Sleep;
+
+use crate::{CancellationSignal, Context};
+
+/// How long `CancellableSink` waits for the inner sink to close after cancellation, unless configured otherwise
+pub const DEFAULT_CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Error of a `CancellableSink`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkError<E> {
+    /// The context was cancelled
+    Cancelled,
+    /// The inner sink failed
+    Sink(E),
+}
+
+impl<E: fmt::Display> fmt::Display for SinkError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SinkError::Cancelled => write!(f, "sink cancelled"),
+            SinkError::Sink(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for SinkError<E> {}
+
+enum State {
+    Open,
+    /// Cancelled, closing the inner sink until the timer expires
+    Closing(Pin<Box<Sleep>>),
+    Closed,
+}
+
+/// Sink that stops accepting items once its context is cancelled. Created by `Context::wrap_sink`.
+///
+/// After cancellation, the next `poll_ready`, `poll_flush` or `poll_close` closes the inner sink, waiting at most the
+/// close timeout so buffered items are not lost, and then completes with `SinkError::Cancelled`. Senders parked on a
+/// full sink are woken by the cancellation.
+pub struct CancellableSink<S, T> {
+    sink: Pin<Box<S>>,
+    cancelled: CancellationSignal,
+    close_timeout: Duration,
+    state: State,
+    _item: std::marker::PhantomData<fn(T)>,
+}
+
+impl<S: Sink<T>, T> CancellableSink<S, T> {
+    /// Wait at most `timeout` for the inner sink to close after cancellation. Defaults to `DEFAULT_CLOSE_TIMEOUT`.
+    pub fn close_timeout(mut self, timeout: Duration) -> Self {
+        self.close_timeout = timeout;
+        self
+    }
+
+    /// The inner sink
+    pub fn into_inner(self) -> Pin<Box<S>> {
+        self.sink
+    }
+
+    /// Closes the inner sink once cancelled. Returns None while the context is not cancelled.
+    fn poll_cancelled(&mut self, cx: &mut TaskContext<'_>) -> Option<Poll<Result<(), SinkError<S::Error>>>> {
+        if let State::Open = self.state {
+            if Pin::new(&mut self.cancelled).poll(cx).is_pending() {
+                return None;
+            }
+            self.state = State::Closing(Box::pin(tokio::time::sleep(self.close_timeout)));
+        }
+        if let State::Closing(timer) = &mut self.state {
+            let closed = self.sink.as_mut().poll_close(cx).is_ready();
+            if !closed && timer.as_mut().poll(cx).is_pending() {
+                return Some(Poll::Pending);
+            }
+            self.state = State::Closed;
+        }
+        Some(Poll::Ready(Err(SinkError::Cancelled)))
+    }
+}
+
+impl<S: Sink<T>, T> Sink<T> for CancellableSink<S, T> {
+    type Error = SinkError<S::Error>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        if let Some(poll) = this.poll_cancelled(cx) {
+            return poll;
+        }
+        this.sink.as_mut().poll_ready(cx).map_err(SinkError::Sink)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        if !matches!(this.state, State::Open) || this.cancelled.is_cancelled() {
+            return Err(SinkError::Cancelled);
+        }
+        this.sink.as_mut().start_send(item).map_err(SinkError::Sink)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        if let Some(poll) = this.poll_cancelled(cx) {
+            return poll;
+        }
+        this.sink.as_mut().poll_flush(cx).map_err(SinkError::Sink)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        if let Some(poll) = this.poll_cancelled(cx) {
+            return poll;
+        }
+        this.sink.as_mut().poll_close(cx).map_err(SinkError::Sink)
+    }
+}
+
+impl Context {
+    /// Wrap `sink` so sending stops with `SinkError::Cancelled` once this context is cancelled, instead of hanging
+    /// on a sink nobody reads from anymore.
+    /// ```rust, no_run
+    /// use futures_util::SinkExt;
+    /// use tokio_tree_context::{Context, SinkError};
+    ///
+    /// # async fn example<S: futures_sink::Sink<u32>>(ctx: Context, writer: S) -> Result<(), SinkError<S::Error>> {
+    /// let mut writer = ctx.wrap_sink(writer);
+    /// writer.send(1).await?; // fails with SinkError::Cancelled after cancellation
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn wrap_sink<S: Sink<T>, T>(&self, sink: S) -> CancellableSink<S, T> {
+        CancellableSink {
+            sink: Box::pin(sink),
+            cancelled: self.cancellation_signal(),
+            close_timeout: DEFAULT_CLOSE_TIMEOUT,
+            state: State::Open,
+            _item: std::marker::PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::SinkExt;
+    use std::sync::{Arc, Mutex};
+
+    /// Accepts one item at a time, and only delivers it on flush. Nobody flushes, like a writer whose reader is gone.
+    struct Stuck {
+        buffered: Option<u32>,
+        delivered: Arc<Mutex<Vec<u32>>>,
+        close_hangs: bool,
+    }
+
+    impl Sink<u32> for Stuck {
+        type Error = std::convert::Infallible;
+
+        fn poll_ready(self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+            if self.buffered.is_some() {
+                Poll::Pending
+            } else {
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, item: u32) -> Result<(), Self::Error> {
+            self.buffered = Some(item);
+            Ok(())
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+            if let Some(item) = self.buffered.take() {
+                self.delivered.lock().unwrap().push(item);
+            }
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+            if self.close_hangs {
+                return Poll::Pending;
+            }
+            self.poll_flush(cx)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancellation_wakes_parked_sender_and_closes() {
+        for close_hangs in [false, true] {
+            let ctx = Context::new();
+            let delivered = Arc::new(Mutex::new(Vec::new()));
+            let stuck = Stuck {
+                buffered: None,
+                delivered: delivered.clone(),
+                close_hangs,
+            };
+            let mut sink = ctx.wrap_sink(stuck).close_timeout(Duration::from_secs(2));
+            let sender = tokio::spawn(async move {
+                sink.feed(1).await.unwrap();
+                sink.feed(2).await
+            });
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            assert!(!sender.is_finished());
+            let start = tokio::time::Instant::now();
+            ctx.cancel();
+            assert_eq!(sender.await.unwrap(), Err(SinkError::Cancelled));
+            if close_hangs {
+                // gave up on the inner sink after the close timeout
+                assert_eq!(start.elapsed(), Duration::from_secs(2));
+                assert!(delivered.lock().unwrap().is_empty());
+            } else {
+                assert_eq!(start.elapsed(), Duration::ZERO);
+                assert_eq!(*delivered.lock().unwrap(), vec![1]);
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::future::Future;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use crate::{Context, ContextBuilder, ContextId, ContextRef, DrainTimedOut, SpawnError};
+
+/// A child context that can be restarted in place, created with `Context::child_slot`.
+///
+/// Each restart replaces the child with a new generation. Code that reaches the child through a `SlotRef` always
+/// gets the current generation, so nothing has to be re-wired after a restart. Dropping the slot cancels the child.
+pub struct ChildSlot {
+    slot: Arc<Slot>,
+}
+
+/// Non-owning handle to the current generation of a `ChildSlot`, like a `ContextRef` that follows restarts
+#[derive(Clone)]
+pub struct SlotRef {
+    slot: Weak<Slot>,
+}
+
+struct Slot {
+    parent: ContextRef,
+    builder: Box<dyn Fn() -> ContextBuilder + Send + Sync>,
+    current: Mutex<Generation>,
+}
+
+struct Generation {
+    number: u64,
+    context: Context,
+}
+
+impl Slot {
+    fn current(&self) -> (u64, ContextRef) {
+        let current = self.current.lock().unwrap();
+        (current.number, current.context.as_ref())
+    }
+}
+
+impl ChildSlot {
+    /// Number of the current generation, 1 for the first child and incremented by each restart
+    pub fn generation(&self) -> u64 {
+        self.slot.current.lock().unwrap().number
+    }
+
+    /// The current generation
+    pub fn current(&self) -> ContextRef {
+        self.slot.current().1
+    }
+
+    /// A handle that follows the restarts of this slot
+    pub fn slot_ref(&self) -> SlotRef {
+        SlotRef { slot: Arc::downgrade(&self.slot) }
+    }
+
+    /// Replace the child with a new generation, then cancel the old one and wait up to `drain_timeout` for it to
+    /// drain, like `Context::cancel_and_wait`.
+    ///
+    /// The new generation is built from the builder of the slot and takes over the child limit and the values of the
+    /// old one. It is in place as soon as `restart` returns, so new work can start while the old generation drains.
+    ///
+    /// The old generation counts towards the child limit of the parent until it is dropped. If the parent has no room
+    /// for the new generation, it starts out cancelled.
+    pub fn restart(&self, drain_timeout: Duration) -> impl Future<Output = Result<(), DrainTimedOut>> + Send + Unpin + 'static {
+        let old = {
+            let mut current = self.slot.current.lock().unwrap();
+            let context = new_generation(&self.slot.parent, &self.slot.builder);
+            let old = &current.context.inner;
+            context.inner.max_children.store(old.max_children.load(Ordering::SeqCst), Ordering::SeqCst);
+            context.inner.values.copy_from(&old.values, |_| true);
+            let number = current.number + 1;
+            std::mem::replace(&mut *current, Generation { number, context })
+        };
+        old.context.cancel_and_wait(drain_timeout)
+    }
+}
+
+impl SlotRef {
+    /// Number of the current generation, None if the slot is gone
+    pub fn generation(&self) -> Option<u64> {
+        self.slot.upgrade().map(|slot| slot.current().0)
+    }
+
+    /// The current generation. Once the slot is gone, a handle that only reports cancellation.
+    pub fn context_ref(&self) -> ContextRef {
+        match self.slot.upgrade() {
+            Some(slot) => slot.current().1,
+            None => ContextRef::gone(),
+        }
+    }
+
+    /// Id of the current generation, None if the slot is gone
+    pub fn id(&self) -> Option<ContextId> {
+        self.slot.upgrade().map(|slot| slot.current.lock().unwrap().context.id())
+    }
+
+    /// Spawn a task under the current generation. Fails with `SpawnError::ScopeClosed` if the slot is gone.
+    #[track_caller]
+    pub fn spawn<T>(&self, future: T) -> Result<tokio::task::JoinHandle<Option<T::Output>>, SpawnError>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        self.context_ref().spawn(future)
+    }
+}
+
+fn new_generation(parent: &ContextRef, builder: &(dyn Fn() -> ContextBuilder + Send + Sync)) -> Context {
+    match parent.upgrade() {
+        Some(parent) => Context::try_create(Some(&parent), builder()).unwrap_or_else(|_| {
+            let child = builder().build();
+            child.inner.cancel(crate::CancellationCause::Explicit);
+            child
+        }),
+        None => {
+            let child = builder().build();
+            child.inner.cancel(crate::CancellationCause::Parent);
+            child
+        }
+    }
+}
+
+impl Context {
+    /// Create a child context that can be restarted with `ChildSlot::restart`, built with `builder` for every
+    /// generation.
+    ///
+    /// If this context already has as many children as `with_max_children` allows, the first generation starts out
+    /// cancelled.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(mut root: Context) {
+    /// let storage = root.child_slot(|| Context::builder().name("storage"));
+    /// let handle = storage.slot_ref();
+    /// let _ = handle.spawn(async { /* serve */ });
+    /// // the storage subsystem failed
+    /// if let Err(report) = storage.restart(Duration::from_secs(5)).await {
+    ///     eprintln!("generation {} did not drain: {report}", storage.generation() - 1);
+    /// }
+    /// let _ = handle.spawn(async { /* runs in the new generation */ });
+    /// # }
+    /// ```
+    pub fn child_slot(&mut self, builder: impl Fn() -> ContextBuilder + Send + Sync + 'static) -> ChildSlot {
+        let parent = self.as_ref();
+        let context = new_generation(&parent, &builder);
+        ChildSlot {
+            slot: Arc::new(Slot {
+                parent,
+                builder: Box::new(builder),
+                current: Mutex::new(Generation { number: 1, context }),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ContextLocalKey;
+
+    static SHARD: ContextLocalKey<u32> = ContextLocalKey::new("shard");
+
+    #[tokio::test(start_paused = true)]
+    async fn restart_swaps_the_generation_behind_slot_refs() {
+        let mut root = Context::new();
+        let slot = root.child_slot(|| Context::builder().name("storage"));
+        let handle = slot.slot_ref();
+        let old = slot.current();
+        assert_eq!(handle.generation(), Some(1));
+        slot.slot.current.lock().unwrap().context.set_value(&SHARD, 7);
+        let old_task = handle.spawn(std::future::pending::<()>()).unwrap();
+
+        slot.restart(Duration::from_secs(1)).await.unwrap();
+        assert!(old.is_cancelled());
+        assert_eq!(old_task.await.unwrap(), None);
+        assert_eq!(handle.generation(), Some(2));
+        assert!(!handle.context_ref().is_cancelled());
+        let id = handle.id();
+        let current = slot.slot.current.lock().unwrap();
+        assert_eq!(current.context.name(), Some("storage"));
+        assert_eq!(current.context.value(&SHARD).as_deref(), Some(&7));
+        assert_eq!(id, Some(current.context.id()));
+        drop(current);
+
+        drop(slot);
+        assert_eq!(handle.generation(), None);
+        assert!(handle.spawn(async {}).is_err());
+        assert!(!root.is_cancelled());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn generations_beyond_the_child_limit_start_cancelled() {
+        let mut root = Context::new().with_max_children(1);
+        let slot = root.child_slot(Context::builder);
+        assert!(!slot.current().is_cancelled());
+        slot.restart(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(slot.generation(), 2);
+        assert!(slot.current().is_cancelled());
+
+        let _taken = root.try_new_child_context().unwrap();
+        let other = root.child_slot(Context::builder);
+        assert!(other.current().is_cancelled());
+    }
+}
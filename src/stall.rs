@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+
+use crate::tree::SpawnLocation;
+use crate::{Context, ContextInner};
+
+/// Called by the stall monitor once each time a task starts stalling
+pub(crate) type StallHandler = Arc<dyn Fn(&StalledTask) + Send + Sync>;
+
+/// A live task that has not been polled for longer than the stall threshold of its context
+#[derive(Debug, Clone)]
+pub struct StalledTask {
+    pub id: u64,
+    pub name: Option<Arc<str>>,
+    pub spawned_at: SpawnLocation,
+    /// Time since the task was last polled
+    pub stalled_for: Duration,
+}
+
+/// Stored by `StallDetector::stamp` in place of a poll time, for the next scan to replace with its own time
+const POLLED: u64 = u64::MAX;
+
+pub(crate) struct StallDetector {
+    threshold: Duration,
+    /// Scan times are stored as milliseconds since this instant
+    epoch: Instant,
+    handler: Option<StallHandler>,
+    /// Stalls detected by the monitor
+    stalls: AtomicU64,
+}
+
+impl StallDetector {
+    pub(crate) fn new(threshold: Duration, handler: Option<StallHandler>) -> StallDetector {
+        StallDetector {
+            threshold,
+            epoch: Instant::now(),
+            handler,
+            stalls: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn stalls(&self) -> u64 {
+        self.stalls.load(Ordering::Relaxed)
+    }
+
+    /// Record a poll. This runs on every poll of every task of the context, so it is a single relaxed store, without
+    /// reading the clock: the scans turn the mark into a time.
+    pub(crate) fn stamp(&self, last_poll: &AtomicU64) {
+        last_poll.store(POLLED, Ordering::Relaxed);
+    }
+
+    /// How long a task has been stalled, or None if it was polled within the threshold. A task polled since the last
+    /// scan counts as polled now, so the stall is measured from the first scan that found it not polled, which the
+    /// monitor runs every half threshold.
+    pub(crate) fn stalled_for(&self, last_poll: &AtomicU64, now: Instant) -> Option<Duration> {
+        let now_ms = now.saturating_duration_since(self.epoch).as_millis() as u64;
+        let last_poll = match last_poll.compare_exchange(POLLED, now_ms, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => now_ms,
+            Err(last_poll) => last_poll,
+        };
+        let stalled_for = Duration::from_millis(now_ms.saturating_sub(last_poll));
+        (stalled_for > self.threshold).then_some(stalled_for)
+    }
+}
+
+impl ContextInner {
+    fn stalled_tasks(&self) -> Vec<StalledTask> {
+        let Some(detector) = &self.stall else {
+            return Vec::new();
+        };
+        let now = Instant::now();
+        let mut stalled: Vec<StalledTask> = self
+            .tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(id, info)| {
+                let stalled_for = detector.stalled_for(info.last_poll.as_deref()?, now)?;
+                Some(StalledTask {
+                    id: *id,
+                    name: info.name.clone(),
+                    spawned_at: info.location.into(),
+                    stalled_for,
+                })
+            })
+            .collect();
+        stalled.sort_by_key(|task| task.id);
+        stalled
+    }
+}
+
+/// Count tasks that start stalling and report them to the handler and as events. Only holds the context while
+/// scanning it, and exits once the context is cancelled or gone.
+pub(crate) async fn monitor(inner: Weak<ContextInner>, detector: Arc<StallDetector>, mut cancel_receiver: broadcast::Receiver<()>) {
+    let period = (detector.threshold / 2).max(Duration::from_millis(1));
+    let mut ticker = tokio::time::interval(period);
+    let mut flagged = HashSet::new();
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {},
+            _ = cancel_receiver.recv() => return,
+        }
+        let Some(context) = inner.upgrade().filter(|context| !context.is_cancelled()) else {
+            return;
+        };
+        let stalled = context.stalled_tasks();
+        let still_stalled: HashSet<u64> = stalled.iter().map(|task| task.id).collect();
+        for task in stalled.iter().filter(|task| !flagged.contains(&task.id)) {
+            detector.stalls.fetch_add(1, Ordering::Relaxed);
+            if let Some(handler) = &detector.handler {
+                handler(task);
+            }
+            context.emit_task_stalled(task);
+        }
+        flagged = still_stalled;
+    }
+}
+
+impl Context {
+    /// Live tasks of this context that have not been polled for longer than its stall threshold.
+    ///
+    /// Empty unless the context was built with `ContextBuilder::stall_threshold`.
+    pub fn stalled_tasks(&self) -> Vec<StalledTask> {
+        self.inner.stalled_tasks()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Context, ContextEvent};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn tasks_that_stop_being_polled_are_flagged() {
+        let flagged = Arc::new(Mutex::new(Vec::new()));
+        let seen = flagged.clone();
+        let mut ctx = Context::builder()
+            .stall_threshold(Duration::from_secs(1))
+            .on_stall(move |task| seen.lock().unwrap().push(task.name.clone()))
+            .build();
+        let _busy = ctx.spawn_named("busy", async {
+            loop {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        });
+        let _stuck = ctx.spawn_named("stuck", std::future::pending::<()>());
+        let line = line!() - 1;
+        tokio::time::sleep(Duration::from_secs(3)).await;
+
+        let stalled = ctx.stalled_tasks();
+        assert_eq!(stalled.len(), 1);
+        assert_eq!(stalled[0].name.as_deref(), Some("stuck"));
+        assert_eq!(stalled[0].spawned_at.line, line);
+        assert!(stalled[0].stalled_for >= Duration::from_secs(2));
+        // reported once, not on every scan
+        assert_eq!(*flagged.lock().unwrap(), vec![Some("stuck".into())]);
+        let tree = ctx.tree();
+        let stuck = tree.root.tasks.iter().find(|task| task.name.as_deref() == Some("stuck")).unwrap();
+        assert!(stuck.stalled_for_ms.unwrap() >= 2000);
+        assert!(tree.root.tasks.iter().any(|task| task.stalled_for_ms.is_none()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stalls_are_counted_and_published_once() {
+        let mut ctx = Context::builder().stall_threshold(Duration::from_secs(1)).build();
+        let mut events = ctx.subscribe_events();
+        let _stuck = ctx.spawn_named("stuck", std::future::pending::<()>());
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        assert_eq!(ctx.stats().stalls, 1);
+
+        let id = ctx.id();
+        drop(ctx);
+        let mut stalls = Vec::new();
+        while let Some(event) = events.recv().await {
+            if let ContextEvent::TaskStalled { context, stalled_for, .. } = event {
+                assert_eq!(context, id);
+                stalls.push(stalled_for);
+            }
+        }
+        assert_eq!(stalls.len(), 1);
+        assert!(stalls[0] > Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,41 @@
+use crate::{CapacityStats, Context, ContextInner, MemoryStats};
+#[cfg(feature = "poll-time")]
+use crate::PollStats;
+
+/// Statistics of a context, taken by `Context::stats`
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct ContextStats {
+    /// Tasks of the context that are running now
+    pub live_tasks: usize,
+    /// Used and free units, if the context has a capacity
+    pub capacity: Option<CapacityStats>,
+    /// Bytes reserved with `Context::reserve_memory`, the subtree total includes descendants
+    pub memory: MemoryStats,
+    /// Tasks found stalled since the context was created, each stall counted once. 0 without a stall threshold.
+    pub stalls: u64,
+    /// Polls of all tasks of the context since it was created, with the `poll-time` feature
+    #[cfg(feature = "poll-time")]
+    pub poll: PollStats,
+}
+
+impl ContextInner {
+    pub(crate) fn stats(&self) -> ContextStats {
+        ContextStats {
+            live_tasks: self.active_tasks.load(std::sync::atomic::Ordering::SeqCst),
+            capacity: self.capacity.as_ref().map(|capacity| capacity.stats()),
+            memory: self.memory.stats(),
+            stalls: self.stall.as_ref().map_or(0, |stall| stall.stalls()),
+            #[cfg(feature = "poll-time")]
+            poll: self.poll_time.get(),
+        }
+    }
+}
+
+impl Context {
+    /// Statistics of this context, not including its descendants
+    pub fn stats(&self) -> ContextStats {
+        self.inner.stats()
+    }
+}
@@ -0,0 +1,112 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+
+use futures_core::stream::{FusedStream, Stream};
+
+use crate::ContextRef;
+
+/// Stream that ends once its context is cancelled, created by `TakeUntilCancelledExt::take_until_cancelled`
+pub struct TakeUntilCancelled<S> {
+    stream: Pin<Box<S>>,
+    cancelled: Pin<Box<dyn Future<Output = ()> + Send>>,
+    ctx_cancelled: bool,
+    done: bool,
+}
+
+impl<S> TakeUntilCancelled<S> {
+    /// True if the stream ended because the context was cancelled rather than because the inner stream was exhausted
+    pub fn ctx_cancelled(&self) -> bool {
+        self.ctx_cancelled
+    }
+
+    /// The inner stream
+    pub fn into_inner(self) -> Pin<Box<S>> {
+        self.stream
+    }
+}
+
+impl<S: Stream> Stream for TakeUntilCancelled<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<S::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        if self.cancelled.as_mut().poll(cx).is_ready() {
+            self.ctx_cancelled = true;
+            self.done = true;
+            return Poll::Ready(None);
+        }
+        let item = self.stream.as_mut().poll_next(cx);
+        if let Poll::Ready(None) = item {
+            self.done = true;
+        }
+        item
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.done {
+            (0, Some(0))
+        } else {
+            self.stream.size_hint()
+        }
+    }
+}
+
+impl<S: Stream> FusedStream for TakeUntilCancelled<S> {
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
+
+/// Adds `take_until_cancelled` to every stream
+pub trait TakeUntilCancelledExt: Stream + Sized {
+    /// End the stream once `ctx` is cancelled. Accepts a `&Context` or a `ContextRef`.
+    /// ```rust, no_run
+    /// use futures_util::StreamExt;
+    /// use tokio_tree_context::{Context, TakeUntilCancelledExt};
+    ///
+    /// # async fn example(ctx: Context, lines: impl futures_core::Stream<Item = String>) {
+    /// let mut lines = std::pin::pin!(lines.take_until_cancelled(&ctx));
+    /// while let Some(line) = lines.next().await {
+    ///     println!("{line}");
+    /// }
+    /// if lines.ctx_cancelled() {
+    ///     println!("stopped early");
+    /// }
+    /// # }
+    /// ```
+    fn take_until_cancelled(self, ctx: impl Into<ContextRef>) -> TakeUntilCancelled<Self> {
+        TakeUntilCancelled {
+            stream: Box::pin(self),
+            cancelled: Box::pin(ctx.into().cancelled()),
+            ctx_cancelled: false,
+            done: false,
+        }
+    }
+}
+
+impl<S: Stream> TakeUntilCancelledExt for S {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Context;
+    use futures_util::StreamExt;
+
+    #[tokio::test]
+    async fn stream_ends_on_cancel_or_exhaustion() {
+        let ctx = Context::new();
+        let mut numbers = futures_util::stream::iter(1..=3).take_until_cancelled(&ctx);
+        assert_eq!(numbers.size_hint(), (3, Some(3)));
+        assert_eq!((&mut numbers).collect::<Vec<_>>().await, vec![1, 2, 3]);
+        assert!(numbers.is_terminated() && !numbers.ctx_cancelled());
+
+        let mut ticks = futures_util::stream::repeat(()).take_until_cancelled(ctx.as_ref());
+        assert_eq!(ticks.next().await, Some(()));
+        drop(ctx);
+        assert_eq!(ticks.next().await, None);
+        assert!(ticks.is_terminated() && ticks.ctx_cancelled());
+    }
+}
@@ -0,0 +1,10 @@
+//! Synchronization primitives of the cancellation path, swapped for loom's when model checking with
+//! `RUSTFLAGS="--cfg loom" cargo test --release --lib loom`.
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicBool, Ordering};
+#[cfg(loom)]
+pub(crate) use loom::sync::{Condvar, Mutex};
+#[cfg(not(loom))]
+pub(crate) use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(not(loom))]
+pub(crate) use std::sync::{Condvar, Mutex};
@@ -0,0 +1,138 @@
+use std::future::Future;
+use std::panic::Location;
+use std::sync::Arc;
+use tokio::time::Instant;
+
+use crate::{Context, ContextInner, TaskOptions};
+
+/// Counts tasks as they are spawned
+pub const TASKS_SPAWNED: &str = "tokio_tree_context_tasks_spawned_total";
+/// Counts tasks that ran to completion
+pub const TASKS_COMPLETED: &str = "tokio_tree_context_tasks_completed_total";
+/// Counts tasks that stopped before completing: cancelled, timed out, aborted or panicked
+pub const TASKS_CANCELLED: &str = "tokio_tree_context_tasks_cancelled_total";
+/// Time from spawn to completion of tasks that ran to completion, in seconds
+pub const TASK_DURATION: &str = "tokio_tree_context_task_duration_seconds";
+
+/// Emits the lifecycle metrics of a task, the end of it when dropped
+pub(crate) struct TaskMetrics {
+    label: Arc<str>,
+    spawned_at: Instant,
+    completed: bool,
+}
+
+impl TaskMetrics {
+    /// Count the spawn of a task under `label`, or the name of the context
+    pub(crate) fn spawned(inner: &ContextInner, label: Option<Arc<str>>) -> TaskMetrics {
+        let label = label.or_else(|| inner.name.clone()).unwrap_or_else(|| Arc::from("unnamed"));
+        ::metrics::counter!(TASKS_SPAWNED, "task_label" => label.to_string()).increment(1);
+        TaskMetrics {
+            label,
+            spawned_at: Instant::now(),
+            completed: false,
+        }
+    }
+
+    pub(crate) fn completed(&mut self) {
+        self.completed = true;
+    }
+}
+
+impl Drop for TaskMetrics {
+    fn drop(&mut self) {
+        let label = self.label.to_string();
+        if self.completed {
+            ::metrics::counter!(TASKS_COMPLETED, "task_label" => label.clone()).increment(1);
+            ::metrics::histogram!(TASK_DURATION, "task_label" => label).record(self.spawned_at.elapsed().as_secs_f64());
+        } else {
+            ::metrics::counter!(TASKS_CANCELLED, "task_label" => label).increment(1);
+        }
+    }
+}
+
+impl Context {
+    /// Spawn a task whose lifecycle metrics are tagged with `task_label = label` instead of the context name.
+    ///
+    /// All tasks with the same label add up to the same series, e.g. to watch the latency of every "db-query" task.
+    /// The metrics are emitted through the `metrics` crate as `TASKS_SPAWNED`, `TASKS_COMPLETED`, `TASKS_CANCELLED`
+    /// and `TASK_DURATION`.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut ctx = Context::new();
+    /// ctx.spawn_with_metrics_label("db-query", async move { /* query */ });
+    /// ```
+    #[track_caller]
+    pub fn spawn_with_metrics_label<T>(&mut self, label: impl Into<String>, future: T) -> tokio::task::JoinHandle<Option<T::Output>>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        let options = TaskOptions {
+            metrics_label: Some(Arc::from(label.into())),
+            ..Default::default()
+        };
+        self.inner.spawn_or_none(self.inner.task_future_at(None, future, Location::caller(), options))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::metrics::{Counter, CounterFn, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct Counted(Mutex<Vec<String>>);
+
+    struct Series(Arc<Counted>, String);
+
+    impl CounterFn for Series {
+        fn increment(&self, _: u64) {
+            self.0 .0.lock().unwrap().push(self.1.clone());
+        }
+        fn absolute(&self, _: u64) {}
+    }
+
+    struct Recording(Arc<Counted>);
+
+    impl Recorder for Recording {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            let labels: Vec<_> = key.labels().map(|label| label.value().to_string()).collect();
+            Counter::from_arc(Arc::new(Series(self.0.clone(), format!("{} {}", key.name(), labels.join(",")))))
+        }
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+        fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::noop()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tasks_are_counted_by_label_or_context_name() {
+        let counted = Arc::new(Counted::default());
+        ::metrics::set_global_recorder(Recording(counted.clone())).unwrap();
+        let mut ctx = Context::builder().name("metered-api").build();
+        ctx.spawn_with_metrics_label("metered-db-query", async {}).await.unwrap();
+        let stuck = ctx.spawn(tokio::time::sleep(Duration::from_secs(10)));
+        tokio::task::yield_now().await;
+        ctx.cancel();
+        stuck.await.unwrap();
+        // other tests run concurrently and are counted too
+        let counted: Vec<_> = counted.0.lock().unwrap().iter().filter(|series| series.contains(" metered-")).cloned().collect();
+        assert_eq!(
+            counted,
+            vec![
+                format!("{} metered-db-query", TASKS_SPAWNED),
+                format!("{} metered-db-query", TASKS_COMPLETED),
+                format!("{} metered-api", TASKS_SPAWNED),
+                format!("{} metered-api", TASKS_CANCELLED),
+            ]
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::{Context, ContextInner, TaskMeta};
+
+/// Receives the lifecycle events of the tasks of a context, see `Context::with_telemetry_recorder`
+pub trait TelemetryRecorder: Send + Sync {
+    /// A task was spawned
+    fn record_spawn(&self, meta: &TaskMeta);
+    /// A task ran to completion, `duration` after it was spawned
+    fn record_complete(&self, meta: &TaskMeta, duration: Duration);
+    /// A task stopped before completing: cancelled, timed out, aborted or panicked
+    fn record_cancel(&self, meta: &TaskMeta);
+}
+
+/// The recorder set on a context itself, if any
+pub(crate) type TelemetrySlot = Mutex<Option<Arc<dyn TelemetryRecorder>>>;
+
+/// Reports the end of a task to its recorder when dropped
+pub(crate) struct TaskTelemetry {
+    recorder: Arc<dyn TelemetryRecorder>,
+    meta: TaskMeta,
+    spawned_at: Instant,
+    completed: bool,
+}
+
+impl TaskTelemetry {
+    /// Report the spawn of a task, if the context or an ancestor has a recorder
+    pub(crate) fn spawned(inner: &ContextInner, name: Option<&str>, priority: Option<i32>) -> Option<TaskTelemetry> {
+        let recorder = inner.telemetry_recorder()?;
+        let meta = TaskMeta {
+            name: name.map(String::from),
+            live_tasks: inner.active_tasks.load(std::sync::atomic::Ordering::SeqCst),
+            priority,
+        };
+        recorder.record_spawn(&meta);
+        Some(TaskTelemetry {
+            recorder,
+            meta,
+            spawned_at: Instant::now(),
+            completed: false,
+        })
+    }
+
+    pub(crate) fn completed(&mut self) {
+        self.completed = true;
+    }
+}
+
+impl Drop for TaskTelemetry {
+    fn drop(&mut self) {
+        if self.completed {
+            self.recorder.record_complete(&self.meta, self.spawned_at.elapsed());
+        } else {
+            self.recorder.record_cancel(&self.meta);
+        }
+    }
+}
+
+impl ContextInner {
+    /// The recorder of this context, or of the nearest ancestor that has one
+    fn telemetry_recorder(&self) -> Option<Arc<dyn TelemetryRecorder>> {
+        if let Some(recorder) = self.telemetry.lock().unwrap().as_ref() {
+            return Some(recorder.clone());
+        }
+        self.parent.as_ref().and_then(|parent| parent.telemetry_recorder())
+    }
+}
+
+impl Context {
+    /// Report the spawn, completion and cancellation of every task of this context to `recorder`.
+    ///
+    /// Child contexts use the recorder of their nearest ancestor unless they are given their own.
+    /// ```rust, no_run
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    /// use tokio_tree_context::{Context, TaskMeta, TelemetryRecorder};
+    ///
+    /// struct Log;
+    ///
+    /// impl TelemetryRecorder for Log {
+    ///     fn record_spawn(&self, meta: &TaskMeta) {
+    ///         println!("spawned {:?}", meta.name);
+    ///     }
+    ///     fn record_complete(&self, meta: &TaskMeta, duration: Duration) {
+    ///         println!("{:?} completed in {:?}", meta.name, duration);
+    ///     }
+    ///     fn record_cancel(&self, meta: &TaskMeta) {
+    ///         println!("{:?} cancelled", meta.name);
+    ///     }
+    /// }
+    ///
+    /// let ctx = Context::new().with_telemetry_recorder(Arc::new(Log));
+    /// ```
+    pub fn with_telemetry_recorder(self, recorder: Arc<dyn TelemetryRecorder>) -> Context {
+        *self.inner.telemetry.lock().unwrap() = Some(recorder);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorded(Mutex<Vec<String>>);
+
+    impl TelemetryRecorder for Recorded {
+        fn record_spawn(&self, meta: &TaskMeta) {
+            self.0.lock().unwrap().push(format!("spawn {}", meta.name.as_deref().unwrap_or("?")));
+        }
+        fn record_complete(&self, meta: &TaskMeta, duration: Duration) {
+            self.0.lock().unwrap().push(format!("complete {} {:?}", meta.name.as_deref().unwrap_or("?"), duration));
+        }
+        fn record_cancel(&self, meta: &TaskMeta) {
+            self.0.lock().unwrap().push(format!("cancel {}", meta.name.as_deref().unwrap_or("?")));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lifecycle_is_recorded_and_inherited() {
+        let recorded = Arc::new(Recorded::default());
+        let mut root = Context::new().with_telemetry_recorder(recorded.clone());
+        let mut child = root.new_child_context();
+        let done = child.spawn_named("done", tokio::time::sleep(Duration::from_secs(1)));
+        let stuck = child.spawn_named("stuck", std::future::pending::<()>());
+        done.await.unwrap();
+        drop(child);
+        stuck.await.unwrap();
+        assert_eq!(*recorded.0.lock().unwrap(), vec!["spawn done", "spawn stuck", "complete done 1s", "cancel stuck"]);
+    }
+}
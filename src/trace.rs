@@ -0,0 +1,134 @@
+use std::fmt;
+use std::sync::Mutex;
+
+use crate::{Context, ContextInner};
+
+/// Distributed tracing identifier propagated through a context tree. Displayed as 32 lowercase hex digits, like a
+/// W3C trace context trace id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
+pub struct TraceId(pub u128);
+
+impl fmt::Display for TraceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl From<u128> for TraceId {
+    fn from(id: u128) -> Self {
+        TraceId(id)
+    }
+}
+
+/// The trace id set on a context itself, if any
+pub(crate) type TraceSlot = Mutex<Option<TraceId>>;
+
+/// Set with `Context::with_inherit_tracing_context`, None to follow the parent
+pub(crate) type InheritSpanSlot = Mutex<Option<bool>>;
+
+impl ContextInner {
+    /// The trace id of this context or of the nearest ancestor that has one
+    pub(crate) fn trace_id(&self) -> Option<TraceId> {
+        let mut context = Some(self);
+        while let Some(current) = context {
+            if let Some(id) = *current.trace_id.lock().unwrap() {
+                return Some(id);
+            }
+            context = current.parent.as_deref();
+        }
+        None
+    }
+
+    /// Whether tasks spawned on this context run in the span of the caller, as set on this context or the nearest
+    /// ancestor. True if none of them set it.
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    pub(crate) fn inherits_span(&self) -> bool {
+        let mut context = Some(self);
+        while let Some(current) = context {
+            if let Some(inherit) = *current.inherit_span.lock().unwrap() {
+                return inherit;
+            }
+            context = current.parent.as_deref();
+        }
+        true
+    }
+
+    /// The span a task spawned now runs in
+    #[cfg(feature = "tracing")]
+    pub(crate) fn task_span(&self) -> tracing::Span {
+        match (self.trace_id(), self.inherits_span()) {
+            (Some(trace_id), true) => tracing::info_span!("task", trace_id = %trace_id, context = %self.id),
+            (Some(trace_id), false) => tracing::info_span!(parent: None, "task", trace_id = %trace_id, context = %self.id),
+            (None, true) => tracing::Span::current(),
+            (None, false) => tracing::Span::none(),
+        }
+    }
+}
+
+impl Context {
+    /// Attach a trace id to this context and, unless they have their own, all its descendants.
+    ///
+    /// With the `tracing` feature, tasks spawned on the context run in a span with a `trace_id` field.
+    /// ```rust, no_run
+    /// use tokio_tree_context::{Context, TraceId};
+    ///
+    /// let mut request = Context::new().with_trace_id(TraceId(0x4bf92f3577b34da6a3ce929d0e0e4736));
+    /// let child = request.new_child_context();
+    /// println!("handling trace {}", child.trace_id().unwrap());
+    /// ```
+    pub fn with_trace_id(self, id: TraceId) -> Context {
+        *self.inner.trace_id.lock().unwrap() = Some(id);
+        self
+    }
+
+    /// Whether tasks spawned on this context and, unless they override it, its descendants run inside the span that
+    /// is current where they are spawned. True by default.
+    ///
+    /// Only has an effect with the `tracing` feature. With `false`, tasks start without a parent span, so background
+    /// work spawned while handling a request is not attributed to that request. A context with a trace id still runs
+    /// its tasks in a `task` span, which then is a root span.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut background = Context::new().with_inherit_tracing_context(false);
+    /// background.spawn(async move { /* refresh the cache, outside of any request span */ });
+    /// ```
+    pub fn with_inherit_tracing_context(self, inherit: bool) -> Context {
+        *self.inner.inherit_span.lock().unwrap() = Some(inherit);
+        self
+    }
+
+    /// The trace id of this context, inherited from the nearest ancestor if it has none itself
+    pub fn trace_id(&self) -> Option<TraceId> {
+        self.inner.trace_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_id_is_inherited_by_descendants() {
+        let mut root = Context::new().with_trace_id(TraceId(0xabc));
+        let mut child = root.new_child_context();
+        let grandchild = child.new_child_context();
+        assert_eq!(grandchild.trace_id(), Some(TraceId(0xabc)));
+        assert_eq!(grandchild.trace_id().unwrap().to_string(), "00000000000000000000000000000abc");
+        let other = root.new_child_context().with_trace_id(TraceId(1));
+        assert_eq!(other.trace_id(), Some(TraceId(1)));
+        assert_eq!(Context::new().trace_id(), None);
+    }
+
+    #[test]
+    fn span_inheritance_follows_the_nearest_setting() {
+        let mut root = Context::new().with_inherit_tracing_context(false);
+        let mut child = root.new_child_context();
+        let grandchild = child.new_child_context().with_inherit_tracing_context(true);
+        assert!(!root.inner.inherits_span());
+        assert!(!child.inner.inherits_span());
+        assert!(grandchild.inner.inherits_span());
+        assert!(Context::new().inner.inherits_span());
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt;
+use std::future::Future;
+use std::panic::Location;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, Weak};
+use tokio::sync::Notify;
+
+use crate::{CancelScope, Context, ContextInner, SpawnError, TaskGuard, TaskOptions};
+
+/// Reason `Context::transfer_task` failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferError {
+    /// The context has no live task with this id that was spawned with `spawn_transferable`
+    UnknownTask(u64),
+    /// The source or the target context is cancelled
+    Cancelled,
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransferError::UnknownTask(id) => write!(f, "no transferable task with id {}", id),
+            TransferError::Cancelled => write!(f, "context is cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for TransferError {}
+
+/// Handle and task id of a task spawned with `Context::spawn_transferable`
+type Transferable<T> = (tokio::task::JoinHandle<Option<T>>, u64);
+
+/// The context a transferable task currently belongs to, swapped by `Context::transfer_task`
+#[derive(Default)]
+pub(crate) struct TransferSlot {
+    /// Set by `task_future_at` once the task is registered
+    current: Mutex<Option<TaskGuard>>,
+    moved: Notify,
+}
+
+impl Context {
+    /// Spawn a task that can later be moved to another context with `transfer_task`. Returns the handle and the id
+    /// of the task, as shown in `tree()`.
+    ///
+    /// The task is run like any other task of the context, and stops when the context it currently belongs to is
+    /// cancelled.
+    #[track_caller]
+    pub fn spawn_transferable<T>(&mut self, future: T) -> Result<Transferable<T::Output>, SpawnError>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        let slot = Arc::new(TransferSlot::default());
+        let options = TaskOptions { transfer: Some(slot.clone()), ..Default::default() };
+        let task = self.inner.task_future_at(None, future, Location::caller(), options)?;
+        let id = slot.with(|guard| guard.id);
+        Ok((self.inner.spawn(task), id))
+    }
+
+    /// Move a task spawned with `spawn_transferable` from this context to `to`, so that cancelling this context no
+    /// longer stops it. Returns the id of the task in `to`.
+    ///
+    /// The task counts as a task of `to` from then on. A cancellation of either context that races with the transfer
+    /// either happens first, and the transfer fails with `TransferError::Cancelled`, or does not affect the task.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example() {
+    /// let mut old = Context::new();
+    /// let (_flusher, id) = old.spawn_transferable(async move { /* flush metrics forever */ }).unwrap();
+    /// let reloaded = Context::new();
+    /// old.transfer_task(id, &reloaded).unwrap();
+    /// drop(old); // the flusher keeps running under `reloaded`
+    /// # }
+    /// ```
+    pub fn transfer_task(&self, task_id: u64, to: &Context) -> Result<u64, TransferError> {
+        let unknown = || TransferError::UnknownTask(task_id);
+        let (slot, name, location) = {
+            let tasks = self.inner.tasks.lock().unwrap();
+            let task = tasks.get(&task_id).ok_or_else(unknown)?;
+            let slot = task.transfer.as_ref().and_then(Weak::upgrade).ok_or_else(unknown)?;
+            (slot, task.name.clone(), task.location)
+        };
+        let mut current = slot.current.lock().unwrap();
+        let Some(guard) = current.as_mut().filter(|guard| Arc::ptr_eq(&guard.inner, &self.inner) && guard.id == task_id) else {
+            return Err(unknown());
+        };
+        if self.inner.is_cancelled() || to.inner.is_cancelled() {
+            return Err(TransferError::Cancelled);
+        }
+        let moved = TaskGuard::new(to.inner.clone(), name.clone(), location, None, CancelScope::Full).map_err(|_| TransferError::Cancelled)?;
+        to.inner.task_counters.track(&moved.outcome);
+        if let Some(name) = &name {
+            to.inner.name_stats.task_spawned(name);
+        }
+        if guard.running.load(Ordering::SeqCst) {
+            moved.start_running();
+        }
+        let id = moved.id;
+        let previous = std::mem::replace(guard, moved);
+        // counted as spawned by both contexts, and as ended only by the one it ends in
+        previous.outcome.set(crate::epoch::OutcomeSlot::UNTRACKED);
+        drop(current);
+        slot.register();
+        drop(previous);
+        slot.moved.notify_waiters();
+        Ok(id)
+    }
+}
+
+impl TransferSlot {
+    /// Hold the guard of the task, returning the slot for the task to keep
+    pub(crate) fn hold(self: Arc<Self>, guard: TaskGuard) -> Arc<Self> {
+        *self.current.lock().unwrap() = Some(guard);
+        self.register();
+        self
+    }
+
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&TaskGuard) -> R) -> R {
+        f(self.current.lock().unwrap().as_ref().expect("the guard is held before the task runs"))
+    }
+
+    /// Resolves once the context the task belongs to is cancelled, following it across transfers
+    pub(crate) async fn cancelled(self: Arc<Self>) {
+        loop {
+            // enabled before reading the current context, so a transfer in between is not missed
+            let mut moved = std::pin::pin!(self.moved.notified());
+            moved.as_mut().enable();
+            let (context, cancelled) = self.with(|guard| (guard.inner.clone(), guard.inner.cancelled()));
+            tokio::select! {
+                _ = cancelled => {
+                    if self.with(|guard| Arc::ptr_eq(&guard.inner, &context)) {
+                        return;
+                    }
+                },
+                _ = moved => {},
+            }
+        }
+    }
+
+    /// Let `transfer_task` find the task in the registry of its current context
+    fn register(self: &Arc<Self>) {
+        self.with(|guard| {
+            if let Some(task) = guard.inner.tasks.lock().unwrap().get_mut(&guard.id) {
+                task.transfer = Some(Arc::downgrade(self));
+            }
+        });
+    }
+}
+
+impl ContextInner {
+    #[cfg(test)]
+    fn live_task_ids(&self) -> Vec<u64> {
+        self.tasks.lock().unwrap().keys().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn transferred_task_follows_the_new_context() {
+        let mut old = Context::new();
+        let new = Context::new();
+        let (handle, id) = old.spawn_transferable(std::future::pending::<()>()).unwrap();
+        assert_eq!(old.transfer_task(id + 1000, &new), Err(TransferError::UnknownTask(id + 1000)));
+        let moved = old.transfer_task(id, &new).unwrap();
+        assert!(old.inner.live_task_ids().is_empty());
+        assert_eq!(new.inner.live_task_ids(), vec![moved]);
+        assert_eq!(old.transfer_task(id, &new), Err(TransferError::UnknownTask(id)));
+
+        drop(old);
+        tokio::task::yield_now().await;
+        assert!(!handle.is_finished());
+        drop(new);
+        assert_eq!(handle.await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn transferable_tasks_are_run_like_other_tasks() {
+        let mut old = Context::builder().capacity(1).build();
+        let new = Context::new();
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let (handle, id) = old.spawn_transferable(async move { rx.await.is_ok() }).unwrap();
+        old.transfer_task(id, &new).unwrap();
+        tx.send(()).unwrap();
+        assert_eq!(handle.await.unwrap(), Some(true));
+        assert_eq!(new.stats_epoch().counts.completed, 1);
+        assert_eq!(old.stats_epoch().counts.spawned, 1);
+
+        let (handle, _) = old.spawn_transferable(async { panic!("boom") }).unwrap();
+        assert!(handle.await.unwrap_err().is_panic());
+        assert_eq!(old.stats_epoch().counts.panicked, 1);
+    }
+}
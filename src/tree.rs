@@ -0,0 +1,333 @@
+//! Snapshot of a context tree for debugging and ops endpoints.
+//!
+//! With the `serde` feature the snapshot types implement `Serialize`. The serialized field names form a schema that
+//! dashboards can rely on; it is versioned by `TREE_SCHEMA_VERSION`, which is bumped whenever a field is renamed or
+//! removed. Adding fields does not bump the version. Schema version 1:
+//!
+//! ```text
+//! ContextTree   { schema_version: u32, root: ContextNode }
+//! ContextNode   { id: u64, name: string | null, status: "Active" | "Cancelled",
+//!                 cancellation_cause: string | null, cancellation_details: CauseDetails | null,
+//!                 live_tasks: u64, deadline_in_ms: u64 | null,
+//!                 memory_reserved: u64, subtree_memory_reserved: u64,
+//!                 tasks: [TaskNode], children: [ContextNode] }
+//! TaskNode      { id: u64, name: string | null, spawned_at: SpawnLocation, stalled_for_ms: u64 | null,
+//!                 progress: { value: u64, state: string | null } | null,
+//!                 cancel_scope: "Full" | "OwnContextOnly" | { Ancestor: u64 },
+//!                 poll_count: u64, poll_time_us: u64 }    (poll_count and poll_time_us with the poll-time feature)
+//! SpawnLocation { file: string, line: u32, column: u32 }
+//! CauseDetails  { context: u64, task_id: u64, spawned_at: SpawnLocation }    (for "Panic")
+//!               | { reason: string }                                         (for "Emergency")
+//! ```
+//!
+//! `cancellation_cause` is the name of the `CancellationCause` variant, see `CancellationCause::kind`. Causes that
+//! carry data have it in `cancellation_details`, which is null for the others.
+use std::fmt;
+use std::panic::Location;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::time::Instant;
+
+use crate::progress::ProgressReceiver;
+use crate::{CancelScope, CancellationCause, Context, ContextInner, ProgressValue};
+
+/// Version of the serialized `ContextTree` schema
+pub const TREE_SCHEMA_VERSION: u32 = 1;
+
+/// Process wide unique identifier of a context
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
+pub struct ContextId(u64);
+
+impl ContextId {
+    pub(crate) fn next() -> ContextId {
+        static NEXT_CONTEXT_ID: AtomicU64 = AtomicU64::new(1);
+        ContextId(NEXT_CONTEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// The id as a number
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for ContextId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ctx-{}", self.0)
+    }
+}
+
+/// What the registry of a context knows about one of its live tasks
+pub(crate) struct TaskInfo {
+    pub(crate) name: Option<Arc<str>>,
+    pub(crate) location: &'static Location<'static>,
+    /// Set when the context detects stalls, see `StallDetector::stamp`
+    pub(crate) last_poll: Option<Arc<AtomicU64>>,
+    /// Set for tasks spawned with `Context::spawn_monitored`
+    pub(crate) progress: Option<ProgressReceiver>,
+    pub(crate) cancel_scope: CancelScope,
+    /// Set for tasks spawned with `Context::spawn_transferable`
+    pub(crate) transfer: Option<std::sync::Weak<crate::transfer::TransferSlot>>,
+    #[cfg(feature = "poll-time")]
+    pub(crate) poll_time: Arc<crate::poll_time::PollCounters>,
+}
+
+/// Snapshot of a context and all its descendants, created by `Context::tree`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ContextTree {
+    /// Always `TREE_SCHEMA_VERSION`
+    pub schema_version: u32,
+    pub root: ContextNode,
+}
+
+/// Whether a context is still running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ContextStatus {
+    Active,
+    Cancelled,
+}
+
+/// Snapshot of one context
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ContextNode {
+    pub id: ContextId,
+    pub name: Option<Arc<str>>,
+    pub status: ContextStatus,
+    /// Serialized as `cancellation_cause` and `cancellation_details`, see the module docs
+    #[cfg_attr(feature = "serde", serde(flatten, serialize_with = "serialize_cause"))]
+    pub cancellation_cause: Option<CancellationCause>,
+    pub live_tasks: usize,
+    /// Milliseconds left until the deadline of the context itself, 0 once it has passed
+    pub deadline_in_ms: Option<u64>,
+    /// Bytes reserved with `Context::reserve_memory` in the context itself
+    pub memory_reserved: u64,
+    /// Bytes reserved in the context and its descendants
+    pub subtree_memory_reserved: u64,
+    pub tasks: Vec<TaskNode>,
+    pub children: Vec<ContextNode>,
+}
+
+/// Write a cause in the stable form of the schema: its kind as a string, and its data as a separate object
+#[cfg(feature = "serde")]
+fn serialize_cause<S: serde::Serializer>(cause: &Option<CancellationCause>, serializer: S) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeMap;
+
+    #[derive(serde::Serialize)]
+    struct PanicDetails<'a> {
+        context: ContextId,
+        task_id: u64,
+        spawned_at: &'a SpawnLocation,
+    }
+
+    #[derive(serde::Serialize)]
+    struct EmergencyDetails<'a> {
+        reason: &'a str,
+    }
+
+    let mut map = serializer.serialize_map(Some(2))?;
+    map.serialize_entry("cancellation_cause", &cause.as_ref().map(CancellationCause::kind))?;
+    match cause {
+        Some(CancellationCause::Panic { context, task_id, spawned_at }) => {
+            map.serialize_entry("cancellation_details", &PanicDetails { context: *context, task_id: *task_id, spawned_at })?
+        }
+        Some(CancellationCause::Emergency { reason }) => {
+            map.serialize_entry("cancellation_details", &EmergencyDetails { reason })?
+        }
+        _ => map.serialize_entry("cancellation_details", &None::<()>)?,
+    }
+    map.end()
+}
+
+/// Snapshot of one live task
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TaskNode {
+    /// Identifier of the task, unique within the process
+    pub id: u64,
+    pub name: Option<Arc<str>>,
+    pub spawned_at: SpawnLocation,
+    /// How long the task has not been polled, if that is longer than the stall threshold of its context
+    pub stalled_for_ms: Option<u64>,
+    /// Latest value published by a task spawned with `Context::spawn_monitored`
+    pub progress: Option<ProgressValue>,
+    /// Which cancellation the task listens to, see `Context::spawn_with_cancel_scope`
+    pub cancel_scope: CancelScope,
+    /// Number of polls of the task so far, with the `poll-time` feature
+    #[cfg(feature = "poll-time")]
+    pub poll_count: u64,
+    /// Time spent polling the task so far, with the `poll-time` feature
+    #[cfg(feature = "poll-time")]
+    pub poll_time_us: u64,
+}
+
+/// Source location a task was spawned from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SpawnLocation {
+    pub file: &'static str,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl From<&'static Location<'static>> for SpawnLocation {
+    fn from(location: &'static Location<'static>) -> Self {
+        SpawnLocation {
+            file: location.file(),
+            line: location.line(),
+            column: location.column(),
+        }
+    }
+}
+
+impl fmt::Display for SpawnLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+impl ContextInner {
+    fn snapshot(&self, now: Instant) -> ContextNode {
+        let mut tasks: Vec<TaskNode> = self
+            .tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, info)| TaskNode {
+                id: *id,
+                name: info.name.clone(),
+                spawned_at: info.location.into(),
+                stalled_for_ms: self
+                    .stall
+                    .as_ref()
+                    .zip(info.last_poll.as_deref())
+                    .and_then(|(stall, last_poll)| stall.stalled_for(last_poll, now))
+                    .map(|stalled_for| stalled_for.as_millis() as u64),
+                progress: info.progress.as_ref().map(|progress| progress.borrow().clone()),
+                cancel_scope: info.cancel_scope,
+                #[cfg(feature = "poll-time")]
+                poll_count: info.poll_time.get().polls,
+                #[cfg(feature = "poll-time")]
+                poll_time_us: info.poll_time.get().busy.as_micros() as u64,
+            })
+            .collect();
+        tasks.sort_by_key(|task| task.id);
+        let cause = self.cause();
+        let memory = self.memory.stats();
+        ContextNode {
+            id: self.id,
+            name: self.name.clone(),
+            status: if cause.is_some() { ContextStatus::Cancelled } else { ContextStatus::Active },
+            cancellation_cause: cause,
+            live_tasks: tasks.len(),
+            deadline_in_ms: self.deadline.map(|deadline| deadline.saturating_duration_since(now).as_millis() as u64),
+            memory_reserved: memory.reserved,
+            subtree_memory_reserved: memory.subtree_reserved,
+            tasks,
+            children: self.live_children().iter().map(|child| child.snapshot(now)).collect(),
+        }
+    }
+}
+
+impl Context {
+    /// Identifier of this context
+    pub fn id(&self) -> ContextId {
+        self.inner.id
+    }
+
+    /// Name given to this context with `ContextBuilder::name`
+    pub fn name(&self) -> Option<&str> {
+        self.inner.name.as_deref()
+    }
+
+    /// Snapshot of this context, its live tasks and all its descendants.
+    ///
+    /// With the `serde` feature the result can be serialized directly, e.g. to serve it from a debug endpoint.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let mut root = Context::builder().name("root").build();
+    /// root.spawn_named("listener", async move { /* accept connections */ });
+    /// for task in root.tree().root.tasks {
+    ///     println!("{:?} spawned at {}", task.name, task.spawned_at);
+    /// }
+    /// ```
+    pub fn tree(&self) -> ContextTree {
+        ContextTree {
+            schema_version: TREE_SCHEMA_VERSION,
+            root: self.inner.snapshot(Instant::now()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn tree_lists_children_and_live_tasks() {
+        let mut root = Context::builder().name("root").build();
+        let mut child = Context::builder().name("child").build_child(&mut root);
+        let _idle = child.spawn_named("idle", std::future::pending::<()>());
+        let line = line!() - 1;
+
+        let tree = root.tree();
+        assert_eq!(tree.schema_version, TREE_SCHEMA_VERSION);
+        assert_eq!(tree.root.name.as_deref(), Some("root"));
+        assert_eq!(tree.root.status, ContextStatus::Active);
+        assert_eq!(tree.root.children.len(), 1);
+        let node = &tree.root.children[0];
+        assert_eq!(node.id, child.id());
+        assert_eq!(node.live_tasks, 1);
+        assert_eq!(node.tasks[0].name.as_deref(), Some("idle"));
+        assert_eq!(node.tasks[0].spawned_at.file, file!());
+        assert_eq!(node.tasks[0].spawned_at.line, line);
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn tree_serializes_with_stable_field_names() {
+        let mut root = Context::builder().name("root").build();
+        let child = root.new_child_context();
+        drop(child);
+        let _child = root.new_child_context();
+        let json = serde_json::to_value(root.tree()).unwrap();
+        assert_eq!(json["schema_version"], 1);
+        assert_eq!(json["root"]["name"], "root");
+        assert_eq!(json["root"]["status"], "Active");
+        assert_eq!(json["root"]["live_tasks"], 0);
+        assert!(json["root"]["deadline_in_ms"].is_null());
+        assert_eq!(json["root"]["children"][0]["cancellation_cause"], serde_json::Value::Null);
+        assert_eq!(json["root"]["children"][0]["cancellation_details"], serde_json::Value::Null);
+        assert_eq!(json["root"]["children"].as_array().unwrap().len(), 1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn panic_cause_serializes_as_kind_and_details() {
+        let mut root = Context::builder().panic_policy(crate::PanicPolicy::CancelContext).build();
+        let task = root.spawn(async { panic!("boom") });
+        let _ = task.await;
+        let Some(CancellationCause::Panic { task_id, spawned_at, .. }) = root.cancellation_cause() else {
+            panic!("not cancelled by the panic");
+        };
+        let json = serde_json::to_value(root.tree()).unwrap();
+        assert_eq!(json["root"]["cancellation_cause"], "Panic");
+        assert_eq!(json["root"]["cancellation_details"]["context"], root.id().as_u64());
+        assert_eq!(json["root"]["cancellation_details"]["task_id"], task_id);
+        assert_eq!(json["root"]["cancellation_details"]["spawned_at"]["line"], spawned_at.line);
+        assert_eq!(json["root"]["cancellation_details"].as_object().unwrap().len(), 3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn emergency_cause_serializes_its_reason() {
+        let root = Context::new();
+        root.emergency_cancel("watchdog");
+        let json = serde_json::to_value(root.tree()).unwrap();
+        assert_eq!(json["root"]["cancellation_cause"], "Emergency");
+        assert_eq!(json["root"]["cancellation_details"], serde_json::json!({ "reason": "watchdog" }));
+    }
+}
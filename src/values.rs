@@ -0,0 +1,156 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+use crate::Context;
+
+/// Typed key of a context-local value.
+///
+/// Keys are identified by their name together with the value type, so two keys with the same name and type refer to
+/// the same value.
+/// ```rust
+/// use tokio_tree_context::{Context, ContextLocalKey};
+///
+/// static REQUEST_ID: ContextLocalKey<u64> = ContextLocalKey::new("request_id");
+///
+/// let ctx = Context::new();
+/// ctx.set_value(&REQUEST_ID, 42);
+/// assert_eq!(ctx.value(&REQUEST_ID).as_deref(), Some(&42));
+/// ```
+pub struct ContextLocalKey<T> {
+    name: &'static str,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T> ContextLocalKey<T> {
+    /// Create a key with the given name
+    pub const fn new(name: &'static str) -> Self {
+        ContextLocalKey { name, _value: PhantomData }
+    }
+
+    /// Name of the key
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// Identity of a context-local key, regardless of its value type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyId {
+    name: &'static str,
+    type_id: TypeId,
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Object safe view of any `ContextLocalKey<T>`, used to list keys of different value types together
+pub trait ContextKeyErase: sealed::Sealed {
+    /// Identity of the key
+    fn key_id(&self) -> KeyId;
+}
+
+impl<T> sealed::Sealed for ContextLocalKey<T> {}
+
+impl<T: 'static> ContextKeyErase for ContextLocalKey<T> {
+    fn key_id(&self) -> KeyId {
+        KeyId {
+            name: self.name,
+            type_id: TypeId::of::<T>(),
+        }
+    }
+}
+
+/// Values stored in a context
+#[derive(Default)]
+pub(crate) struct Values {
+    values: Mutex<HashMap<KeyId, Arc<dyn Any + Send + Sync>>>,
+}
+
+impl Values {
+    pub(crate) fn copy_from(&self, other: &Values, keep: impl Fn(&KeyId) -> bool) {
+        let copied = other.values.lock().unwrap().iter().filter(|(key, _)| keep(key)).map(|(key, value)| (*key, value.clone())).collect();
+        *self.values.lock().unwrap() = copied;
+    }
+}
+
+impl Context {
+    /// Store a value in this context, replacing the previous value of the key
+    pub fn set_value<T: Send + Sync + 'static>(&self, key: &ContextLocalKey<T>, value: T) {
+        self.inner.values.values.lock().unwrap().insert(key.key_id(), Arc::new(value));
+    }
+
+    /// The value of the key stored in this context
+    pub fn value<T: Send + Sync + 'static>(&self, key: &ContextLocalKey<T>) -> Option<Arc<T>> {
+        let value = self.inner.values.values.lock().unwrap().get(&key.key_id())?.clone();
+        value.downcast().ok()
+    }
+
+    /// Remove the value of the key from this context, returning it
+    pub fn remove_value<T: Send + Sync + 'static>(&self, key: &ContextLocalKey<T>) -> Option<Arc<T>> {
+        let value = self.inner.values.values.lock().unwrap().remove(&key.key_id())?;
+        value.downcast().ok()
+    }
+
+    /// Create a child context that starts with the values of the listed keys, and no others.
+    ///
+    /// Children created with `new_child_context` start without any values, so values used as implementation
+    /// details of one scope do not leak into the contexts it hands out.
+    /// ```rust
+    /// use tokio_tree_context::{Context, ContextLocalKey};
+    ///
+    /// static TENANT: ContextLocalKey<String> = ContextLocalKey::new("tenant");
+    /// static DB_POOL_SIZE: ContextLocalKey<usize> = ContextLocalKey::new("db_pool_size");
+    ///
+    /// let mut ctx = Context::new();
+    /// ctx.set_value(&TENANT, "acme".to_string());
+    /// ctx.set_value(&DB_POOL_SIZE, 8);
+    /// let child = ctx.with_parent_values_inherited(&[&TENANT]);
+    /// assert!(child.value(&TENANT).is_some());
+    /// assert!(child.value(&DB_POOL_SIZE).is_none());
+    /// ```
+    pub fn with_parent_values_inherited(&mut self, keys: &[&dyn ContextKeyErase]) -> Context {
+        let keys: Vec<KeyId> = keys.iter().map(|key| key.key_id()).collect();
+        let child = self.new_child_context();
+        child.inner.values.copy_from(&self.inner.values, |key| keys.contains(key));
+        child
+    }
+
+    /// Create a child context that starts with all values of this context
+    pub fn inherit_all_values(&mut self) -> Context {
+        let child = self.new_child_context();
+        child.inner.values.copy_from(&self.inner.values, |_| true);
+        child
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static NAME: ContextLocalKey<String> = ContextLocalKey::new("name");
+    static SECRET: ContextLocalKey<u32> = ContextLocalKey::new("secret");
+    static SECRET_AS_STRING: ContextLocalKey<String> = ContextLocalKey::new("secret");
+
+    #[test]
+    fn values_are_inherited_selectively() {
+        let mut ctx = Context::new();
+        ctx.set_value(&NAME, "root".to_string());
+        ctx.set_value(&SECRET, 7);
+        assert!(ctx.value(&SECRET_AS_STRING).is_none());
+
+        assert!(ctx.new_child_context().value(&NAME).is_none());
+        let selective = ctx.with_parent_values_inherited(&[&NAME]);
+        assert_eq!(selective.value(&NAME).as_deref().map(String::as_str), Some("root"));
+        assert!(selective.value(&SECRET).is_none());
+
+        let all = ctx.inherit_all_values();
+        assert_eq!(all.value(&SECRET).as_deref(), Some(&7));
+        // children get their own copy
+        all.set_value(&SECRET, 8);
+        assert_eq!(ctx.remove_value(&SECRET).as_deref(), Some(&7));
+        assert!(ctx.value(&SECRET).is_none());
+    }
+}
@@ -0,0 +1,169 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::Context;
+
+/// Reason `Context::wait_for` gave up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WaitError<E> {
+    /// The context was cancelled
+    Cancelled,
+    /// The effective deadline of the context passed
+    DeadlineExceeded,
+    /// The condition failed
+    Condition(E),
+}
+
+impl<E: fmt::Display> fmt::Display for WaitError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WaitError::Cancelled => write!(f, "context is cancelled"),
+            WaitError::DeadlineExceeded => write!(f, "deadline exceeded"),
+            WaitError::Condition(error) => write!(f, "condition failed: {}", error),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for WaitError<E> {}
+
+/// What a condition checked by `Context::wait_for` returns: `Option<T>`, or `Result<Option<T>, E>` for a check that
+/// can fail. None means not yet.
+pub trait WaitOutcome {
+    type Value;
+    type Error;
+
+    fn into_outcome(self) -> Result<Option<Self::Value>, Self::Error>;
+}
+
+impl<T> WaitOutcome for Option<T> {
+    type Value = T;
+    type Error = Infallible;
+
+    fn into_outcome(self) -> Result<Option<T>, Infallible> {
+        Ok(self)
+    }
+}
+
+impl<T, E> WaitOutcome for Result<Option<T>, E> {
+    type Value = T;
+    type Error = E;
+
+    fn into_outcome(self) -> Result<Option<T>, E> {
+        self
+    }
+}
+
+impl Context {
+    /// Check `condition` every `interval` until it yields a value, the context is cancelled or its effective deadline
+    /// passes. The first check happens right away. A check that hangs is abandoned as the context gives up.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(ctx: Context) {
+    /// let ready = ctx.wait_for(Duration::from_millis(200), || async {
+    ///     std::fs::metadata("/run/sidecar.ready").ok()
+    /// });
+    /// if let Err(e) = ready.await {
+    ///     eprintln!("sidecar not ready: {e}");
+    /// }
+    /// # }
+    /// ```
+    pub async fn wait_for<F, Fut>(&self, interval: Duration, condition: F) -> Result<<Fut::Output as WaitOutcome>::Value, WaitError<<Fut::Output as WaitOutcome>::Error>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future,
+        Fut::Output: WaitOutcome,
+    {
+        self.wait_with_backoff(interval, interval, condition).await
+    }
+
+    /// Like `wait_for`, but double the interval after each unsuccessful check, up to `max_interval`
+    pub async fn wait_with_backoff<F, Fut>(
+        &self,
+        initial_interval: Duration,
+        max_interval: Duration,
+        mut condition: F,
+    ) -> Result<<Fut::Output as WaitOutcome>::Value, WaitError<<Fut::Output as WaitOutcome>::Error>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future,
+        Fut::Output: WaitOutcome,
+    {
+        let deadline = self.inner.effective_deadline();
+        let expired = async move {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+        let mut expired = std::pin::pin!(expired);
+        let mut cancelled = std::pin::pin!(self.inner.cancelled());
+        // the deadline of an ancestor cancels this context as well
+        let gave_up = || match deadline {
+            Some(deadline) if deadline <= Instant::now() => WaitError::DeadlineExceeded,
+            _ => WaitError::Cancelled,
+        };
+        let mut interval = initial_interval;
+        loop {
+            let outcome = tokio::select! {
+                biased;
+                _ = &mut cancelled => return Err(gave_up()),
+                _ = &mut expired => return Err(WaitError::DeadlineExceeded),
+                outcome = condition() => outcome.into_outcome(),
+            };
+            match outcome {
+                Ok(Some(value)) => return Ok(value),
+                Ok(None) => {}
+                Err(error) => return Err(WaitError::Condition(error)),
+            }
+            tokio::select! {
+                biased;
+                _ = &mut cancelled => return Err(gave_up()),
+                _ = &mut expired => return Err(WaitError::DeadlineExceeded),
+                _ = tokio::time::sleep(interval) => {},
+            }
+            interval = (interval * 2).min(max_interval.max(initial_interval));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_for_reports_each_way_of_finishing() {
+        let ctx = Context::new();
+        let start = Instant::now();
+        let mut checks = 0;
+        let value = ctx.wait_with_backoff(Duration::from_secs(1), Duration::from_secs(3), || {
+            checks += 1;
+            let ready = (checks == 4).then_some(checks);
+            async move { ready }
+        });
+        assert_eq!(value.await, Ok(4));
+        // 1 + 2 + 3
+        assert_eq!(start.elapsed(), Duration::from_secs(6));
+
+        let failed = ctx.wait_for(Duration::from_secs(1), || async { Err::<Option<()>, _>("unhealthy") });
+        assert_eq!(failed.await, Err(WaitError::Condition("unhealthy")));
+
+        let bounded = Context::builder().timeout(Duration::from_secs(5)).build();
+        let stuck = bounded.wait_for(Duration::from_secs(1), std::future::pending::<Option<()>>);
+        assert_eq!(stuck.await, Err(WaitError::DeadlineExceeded));
+
+        let mut root = Context::new();
+        let child = root.new_child_context();
+        let never = child.wait_for(Duration::from_secs(1), || async { None::<()> });
+        let cancel = async {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            root.cancel();
+        };
+        let (never, _) = tokio::join!(never, cancel);
+        assert_eq!(never, Err(WaitError::Cancelled));
+    }
+}
@@ -0,0 +1,208 @@
+use std::fmt;
+use std::future::Future;
+use std::panic::Location;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{CancelScope, Context, ContextInner, KeepAliveUse, TaskGuard};
+
+/// Outstanding work registered with `Context::register_work`. Dropping it marks the work as done.
+///
+/// While it is alive the work counts as a live task of its context: it shows up in `Context::tree` under its name,
+/// and `when_all_tasks_done` and `cancel_and_wait` wait for it.
+pub struct WorkGuard {
+    /// None if an idle timeout cancelled the context before the work could be registered
+    pub(crate) guard: Option<TaskGuard>,
+}
+
+impl Drop for WorkGuard {
+    fn drop(&mut self) {
+        if let Some(guard) = self.guard.take() {
+            let inner = guard.inner.clone();
+            drop(guard);
+            inner.registered_work.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Returned by `Context::cancel_and_wait` when tasks or registered work are still running after the drain timeout,
+/// or when the drain was cut short by `Context::force_cancel`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrainTimedOut {
+    /// Name of each task or piece of work still running, or where it was spawned if it has no name
+    pub outstanding: Vec<String>,
+    /// True if `force_cancel` ended the drain before the timeout, aborting the outstanding tasks
+    pub forced: bool,
+    /// Keep-alives that extended the drain, see `Context::keep_alive`
+    pub keep_alives: Vec<KeepAliveUse>,
+}
+
+impl fmt::Display for DrainTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = if self.forced { "drain forced" } else { "drain timed out" };
+        write!(f, "{} with {} tasks outstanding: {}", reason, self.outstanding.len(), self.outstanding.join(", "))?;
+        if !self.keep_alives.is_empty() {
+            let held: Duration = self.keep_alives.iter().map(|keep_alive| keep_alive.held_for).sum();
+            write!(f, " ({} keep-alives held for {:?})", self.keep_alives.len(), held)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DrainTimedOut {}
+
+impl Context {
+    /// Count work that does not run as a task of this context, such as a future polled by another executor or a
+    /// callback registered with a C library, until the returned guard is dropped.
+    /// ```rust, no_run
+    /// use tokio_tree_context::Context;
+    ///
+    /// let ctx = Context::new();
+    /// let guard = ctx.register_work("ffi-callback");
+    /// std::thread::spawn(move || {
+    ///     // do the work, then
+    ///     drop(guard);
+    /// });
+    /// ```
+    #[track_caller]
+    pub fn register_work(&self, name: impl Into<String>) -> WorkGuard {
+        self.inner.register_work(Arc::from(name.into()), Location::caller())
+    }
+
+    /// Cancel this context and wait up to `drain_timeout` for the tasks and registered work of it and its
+    /// descendants to finish.
+    ///
+    /// A forgotten `WorkGuard` or a task that ignores cancellation is reported by name instead of hanging the
+    /// shutdown. A `force_cancel` of the context while it drains skips the rest of the timeout.
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    /// use tokio_tree_context::Context;
+    ///
+    /// # async fn example(ctx: Context) {
+    /// if let Err(report) = ctx.cancel_and_wait(Duration::from_secs(5)).await {
+    ///     eprintln!("{report}");
+    /// }
+    /// # }
+    /// ```
+    pub fn cancel_and_wait(self, drain_timeout: Duration) -> impl Future<Output = Result<(), DrainTimedOut>> + Send + Unpin + 'static {
+        let forced = self.inner.forced();
+        let contexts = self.inner.subtree();
+        self.disarm_expectation();
+        drop(self);
+        Box::pin(async move {
+            let root = contexts[0].clone();
+            let drained = async {
+                for inner in &contexts {
+                    inner.clone().tasks_done().await;
+                }
+            };
+            let drain = async {
+                tokio::select! {
+                    _ = drained => return false,
+                    _ = forced => {},
+                }
+                // the forced tasks go away at their next poll, registered work stays
+                for inner in &contexts {
+                    inner.clone().aborted().await;
+                }
+                true
+            };
+            let mut drain = std::pin::pin!(drain);
+            let forced = match tokio::time::timeout(drain_timeout, &mut drain).await {
+                Ok(forced) => forced,
+                // keep-alives extend the drain, each up to its own budget
+                Err(_) => {
+                    let released = async {
+                        for inner in &contexts {
+                            inner.keep_alives_released().await;
+                        }
+                    };
+                    tokio::select! {
+                        forced = drain => forced,
+                        _ = released => root.is_forced(),
+                    }
+                }
+            };
+            let outstanding: Vec<_> = contexts.iter().flat_map(|inner| outstanding(inner)).collect();
+            if outstanding.is_empty() {
+                return Ok(());
+            }
+            let keep_alives = contexts.iter().flat_map(|inner| inner.keep_alive_uses()).collect();
+            Err(DrainTimedOut { outstanding, forced, keep_alives })
+        })
+    }
+}
+
+impl ContextInner {
+    pub(crate) fn register_work(self: &Arc<Self>, name: Arc<str>, location: &'static Location<'static>) -> WorkGuard {
+        // counted before the task is, so `aborted` never waits for registered work
+        self.registered_work.fetch_add(1, Ordering::SeqCst);
+        let guard = TaskGuard::new(self.clone(), Some(name), location, None, CancelScope::Full).ok();
+        if guard.is_none() {
+            self.registered_work.fetch_sub(1, Ordering::SeqCst);
+        }
+        WorkGuard { guard }
+    }
+
+    /// The context and its live descendants, parents before children
+    pub(crate) fn subtree(self: &Arc<Self>) -> Vec<Arc<ContextInner>> {
+        let mut contexts = vec![self.clone()];
+        let mut next = 0;
+        while let Some(inner) = contexts.get(next) {
+            let children = inner.live_children();
+            contexts.extend(children);
+            next += 1;
+        }
+        contexts
+    }
+
+    /// Resolves once only registered work is left
+    async fn aborted(self: Arc<Self>) {
+        loop {
+            let changed = self.tasks_changed.notified();
+            if self.active_tasks.load(Ordering::SeqCst) <= self.registered_work.load(Ordering::SeqCst) {
+                return;
+            }
+            changed.await;
+        }
+    }
+}
+
+pub(crate) fn outstanding(inner: &ContextInner) -> Vec<String> {
+    let tasks = inner.tasks.lock().unwrap();
+    let mut tasks: Vec<_> = tasks.iter().collect();
+    tasks.sort_by_key(|(id, _)| **id);
+    tasks
+        .into_iter()
+        .map(|(_, task)| match &task.name {
+            Some(name) => name.to_string(),
+            None => format!("task spawned at {}:{}", task.location.file(), task.location.line()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn drain_waits_for_registered_work() {
+        let mut root = Context::new();
+        let child = root.new_child_context();
+        let guard = child.register_work("callback");
+        assert_eq!(root.tree().root.children[0].tasks[0].name.as_deref(), Some("callback"));
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            drop(guard);
+        });
+        let started = tokio::time::Instant::now();
+        assert_eq!(root.cancel_and_wait(Duration::from_secs(5)).await, Ok(()));
+        assert_eq!(started.elapsed(), Duration::from_secs(1));
+
+        let root = Context::new();
+        std::mem::forget(root.register_work("forgotten"));
+        let report = root.cancel_and_wait(Duration::from_secs(1)).await.unwrap_err();
+        assert_eq!(report.outstanding, vec!["forgotten".to_string()]);
+    }
+}
@@ -0,0 +1,119 @@
+//! Helpers that are documented as cancel safe are polled part of the way, dropped, and called again: no message,
+//! notification or permit may be lost. Also checks that the future and stream types are `Unpin`.
+use std::future::Future;
+use std::marker::PhantomPinned;
+use std::pin::pin;
+use std::task::Poll;
+use std::time::Duration;
+use tokio_tree_context::{
+    CancellationSignal, CollectingHandle, Context, EventStream, InlineHandle, Messages, OwnedHandle,
+    ScopedOneshotReceiver, TakeUntilCancelled, TaskHandle, UnorderedResults,
+};
+
+/// Poll `future` once and drop it, returning its output if it was ready
+async fn poll_once<F: Future>(future: F) -> Option<F::Output> {
+    let mut future = pin!(future);
+    std::future::poll_fn(|cx| match future.as_mut().poll(cx) {
+        Poll::Ready(output) => Poll::Ready(Some(output)),
+        Poll::Pending => Poll::Ready(None),
+    })
+    .await
+}
+
+fn assert_unpin<T: Unpin>(_: &T) {}
+
+#[test]
+fn future_types_are_unpin() {
+    fn unpin<T: Unpin>() {}
+    unpin::<CancellationSignal>();
+    unpin::<TaskHandle<PhantomPinned>>();
+    unpin::<OwnedHandle<PhantomPinned>>();
+    unpin::<InlineHandle<PhantomPinned>>();
+    unpin::<CollectingHandle<PhantomPinned>>();
+    unpin::<UnorderedResults<PhantomPinned>>();
+    unpin::<EventStream>();
+    unpin::<Messages<()>>();
+    unpin::<TakeUntilCancelled<PhantomPinned>>();
+    unpin::<ScopedOneshotReceiver<PhantomPinned>>();
+}
+
+#[tokio::test]
+async fn context_waiters_are_unpin() {
+    let mut root = Context::new();
+    let child = root.new_child_context();
+    assert_unpin(&child.as_ref().cancelled());
+    assert_unpin(&child.as_ref().closed_with_id());
+    assert_unpin(&child.await_cancellation_with_cause());
+    assert_unpin(&child.when_all_tasks_done());
+    assert_unpin(&child.when_n_tasks_remain(0));
+    assert_unpin(&child.await_n_tasks_active(1));
+    assert_unpin(&child.cancel_and_wait(Duration::from_secs(1)));
+}
+
+#[tokio::test]
+async fn dropped_receives_lose_nothing() {
+    let mut ctx = Context::new();
+    let (tx, mut rx) = ctx.channel(1);
+    assert_eq!(poll_once(rx.recv()).await, None);
+    tx.send(1).await.unwrap();
+    assert_eq!(rx.recv().await, Some(1));
+
+    let (watch_tx, mut watch_rx) = ctx.watch(0);
+    assert_eq!(poll_once(watch_rx.changed()).await, None);
+    watch_tx.send(1).unwrap();
+    assert_eq!(watch_rx.changed().await, Ok(()));
+
+    let (oneshot_tx, mut oneshot_rx) = ctx.oneshot();
+    assert_eq!(poll_once(&mut oneshot_rx).await, None);
+    oneshot_tx.send(2).unwrap();
+    assert_eq!(oneshot_rx.recv().await, Ok(2));
+
+    let mut messages = ctx.messages::<u32>();
+    assert_eq!(poll_once(messages.recv()).await, None);
+    ctx.broadcast(3u32);
+    assert_eq!(messages.recv().await, Some(3));
+
+    let mut events = ctx.subscribe_events();
+    assert!(poll_once(events.recv()).await.is_none());
+    let mut spawner = ctx.new_child_context();
+    spawner.spawn(async {});
+    assert!(events.recv().await.is_some());
+}
+
+#[tokio::test]
+async fn dropped_reserve_gives_the_slot_back() {
+    let ctx = Context::new();
+    let (tx, mut rx) = ctx.channel(1);
+    tx.send(1).await.unwrap();
+    // the buffer is full, so the reserve waits and is dropped
+    assert!(poll_once(tx.reserve()).await.is_none());
+    assert_eq!(rx.recv().await, Some(1));
+    let permit = poll_once(tx.reserve()).await.unwrap().unwrap();
+    drop(permit);
+    tx.try_send(2).unwrap();
+    assert_eq!(rx.recv().await, Some(2));
+}
+
+#[tokio::test]
+async fn dropped_notified_passes_the_notification_on() {
+    let ctx = Context::new();
+    let notify = ctx.scoped_notify();
+    let mut first = Box::pin(notify.notified());
+    assert!(poll_once(first.as_mut()).await.is_none());
+    notify.notify_one();
+    drop(first);
+    assert_eq!(poll_once(notify.notified()).await, Some(Ok(())));
+}
+
+#[tokio::test]
+async fn dropped_next_result_keeps_the_result() {
+    let mut ctx = Context::new();
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    let mut collecting = ctx.spawn_collecting(async move {
+        let _ = rx.await;
+        5
+    });
+    assert_eq!(poll_once(collecting.next_result()).await, None);
+    tx.send(()).unwrap();
+    assert_eq!(collecting.next_result().await, Some(5));
+}
@@ -0,0 +1,89 @@
+//! Races between cancellation and context/task creation, repeated many times on real threads.
+//!
+//! Set `STRESS_ITERATIONS` to run more iterations, e.g. `STRESS_ITERATIONS=10000000 cargo test --release --test stress`.
+//! `CANCEL_SPREAD_BOUND_MS` sets how far apart the first and last child of a wide tree may observe its cancellation.
+use std::time::{Duration, Instant};
+use tokio_tree_context::{CancellationCause, Context};
+
+fn iterations() -> usize {
+    std::env::var("STRESS_ITERATIONS").ok().and_then(|n| n.parse().ok()).unwrap_or(10_000)
+}
+
+#[test]
+fn cancel_during_child_creation() {
+    for _ in 0..iterations() {
+        let mut root = Context::new();
+        let mut parent = root.new_child_context();
+        let canceller = std::thread::spawn(move || drop(root));
+        let child = parent.new_child_context();
+        canceller.join().unwrap();
+        assert_eq!(child.cancellation_cause(), Some(CancellationCause::Parent));
+    }
+}
+
+#[test]
+fn cancel_during_spawn() {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_time().build().unwrap();
+    runtime.block_on(async {
+        for _ in 0..iterations() {
+            let mut root = Context::new();
+            let mut parent = root.new_child_context();
+            let canceller = std::thread::spawn(move || drop(root));
+            let task = parent.spawn(std::future::pending::<()>());
+            canceller.join().unwrap();
+            let outcome = tokio::time::timeout(Duration::from_secs(5), task).await.expect("task survived cancellation");
+            assert_eq!(outcome.unwrap(), None);
+        }
+    });
+}
+
+#[test]
+fn concurrent_cancel_from_two_threads() {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_time().build().unwrap();
+    runtime.block_on(async {
+        for _ in 0..iterations() {
+            let mut root = Context::new();
+            let mut parent = root.new_child_context();
+            let mut child = parent.new_child_context();
+            let task = child.spawn(std::future::pending::<()>());
+            let first = std::thread::spawn(move || drop(root));
+            let second = std::thread::spawn(move || drop(parent));
+            first.join().unwrap();
+            second.join().unwrap();
+            let outcome = tokio::time::timeout(Duration::from_secs(5), task).await.expect("task survived cancellation");
+            assert_eq!(outcome.unwrap(), None);
+            assert_eq!(child.cancellation_cause(), Some(CancellationCause::Parent));
+        }
+    });
+}
+
+#[test]
+fn wide_tree_observes_cancellation_together() {
+    let bound = std::env::var("CANCEL_SPREAD_BOUND_MS").ok().and_then(|ms| ms.parse().ok()).unwrap_or(500);
+    let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(4).enable_time().build().unwrap();
+    runtime.block_on(async {
+        let mut root = match std::env::var("CANCEL_BATCH_SIZE").ok().and_then(|size| size.parse().ok()) {
+            Some(size) => Context::builder().cancel_batch_size(size).build(),
+            None => Context::new(),
+        };
+        let children = root.new_child_contexts(10_000);
+        let observers: Vec<_> = children
+            .iter()
+            .map(|child| {
+                let cancelled = child.as_ref().cancelled();
+                tokio::spawn(async move {
+                    cancelled.await;
+                    Instant::now()
+                })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        root.cancel();
+        let mut observed = Vec::with_capacity(observers.len());
+        for observer in observers {
+            observed.push(observer.await.unwrap());
+        }
+        let spread = *observed.iter().max().unwrap() - *observed.iter().min().unwrap();
+        assert!(spread < Duration::from_millis(bound), "children observed the cancellation {:?} apart", spread);
+    });
+}